@@ -0,0 +1,30 @@
+//! Play chess in the terminal, against another human or with `--engine white|black` against
+//! the library's engine (searching `--depth` plies). See the `cli` module of the library.
+
+use std::io;
+use std::process;
+
+use vprytz_chess::cli::{run, Options};
+use vprytz_chess::Color;
+
+const USAGE: &str = "usage: play [--engine white|black] [--depth <plies>]";
+
+fn main() {
+    let mut options = Options::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next().as_deref()) {
+            ("--engine", Some("white")) => options.engine = Some(Color::White),
+            ("--engine", Some("black")) => options.engine = Some(Color::Black),
+            ("--depth", Some(depth)) if depth.parse::<u32>().is_ok_and(|depth| depth > 0) => {
+                options.depth = depth.parse().unwrap();
+            }
+            _ => {
+                eprintln!("{}", USAGE);
+                process::exit(2);
+            }
+        }
+    }
+
+    run(io::stdin().lock(), io::stdout(), options);
+}
@@ -0,0 +1,31 @@
+//! Full-screen chess in the terminal for two players, optionally with a clock given like the
+//! PGN TimeControl tag (`--time 300+2`). See the `tui` module of the library.
+
+use std::process;
+
+use vprytz_chess::clock::TimeControl;
+use vprytz_chess::Game;
+
+const USAGE: &str = "usage: tui [--time <seconds>+<increment>]";
+
+fn main() {
+    let mut time_control = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (
+            arg.as_str(),
+            args.next().as_deref().and_then(TimeControl::parse),
+        ) {
+            ("--time", Some(control)) => time_control = Some(control),
+            _ => {
+                eprintln!("{}", USAGE);
+                process::exit(2);
+            }
+        }
+    }
+
+    if let Err(error) = vprytz_chess::tui::run(Game::new(), time_control) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
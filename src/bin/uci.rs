@@ -0,0 +1,8 @@
+//! UCI chess engine, reading commands from stdin and answering on stdout.
+//! See the `uci` module of the library for details.
+
+use std::io;
+
+fn main() {
+    vprytz_chess::uci::run(io::stdin().lock(), io::stdout());
+}
@@ -0,0 +1,8 @@
+//! XBoard (CECP) chess engine, reading commands from stdin and answering on stdout.
+//! See the `xboard` module of the library for details.
+
+use std::io;
+
+fn main() {
+    vprytz_chess::xboard::run(io::stdin().lock(), io::stdout());
+}
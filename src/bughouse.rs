@@ -0,0 +1,441 @@
+//! Bughouse, Crazyhouse for two teams of two on two boards.
+//!
+//! Each team plays white on one board and black on the other, so that the partner of white on
+//! board A is black on board B and the other way around. The pieces a player captures go to
+//! the pocket of their partner, who may drop them on their own board (see the
+//! [`variant`](crate::variant) module for how drops work). Both boards are played at the same
+//! time, each with its own clock, and the match ends as soon as one of the boards does: the
+//! team of the winner on that board wins the match.
+//!
+//! Matches can be written to BPGN, the PGN of Bughouse, where the moves of both boards are
+//! written in the order they were made and numbered by board, e.g. "1A. e4 1B. d4 1a. e5".
+//!
+//! # Examples
+//! ```
+//! use vprytz_chess::bughouse::{Board, BughouseMatch};
+//! use vprytz_chess::{Color, PieceType};
+//!
+//! let mut bughouse = BughouseMatch::new();
+//! for (board, mv) in [(Board::A, "e2e4"), (Board::A, "d7d5"), (Board::A, "e4d5")] {
+//!     bughouse.make_move(board, mv).unwrap();
+//! }
+//! // white on board A took a pawn, which goes to black on board B
+//! let pocket = bughouse.get_game(Board::B).get_pocket(Color::Black);
+//! assert_eq!(pocket.count(PieceType::Pawn), 1);
+//! ```
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::clock::{Clock, TimeControl, TimeSource};
+use crate::variant::{color_index, Pocket, Variant};
+use crate::{zobrist, Color, Game, GameState, Move, Outcome, PieceType};
+
+/// The two boards of a match.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Board {
+    A,
+    B,
+}
+
+impl Board {
+    /// Returns the other board.
+    pub fn other(self) -> Board {
+        match self {
+            Board::A => Board::B,
+            Board::B => Board::A,
+        }
+    }
+
+    fn index(self) -> usize {
+        match self {
+            Board::A => 0,
+            Board::B => 1,
+        }
+    }
+}
+
+/// The two teams of a match, the first plays white on board A and black on board B.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Team {
+    First,
+    Second,
+}
+
+impl Team {
+    /// Returns the team of the player with the given color on the board.
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::bughouse::{Board, Team};
+    /// use vprytz_chess::Color;
+    /// assert_eq!(Team::of(Board::B, Color::Black), Team::First);
+    /// ```
+    pub fn of(board: Board, color: Color) -> Team {
+        match (board, color) {
+            (Board::A, Color::White) | (Board::B, Color::Black) => Team::First,
+            _ => Team::Second,
+        }
+    }
+}
+
+/// How a match ended: the board that decided it, how the game on it ended and the winning
+/// team (None for a draw).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BughouseResult {
+    pub board: Board,
+    pub outcome: Outcome,
+    pub winner: Option<Team>,
+}
+
+/// A Bughouse match, two Crazyhouse games where captured pieces go to the partner.
+/// See the module documentation.
+#[derive(Clone)]
+pub struct BughouseMatch {
+    games: [Game; 2],
+    // names of white and black on board A and B
+    players: [[String; 2]; 2],
+    time_control: Option<TimeControl>,
+    // the moves of both boards in the order they were made, in SAN, with the time the player
+    // had left after the move
+    moves: Vec<(Board, Color, u32, String, Option<Duration>)>,
+}
+
+impl Default for BughouseMatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BughouseMatch {
+    /// Creates a match without clocks, both boards in the starting position.
+    pub fn new() -> BughouseMatch {
+        let game = Game::new_variant(Variant::Crazyhouse);
+        BughouseMatch {
+            games: [game.clone(), game],
+            players: Default::default(),
+            time_control: None,
+            moves: Vec::new(),
+        }
+    }
+
+    /// Creates a match where both boards are played with the time control, each board with
+    /// its own clock. The clocks start right away.
+    pub fn with_clocks(control: TimeControl) -> BughouseMatch {
+        let mut bughouse = BughouseMatch::new();
+        for game in bughouse.games.iter_mut() {
+            game.set_clock(Clock::new(control.clone()));
+        }
+        bughouse.time_control = Some(control);
+        bughouse
+    }
+
+    /// Like `with_clocks()`, with clocks reading the time from the given source.
+    pub fn with_time_source(control: TimeControl, source: Arc<dyn TimeSource>) -> BughouseMatch {
+        let mut bughouse = BughouseMatch::new();
+        for game in bughouse.games.iter_mut() {
+            game.set_clock(Clock::with_time_source(control.clone(), source.clone()));
+        }
+        bughouse.time_control = Some(control);
+        bughouse
+    }
+
+    /// Sets the name of a player, written to BPGN.
+    pub fn set_player(&mut self, board: Board, color: Color, name: &str) {
+        self.players[board.index()][color_index(color)] = name.to_string();
+    }
+
+    /// Returns the game played on the board.
+    pub fn get_game(&self, board: Board) -> &Game {
+        &self.games[board.index()]
+    }
+
+    /// Plays a move written in coordinate notation (e.g. "e2e4" or "N@f3") on the board and
+    /// returns the resulting state of the game on it. Returns None if the match is over or the
+    /// move isn't legal.
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::bughouse::{Board, BughouseMatch};
+    /// let mut bughouse = BughouseMatch::new();
+    /// assert!(bughouse.make_move(Board::B, "g1f3").is_some());
+    /// assert!(bughouse.make_move(Board::B, "g1f3").is_none()); // black to move on board B
+    /// ```
+    pub fn make_move(&mut self, board: Board, text: &str) -> Option<GameState> {
+        let mv = self.games[board.index()].parse_uci_move(text)?;
+        self.apply_move(board, mv)
+    }
+
+    /// Plays a legal move on the board and returns the resulting state of the game on it,
+    /// or None if the match is over.
+    pub fn apply_move(&mut self, board: Board, mv: Move) -> Option<GameState> {
+        if self.get_result().is_some() {
+            return None;
+        }
+        let game = &mut self.games[board.index()];
+        let color = game.active_color;
+        let number = game.fullmove_number;
+        let san = game.move_to_san(mv);
+        let pocket = game.get_pocket(color);
+
+        let state = game.apply_move(mv);
+        let remaining = game.get_clock().map(|clock| clock.remaining(color));
+        self.moves.push((board, color, number, san, remaining));
+
+        // the captured piece went to our pocket, but belongs to our partner
+        if let Some(piece) = captured(pocket, game.get_pocket(color)) {
+            game.pockets[color_index(color)].remove(piece);
+            game.hash = zobrist::hash(game);
+
+            let partner = &mut self.games[board.other().index()];
+            partner.pockets[color_index(color.opposite())].add(piece);
+            partner.hash = zobrist::hash(partner);
+            partner.update_state();
+        }
+
+        // the other board stops as well once the match is over
+        if self.get_result().is_some() {
+            for game in self.games.iter_mut() {
+                if let Some(clock) = game.clock.as_mut() {
+                    clock.stop();
+                }
+            }
+        }
+        Some(state)
+    }
+
+    /// Returns how the match ended, or None if both games are still going on. A player who
+    /// runs out of time always loses, since their partner could have given them the pieces
+    /// to mate with.
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::bughouse::{Board, BughouseMatch, Team};
+    /// let mut bughouse = BughouseMatch::new();
+    /// for mv in ["f2f3", "e7e5", "g2g4", "d8h4"] {
+    ///     bughouse.make_move(Board::B, mv);
+    /// }
+    /// assert_eq!(bughouse.get_result().unwrap().winner, Some(Team::First));
+    /// ```
+    pub fn get_result(&self) -> Option<BughouseResult> {
+        [Board::A, Board::B].into_iter().find_map(|board| {
+            let game = self.get_game(board);
+            let outcome = match game.get_outcome()? {
+                Outcome::TimeoutDraw => Outcome::Timeout {
+                    winner: game.active_color.opposite(),
+                },
+                outcome => outcome,
+            };
+            Some(BughouseResult {
+                board,
+                outcome,
+                winner: outcome.winner().map(|winner| Team::of(board, winner)),
+            })
+        })
+    }
+
+    /// Writes the match in BPGN. The result is given for the first team, so "1-0" means
+    /// that white on board A and black on board B won.
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::bughouse::{Board, BughouseMatch};
+    /// let mut bughouse = BughouseMatch::new();
+    /// bughouse.make_move(Board::A, "e2e4");
+    /// bughouse.make_move(Board::B, "d2d4");
+    /// bughouse.make_move(Board::A, "e7e5");
+    /// assert!(bughouse.to_bpgn().ends_with("\n\n1A. e4 1B. d4 1a. e5 *\n"));
+    /// ```
+    pub fn to_bpgn(&self) -> String {
+        let result = match self.get_result() {
+            Some(BughouseResult {
+                winner: Some(Team::First),
+                ..
+            }) => "1-0",
+            Some(BughouseResult {
+                winner: Some(Team::Second),
+                ..
+            }) => "0-1",
+            Some(_) => "1/2-1/2",
+            None => "*",
+        };
+        let name = |board: Board, color: Color| match self.players[board.index()]
+            [color_index(color)]
+        .as_str()
+        {
+            "" => "?",
+            name => name,
+        };
+        let time_control = self
+            .time_control
+            .as_ref()
+            .map_or("-".to_string(), |control| control.to_string());
+
+        let mut bpgn = String::new();
+        for (tag, value) in [
+            ("Event", "?"),
+            ("Site", "?"),
+            ("Date", "????.??.??"),
+            ("WhiteA", name(Board::A, Color::White)),
+            ("BlackA", name(Board::A, Color::Black)),
+            ("WhiteB", name(Board::B, Color::White)),
+            ("BlackB", name(Board::B, Color::Black)),
+            ("TimeControl", &time_control),
+            ("Result", result),
+        ] {
+            bpgn.push_str(&format!("[{} \"{}\"]\n", tag, value.replace('"', "'")));
+        }
+        bpgn.push('\n');
+
+        // the moves, with lines kept under 80 characters
+        let mut tokens: Vec<String> = self
+            .moves
+            .iter()
+            .map(|(board, color, number, san, remaining)| {
+                let board = match (board, color) {
+                    (Board::A, Color::White) => "A",
+                    (Board::A, Color::Black) => "a",
+                    (Board::B, Color::White) => "B",
+                    (Board::B, Color::Black) => "b",
+                };
+                match remaining {
+                    Some(time) => {
+                        format!("{}{}. {}{{{:.1}}}", number, board, san, time.as_secs_f64())
+                    }
+                    None => format!("{}{}. {}", number, board, san),
+                }
+            })
+            .collect();
+        tokens.push(result.to_string());
+
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > 79 {
+                bpgn.push_str(&line);
+                bpgn.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        bpgn.push_str(&line);
+        bpgn.push('\n');
+        bpgn
+    }
+}
+
+// the piece that was added to the pocket by a move, if any
+fn captured(before: Pocket, after: Pocket) -> Option<PieceType> {
+    Pocket::PIECES
+        .into_iter()
+        .find(|piece| after.count(*piece) > before.count(*piece))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualTime;
+
+    fn play(bughouse: &mut BughouseMatch, moves: &[(Board, &str)]) {
+        for (board, mv) in moves {
+            assert!(bughouse.make_move(*board, mv).is_some(), "{}", mv);
+        }
+    }
+
+    #[test]
+    fn captures_go_to_the_partner() {
+        let mut bughouse = BughouseMatch::new();
+        play(
+            &mut bughouse,
+            &[
+                (Board::A, "e2e4"),
+                (Board::A, "d7d5"),
+                (Board::A, "e4d5"),
+                (Board::B, "e2e4"),
+                (Board::A, "d8d5"),
+            ],
+        );
+        let a = bughouse.get_game(Board::A);
+        let b = bughouse.get_game(Board::B);
+        assert!(a.get_pocket(Color::White).is_empty());
+        assert!(a.get_pocket(Color::Black).is_empty());
+        assert_eq!(b.get_pocket(Color::Black).count(PieceType::Pawn), 1);
+        assert_eq!(b.get_pocket(Color::White).count(PieceType::Pawn), 1);
+
+        // and can be dropped there
+        play(&mut bughouse, &[(Board::B, "P@d6"), (Board::B, "P@d3")]);
+        assert!(bughouse
+            .get_game(Board::B)
+            .get_pocket(Color::White)
+            .is_empty());
+        // the hash knows about the pockets, like after any other move
+        let b = bughouse.get_game(Board::B);
+        let fen = "rnbqkbnr/pppppppp/3p4/8/4P3/3P4/PPPP1PPP/RNBQKBNR[] b KQkq - 2 2";
+        let same = Game::from_fen_variant(fen, Variant::Crazyhouse).unwrap();
+        assert_eq!(b.get_zobrist_hash(), same.get_zobrist_hash());
+    }
+
+    #[test]
+    fn match_ends_with_either_board() {
+        let mut bughouse = BughouseMatch::new();
+        play(
+            &mut bughouse,
+            &[
+                (Board::A, "f2f3"),
+                (Board::A, "e7e5"),
+                (Board::B, "e2e4"),
+                (Board::A, "g2g4"),
+                (Board::A, "d8h4"),
+            ],
+        );
+        assert_eq!(
+            bughouse.get_result(),
+            Some(BughouseResult {
+                board: Board::A,
+                outcome: Outcome::Checkmate {
+                    winner: Color::Black
+                },
+                winner: Some(Team::Second),
+            })
+        );
+        assert!(bughouse.make_move(Board::B, "e7e5").is_none());
+        assert!(bughouse.to_bpgn().contains("[Result \"0-1\"]"));
+    }
+
+    #[test]
+    fn clocks_and_bpgn() {
+        let time = Arc::new(ManualTime::default());
+        let control = TimeControl::fischer(Duration::from_secs(60), Duration::from_secs(1));
+        let mut bughouse = BughouseMatch::with_time_source(control, time.clone());
+        bughouse.set_player(Board::A, Color::White, "Alice");
+        bughouse.set_player(Board::B, Color::Black, "Bob");
+
+        time.advance(Duration::from_secs(10));
+        play(&mut bughouse, &[(Board::A, "e2e4"), (Board::B, "d2d4")]);
+        // the clocks of both boards ran at the same time
+        let clock = |board| bughouse.get_game(board).get_clock().unwrap();
+        assert_eq!(
+            clock(Board::A).remaining(Color::White),
+            Duration::from_secs(51)
+        );
+        assert_eq!(
+            clock(Board::B).remaining(Color::White),
+            Duration::from_secs(51)
+        );
+        assert_eq!(bughouse.get_result(), None);
+
+        // black on board B runs out of time, while black on board A still has some
+        time.advance(Duration::from_secs(30));
+        play(&mut bughouse, &[(Board::A, "e7e5")]);
+        time.advance(Duration::from_secs(30));
+        let result = bughouse.get_result().unwrap();
+        assert_eq!(result.board, Board::B);
+        assert_eq!(result.winner, Some(Team::Second));
+
+        let bpgn = bughouse.to_bpgn();
+        assert!(bpgn.starts_with("[Event \"?\"]\n"));
+        assert!(
+            bpgn.contains("[WhiteA \"Alice\"]\n[BlackA \"?\"]\n[WhiteB \"?\"]\n[BlackB \"Bob\"]\n")
+        );
+        assert!(bpgn.contains("[TimeControl \"60+1\"]\n[Result \"0-1\"]\n"));
+        assert!(bpgn.ends_with("\n\n1A. e4{51.0} 1B. d4{51.0} 1a. e5{31.0} 0-1\n"));
+    }
+}
@@ -0,0 +1,257 @@
+//! Chess960 (Fischer Random Chess).
+//!
+//! The pieces of the back rank are shuffled, with the bishops on squares of different colors
+//! and the king somewhere between the rooks, giving 960 possible starting positions. They
+//! are numbered 0 to 959 as described by Reinhard Scharnagl, where number 518 is the usual
+//! starting position.
+//!
+//! Castling works the same as in normal chess in that the king ends up on the C or G file
+//! and the rook next to it, wherever they started. All squares between the king and its
+//! destination and between the rook and its destination must be empty (except for the king
+//! and rook themselves), and the king may not be in check or pass through an attacked square.
+//!
+//! # Examples
+//! ```
+//! use vprytz_chess::Game;
+//! let game = Game::new_chess960(0).unwrap();
+//! assert_eq!(game.to_fen(), "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1");
+//! ```
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{zobrist, Color, Game, Piece, PieceType, BOARD_SIZE};
+
+/// Number of the usual starting position.
+pub const STANDARD_INDEX: u32 = 518;
+
+// where the knights go among the five squares left after placing the bishops and the queen
+const KNIGHT_PLACEMENTS: [(usize, usize); 10] = [
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (0, 4),
+    (1, 2),
+    (1, 3),
+    (1, 4),
+    (2, 3),
+    (2, 4),
+    (3, 4),
+];
+
+/// Returns the back rank (from the A file to the H file) of the starting position with the
+/// given number, or None if the number isn't below 960.
+/// # Examples
+/// ```
+/// use vprytz_chess::chess960::back_rank;
+/// use vprytz_chess::PieceType::*;
+/// assert_eq!(back_rank(518), Some([Rook, Knight, Bishop, Queen, King, Bishop, Knight, Rook]));
+/// ```
+pub fn back_rank(index: u32) -> Option<[PieceType; BOARD_SIZE]> {
+    if index >= 960 {
+        return None;
+    }
+
+    let mut rank: [Option<PieceType>; BOARD_SIZE] = [None; BOARD_SIZE];
+    let mut n = index as usize;
+
+    // light squared bishop on b, d, f or h and dark squared bishop on a, c, e or g
+    rank[n % 4 * 2 + 1] = Some(PieceType::Bishop);
+    n /= 4;
+    rank[n % 4 * 2] = Some(PieceType::Bishop);
+    n /= 4;
+
+    // the rest is placed on the empty squares, counting from the A file
+    let empty = |rank: &[Option<PieceType>; BOARD_SIZE]| -> Vec<usize> {
+        (0..BOARD_SIZE).filter(|col| rank[*col].is_none()).collect()
+    };
+
+    let queen = empty(&rank)[n % 6];
+    rank[queen] = Some(PieceType::Queen);
+    n /= 6;
+
+    let squares = empty(&rank);
+    let (first, second) = KNIGHT_PLACEMENTS[n];
+    rank[squares[first]] = Some(PieceType::Knight);
+    rank[squares[second]] = Some(PieceType::Knight);
+
+    // the king goes between the rooks
+    for (col, piece) in
+        empty(&rank)
+            .into_iter()
+            .zip([PieceType::Rook, PieceType::King, PieceType::Rook])
+    {
+        rank[col] = Some(piece);
+    }
+
+    Some(rank.map(|piece| piece.expect("every square is filled")))
+}
+
+impl Game {
+    /// Creates a Chess960 game starting from the position with the given number (0 to 959).
+    pub fn new_chess960(index: u32) -> Option<Game> {
+        let mut game = Game::new();
+        game.setup_chess960_board(index)?;
+        Some(game)
+    }
+
+    /// Creates a Chess960 game starting from a random position.
+    pub fn random_chess960() -> Game {
+        // no need for a good random number generator to pick one of 960 positions
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+        let seed = zobrist::mix(time ^ zobrist::mix(COUNTER.fetch_add(1, Ordering::Relaxed)));
+
+        Game::new_chess960((seed % 960) as u32).expect("index is below 960")
+    }
+
+    /// Resets the board to the Chess960 starting position with the given number, like
+    /// `setup_initial_board()` does for the usual starting position. Returns None (leaving
+    /// the game as it was) if the number isn't below 960.
+    pub fn setup_chess960_board(&mut self, index: u32) -> Option<()> {
+        let rank = back_rank(index)?;
+
+        self.setup_initial_board();
+        for (col, piece) in rank.into_iter().enumerate() {
+            for (row, color) in [(0, Color::Black), (BOARD_SIZE - 1, Color::White)] {
+                self.board[row][col] = Some(Piece {
+                    color,
+                    piece,
+                    untouched: true,
+                });
+            }
+        }
+        self.chess960 = true;
+        self.hash = zobrist::hash(self);
+        Some(())
+    }
+
+    /// Turns Chess960 castling notation on or off. Castling always follows the Chess960
+    /// rules (which are the same as the normal rules for the usual starting position), but in
+    /// Chess960 it has to be written as the king taking its own rook, e.g. "e1h1" for UCI.
+    pub fn set_chess960(&mut self, chess960: bool) {
+        self.chess960 = chess960;
+    }
+
+    /// Returns true if castling is written the Chess960 way, see `set_chess960()`.
+    pub fn is_chess960(&self) -> bool {
+        self.chess960
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::STARTING_FEN;
+    use crate::perft;
+
+    #[test]
+    fn scharnagl_numbering() {
+        let fen = |index| Game::new_chess960(index).unwrap().to_fen();
+
+        assert_eq!(
+            Game::new_chess960(STANDARD_INDEX).unwrap().to_fen(),
+            STARTING_FEN
+        );
+        assert!(fen(0).starts_with("bbqnnrkr/"));
+        assert!(fen(959).starts_with("rkrnnqbb/"));
+        assert!(Game::new_chess960(960).is_none());
+
+        // all 960 are different and follow the rules
+        let mut seen = std::collections::HashSet::new();
+        for index in 0..960 {
+            let rank = back_rank(index).unwrap();
+            assert!(seen.insert(rank));
+            let cols = |piece| (0..BOARD_SIZE).filter(move |col| rank[*col] == piece);
+            let bishops: Vec<usize> = cols(PieceType::Bishop).collect();
+            let rooks: Vec<usize> = cols(PieceType::Rook).collect();
+            let king = cols(PieceType::King).next().unwrap();
+            assert_ne!(bishops[0] % 2, bishops[1] % 2);
+            assert!(rooks[0] < king && king < rooks[1]);
+        }
+    }
+
+    #[test]
+    fn random_positions() {
+        let game = Game::random_chess960();
+        assert!(game.is_chess960());
+        assert!(game.get_legal_moves().len() >= 18);
+    }
+
+    #[test]
+    fn castling() {
+        // king on b1 and rook on a1, castling queenside moves the king to c1 and the rook to d1
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/8/RK5R w HA - 0 1").unwrap();
+        assert!(game.is_chess960());
+        assert_eq!(game.to_fen(), "4k3/8/8/8/8/8/8/RK5R w KQ - 0 1");
+
+        let long = game.parse_uci_move("b1a1").unwrap();
+        // written as the king taking its rook, b1c1 is a normal king move
+        assert_eq!(game.move_to_uci(long), "b1a1");
+        assert_ne!(game.parse_uci_move("b1c1"), Some(long));
+        game.apply_move(long);
+        assert_eq!(game.to_fen(), "4k3/8/8/8/8/8/8/2KR3R b - - 1 1");
+
+        // the king may not pass through an attacked square on its way to g1
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/1K1r3R w H - 0 1").unwrap();
+        assert!(game.parse_uci_move("b1h1").is_none());
+
+        // the king doesn't move at all, only the rook
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/8/R1K4R w A - 0 1").unwrap();
+        game.apply_move(game.parse_uci_move("c1a1").unwrap());
+        assert_eq!(game.to_fen(), "4k3/8/8/8/8/8/8/2KR3R b - - 1 1");
+
+        // the king ends up where it started, so the only way to write this is king takes rook
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/8/5KR1 w G - 0 1").unwrap();
+        game.make_move("F1".to_string(), "G1".to_string());
+        assert_eq!(game.to_fen(), "4k3/8/8/8/8/8/8/5RK1 b - - 1 1");
+    }
+
+    #[test]
+    fn x_fen_and_shredder_fen() {
+        // the a1 rook has moved to b1's neighbour, so the inner rook needs its file
+        let game = Game::from_fen("rk2r3/8/8/8/8/8/8/RRK5 w Bkq - 0 1").unwrap();
+        assert_eq!(game.to_fen(), "rk2r3/8/8/8/8/8/8/RRK5 w Bkq - 0 1");
+        assert_eq!(game.to_shredder_fen(), "rk2r3/8/8/8/8/8/8/RRK5 w Bea - 0 1");
+
+        assert!(Game::from_fen("4k3/8/8/8/8/8/8/4K2R w G - 0 1").is_none());
+    }
+
+    #[test]
+    fn perft_reference_positions() {
+        // from the chess programming wiki, Chess960 perft results
+        for (fen, expected) in [
+            (
+                "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+                [21, 528, 12189],
+            ),
+            (
+                "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+                [21, 807, 18002],
+            ),
+            (
+                "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+                [20, 479, 10471],
+            ),
+            (
+                "qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9",
+                [22, 593, 13440],
+            ),
+            (
+                "1nbbnrkr/p1p1ppp1/3p4/1p3P1p/3Pq2P/8/PPP1P1P1/QNBBNRKR w HFhf - 0 9",
+                [28, 1120, 31058],
+            ),
+            (
+                "qnbnr1kr/ppp1b1pp/4p3/3p1p2/8/2NPP3/PPP1BPPP/QNB1R1KR w HEhe - 1 9",
+                [29, 899, 26578],
+            ),
+        ] {
+            let mut game = Game::from_fen(fen).unwrap();
+            for (depth, nodes) in expected.iter().enumerate() {
+                assert_eq!(perft(&mut game, depth as u32 + 1), *nodes, "{}", fen);
+            }
+        }
+    }
+}
@@ -0,0 +1,446 @@
+//! Playing chess in the terminal, two humans against each other or a human against the
+//! library's own engine. Used by the `play` binary.
+//!
+//! The board is printed after every move, with White at the bottom unless it has been flipped.
+//! Moves can be typed as two squares ("e2 e4" or "e2-e4"), in coordinate notation ("e2e4",
+//! "e7e8q") or in Standard Algebraic Notation ("e4", "Nf3", "O-O"). The other commands are:
+//!
+//! | **Command**       | **Description**                                                             |
+//! | ----------------- | --------------------------------------------------------------------------- |
+//! | `undo`            | Takes back the last move (against the engine, the last move of your own).   |
+//! | `flip`            | Turns the board around.                                                     |
+//! | `fen`             | Prints the position as FEN.                                                 |
+//! | `pgn save <file>` | Saves the game as PGN.                                                      |
+//! | `load <file>`     | Continues the game saved in a PGN (or FEN) file.                            |
+//! | `resign`          | Gives up the game for the player to move.                                   |
+//! | `draw`            | Claims a draw by repetition or the fifty-move rule, or offers one.          |
+//! | `new`             | Starts a new game.                                                          |
+//! | `help`            | Lists the commands.                                                         |
+//! | `quit`            | Leaves.                                                                     |
+//!
+//! A draw offer is accepted by the other player typing `draw` too, and declined by making a
+//! move. The engine accepts a draw if it doesn't think it is winning.
+//!
+//! # Examples
+//! ```
+//! use vprytz_chess::cli::{run, Options};
+//! let input = "f3\ne5\ng4\nQh4\n";
+//! let output = run(input.as_bytes(), Vec::new(), Options::default());
+//! let output = String::from_utf8(output).unwrap();
+//! assert!(output.contains("0-1 (Black wins by checkmate)"));
+//! ```
+
+use std::fs;
+use std::io::{BufRead, Write};
+
+use crate::render::Style;
+use crate::search::{search, Limits};
+use crate::uci::ENGINE_NAME;
+use crate::{Color, Game, Move, Outcome};
+
+/// How the game in the terminal is played.
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// The color played by the engine, None for two humans.
+    pub engine: Option<Color>,
+    /// How many plies the engine searches.
+    pub depth: u32,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            engine: None,
+            depth: 4,
+        }
+    }
+}
+
+const HELP: &str = "Type a move (e2 e4, e2e4 or Nf3) or one of the commands:
+  undo             take back the last move
+  flip             turn the board around
+  fen              print the position as FEN
+  pgn save <file>  save the game as PGN
+  load <file>      continue a game saved as PGN or FEN
+  resign           give up
+  draw             claim, offer or accept a draw
+  new              start a new game
+  quit             leave";
+
+/// Plays a game with the moves and commands read from `input`, writing the board and messages
+/// to `output`, until the input ends or `quit` is typed. Returns the output, which is handy
+/// when writing to a buffer.
+pub fn run<R: BufRead, W: Write>(input: R, output: W, options: Options) -> W {
+    let mut session = Session::new(output, options);
+    session.show();
+    session.engine_moves();
+
+    for line in input.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        if !session.command(&line) {
+            break;
+        }
+    }
+
+    session.output
+}
+
+struct Session<W> {
+    output: W,
+    options: Options,
+    game: Game,
+    flipped: bool,
+    // set when the game was ended by resigning or agreeing to a draw: the result token and why
+    result: Option<(&'static str, String)>,
+    // the player who offered a draw, until the other player moves
+    draw_offer: Option<Color>,
+}
+
+impl<W: Write> Session<W> {
+    fn new(output: W, options: Options) -> Session<W> {
+        Session {
+            flipped: options.engine == Some(Color::White),
+            output,
+            options,
+            game: Game::new(),
+            result: None,
+            draw_offer: None,
+        }
+    }
+
+    fn say(&mut self, text: &str) {
+        // there is nobody to tell if the terminal went away
+        let _ = writeln!(self.output, "{}", text);
+        let _ = self.output.flush();
+    }
+
+    // handles one line of input, returning false on quit
+    fn command(&mut self, line: &str) -> bool {
+        let line = line.trim();
+        let (command, args) = match line.split_once(' ') {
+            Some((command, args)) => (command, args.trim()),
+            None => (line, ""),
+        };
+
+        match command {
+            "" => {}
+            "quit" | "exit" => return false,
+            "help" | "?" => self.say(HELP),
+            "new" => {
+                self.start(Game::new());
+                return true;
+            }
+            "flip" => {
+                self.flipped = !self.flipped;
+                self.show();
+            }
+            "fen" => {
+                let fen = self.game.to_fen();
+                self.say(&fen);
+            }
+            "undo" => self.undo(),
+            "pgn" => match args.split_once(' ') {
+                Some(("save", file)) => self.save(file.trim()),
+                _ => self.say("Usage: pgn save <file>"),
+            },
+            "load" => self.load(args),
+            "resign" if !self.is_over() => {
+                let loser = self.game.get_active_color();
+                let token = match loser {
+                    Color::White => "0-1",
+                    Color::Black => "1-0",
+                };
+                self.finish(
+                    token,
+                    format!("{} wins by resignation", name(loser.opposite())),
+                );
+            }
+            "draw" if !self.is_over() => self.draw(),
+            _ if self.is_over() => self.say("The game is over, type new to start a new one."),
+            _ => match self.parse_move(line) {
+                Some(mv) => {
+                    self.play(mv);
+                    self.engine_moves();
+                }
+                None => self.say(&format!(
+                    "Unknown move or command: {}. Type help for the commands.",
+                    line
+                )),
+            },
+        }
+        true
+    }
+
+    // square pairs and coordinate notation, then SAN
+    fn parse_move(&self, text: &str) -> Option<Move> {
+        let squares: String = text.chars().filter(|c| !matches!(c, ' ' | '-')).collect();
+        self.game
+            .parse_uci_move(&squares)
+            .or_else(|| self.game.parse_san(text))
+    }
+
+    fn play(&mut self, mv: Move) {
+        let san = self.game.move_to_san(mv);
+        let color = self.game.get_active_color();
+        self.game.apply_move(mv);
+        if self.draw_offer == Some(color.opposite()) {
+            self.draw_offer = None;
+        }
+        if self.options.engine == Some(color) {
+            self.say(&format!("{} plays {}", ENGINE_NAME, san));
+        }
+        self.show();
+    }
+
+    fn engine_moves(&mut self) {
+        while !self.is_over() && Some(self.game.get_active_color()) == self.options.engine {
+            let limits = Limits {
+                depth: Some(self.options.depth),
+                ..Limits::default()
+            };
+            match search(&self.game, &limits).best_move {
+                Some(mv) => self.play(mv),
+                None => break,
+            }
+        }
+    }
+
+    fn undo(&mut self) {
+        if self.game.undo_move().is_none() {
+            self.say("There are no moves to take back.");
+            return;
+        }
+        // take back the engine's answer too, and the rest of a Duck chess turn
+        while !self.game.get_move_history().is_empty()
+            && (Some(self.game.get_active_color()) == self.options.engine
+                || self.game.is_duck_to_move())
+        {
+            self.game.undo_move();
+        }
+        self.result = None;
+        self.draw_offer = None;
+        self.show();
+        self.engine_moves();
+    }
+
+    fn save(&mut self, file: &str) {
+        let player = |color| match self.options.engine {
+            Some(engine) if engine == color => ENGINE_NAME,
+            _ => "?",
+        };
+        let mut tags = vec![
+            ("White", player(Color::White)),
+            ("Black", player(Color::Black)),
+        ];
+        if let Some((token, _)) = &self.result {
+            tags.push(("Result", token));
+        }
+        let pgn = self.game.to_pgn(&tags);
+        match fs::write(file, pgn) {
+            Ok(()) => self.say(&format!("Saved the game to {}", file)),
+            Err(error) => self.say(&format!("Couldn't save to {}: {}", file, error)),
+        }
+    }
+
+    fn load(&mut self, file: &str) {
+        let text = match fs::read_to_string(file) {
+            Ok(text) => text,
+            Err(error) => return self.say(&format!("Couldn't read {}: {}", file, error)),
+        };
+        match Game::from_pgn(&text).or_else(|| Game::from_fen(text.trim())) {
+            Some(game) => self.start(game),
+            None => self.say(&format!("{} isn't a game in PGN or FEN", file)),
+        }
+    }
+
+    fn start(&mut self, game: Game) {
+        self.game = game;
+        self.result = None;
+        self.draw_offer = None;
+        self.show();
+        self.engine_moves();
+    }
+
+    fn draw(&mut self) {
+        let color = self.game.get_active_color();
+        if self.draw_offer == Some(color.opposite()) {
+            return self.finish("1/2-1/2", "Draw by agreement".to_string());
+        }
+        if self.game.halfmove_clock >= 100 {
+            return self.finish("1/2-1/2", "Draw by the fifty-move rule".to_string());
+        }
+        if self.repetitions() >= 3 {
+            return self.finish("1/2-1/2", "Draw by threefold repetition".to_string());
+        }
+        if self.options.engine == Some(color.opposite()) {
+            let limits = Limits {
+                depth: Some(self.options.depth),
+                ..Limits::default()
+            };
+            // the score is from our side, the engine takes the draw unless it is ahead
+            if search(&self.game, &limits).score >= 0 {
+                return self.finish("1/2-1/2", format!("{} accepts the draw", ENGINE_NAME));
+            }
+            return self.say(&format!("{} declines the draw.", ENGINE_NAME));
+        }
+        self.draw_offer = Some(color);
+        self.say(&format!(
+            "{} offers a draw, {} can accept by typing draw or decline by moving.",
+            name(color),
+            name(color.opposite())
+        ));
+    }
+
+    // how many times the current position has been on the board
+    fn repetitions(&self) -> usize {
+        let hash = self.game.get_zobrist_hash();
+        1 + self
+            .game
+            .history
+            .iter()
+            .rev()
+            .take(self.game.halfmove_clock as usize)
+            .filter(|(_, snapshot)| snapshot.hash == hash)
+            .count()
+    }
+
+    fn is_over(&self) -> bool {
+        self.result.is_some() || self.game.get_outcome().is_some()
+    }
+
+    fn finish(&mut self, token: &'static str, reason: String) {
+        self.say(&format!("{} ({})", token, reason));
+        self.result = Some((token, reason));
+    }
+
+    // prints the board and whose turn it is, or how the game ended
+    fn show(&mut self) {
+        let board = board_text(&self.game, self.flipped);
+        self.say(&board);
+        let color = name(self.game.get_active_color());
+        match self.game.get_outcome() {
+            Some(outcome) => {
+                let token = crate::pgn::result_token(Some(outcome));
+                self.say(&format!("{} ({})", token, describe(outcome)));
+            }
+            None if self.game.is_duck_to_move() => {
+                self.say(&format!("{} to move the duck.", color))
+            }
+            None if self.game.is_check() => self.say(&format!("{} to move, check!", color)),
+            None => self.say(&format!("{} to move.", color)),
+        }
+    }
+}
+
+pub(crate) fn name(color: Color) -> &'static str {
+    match color {
+        Color::White => "White",
+        Color::Black => "Black",
+    }
+}
+
+pub(crate) fn describe(outcome: Outcome) -> String {
+    match outcome {
+        Outcome::Checkmate { winner } => format!("{} wins by checkmate", name(winner)),
+        Outcome::Stalemate => "Draw by stalemate".to_string(),
+        Outcome::Timeout { winner } => format!("{} wins on time", name(winner)),
+        Outcome::TimeoutDraw => "Draw, time ran out but there was no way to mate".to_string(),
+        Outcome::VariantWin { winner } => format!("{} wins", name(winner)),
+        Outcome::VariantDraw => "Draw".to_string(),
+    }
+}
+
+// the board with coordinates, and the pieces in hand in Crazyhouse
+fn board_text(game: &Game, flipped: bool) -> String {
+    let mut text = game.render(&Style {
+        flipped,
+        ..Style::default()
+    });
+    let fen = game.to_fen();
+    if let Some((_, pockets)) = fen
+        .split_whitespace()
+        .next()
+        .and_then(|board| board.split_once('['))
+    {
+        text.push_str(&format!("\nIn hand: {}", pockets.trim_end_matches(']')));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{board_text, run, Options};
+    use crate::variant::Variant;
+    use crate::{Color, Game};
+
+    fn session(input: &str, options: Options) -> String {
+        String::from_utf8(run(input.as_bytes(), Vec::new(), options)).unwrap()
+    }
+
+    #[test]
+    fn board() {
+        let mut game = Game::new();
+        game.make_move("E2".to_string(), "E4".to_string());
+        let text = board_text(&game, false);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "8 r n b q k b n r");
+        assert_eq!(lines[4], "4 . . . . P . . .");
+        assert_eq!(lines[8], "  a b c d e f g h");
+
+        let text = board_text(&game, true);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "1 R N B K Q B N R");
+        assert_eq!(lines[8], "  h g f e d c b a");
+
+        let game = Game::from_fen_variant("4k3/8/8/8/8/8/8/4K3[Qp] w - - 0 1", Variant::Crazyhouse)
+            .unwrap();
+        assert!(board_text(&game, false).ends_with("\n  a b c d e f g h\nIn hand: Qp"));
+    }
+
+    #[test]
+    fn moves_and_commands() {
+        let output = session(
+            "e2 e4\ne7-e5\nNf3\nxyz\nundo\nfen\nd2d4\ndraw\nresign\nNc6\n",
+            Options::default(),
+        );
+        assert!(output.contains("Black to move."));
+        assert!(output.contains("Unknown move or command: xyz."));
+        assert!(output.contains("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2"));
+        assert!(output.contains("Black offers a draw"));
+        assert!(output.contains("1-0 (White wins by resignation)"));
+        assert!(output.ends_with("The game is over, type new to start a new one.\n"));
+
+        // the draw is accepted by the other player, and a repeated position can be claimed
+        let output = session("e4\ndraw\ne5\ndraw\n", Options::default());
+        assert!(output.contains("1/2-1/2 (Draw by agreement)"));
+        let output = session(
+            "Nf3\nNf6\nNg1\nNg8\nNf3\nNf6\nNg1\nNg8\ndraw\n",
+            Options::default(),
+        );
+        assert!(output.contains("1/2-1/2 (Draw by threefold repetition)"));
+    }
+
+    #[test]
+    fn against_the_engine() {
+        let options = Options {
+            engine: Some(Color::Black),
+            depth: 1,
+        };
+        let output = session("e4\nundo\nfen\n", options);
+        assert_eq!(output.matches(" plays ").count(), 1);
+        // both the engine's move and ours were taken back
+        assert!(output.ends_with(&format!("{}\n", crate::fen::STARTING_FEN)));
+
+        // playing white, the engine moves first
+        let options = Options {
+            engine: Some(Color::White),
+            depth: 1,
+        };
+        let output = session("", options);
+        assert!(output.contains(" plays "));
+        assert!(output.ends_with("Black to move.\n"));
+    }
+}
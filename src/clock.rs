@@ -0,0 +1,596 @@
+//! Chess clocks.
+//!
+//! A `TimeControl` is made of one or more stages, e.g. "40 moves in 90 minutes, then 30
+//! minutes for the rest of the game". Each stage can add time after every move (Fischer
+//! increment), give back the time used up to some limit (Bronstein delay) or wait a moment
+//! before the clock starts counting down (simple delay).
+//!
+//! A `Clock` set on a `Game` with `Game::set_clock()` ticks for the player to move and is
+//! switched by every move made with `make_move()` or `apply_move()`, and `undo_move()` puts
+//! back the times from before the move taken back. A player whose time runs
+//! out loses, unless the other player can't possibly mate, which makes it a draw (see
+//! `Game::get_outcome()`).
+//!
+//! Clocks read the time from a `TimeSource`, so that a `ManualTime` can be used instead of
+//! the real time in tests.
+//!
+//! # Examples
+//! ```
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//! use vprytz_chess::clock::{Clock, ManualTime, TimeControl};
+//! use vprytz_chess::{Color, Game, Outcome};
+//!
+//! let time = Arc::new(ManualTime::default());
+//! let mut game = Game::new();
+//! game.set_clock(Clock::with_time_source(TimeControl::fischer(Duration::from_secs(60), Duration::from_secs(1)), time.clone()));
+//!
+//! time.advance(Duration::from_secs(10));
+//! game.make_move("E2".to_string(), "E4".to_string());
+//! assert_eq!(game.get_clock().unwrap().remaining(Color::White), Duration::from_secs(51));
+//!
+//! time.advance(Duration::from_secs(60));
+//! assert_eq!(game.get_outcome(), Some(Outcome::Timeout { winner: Color::White }));
+//! ```
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::{Color, Game};
+
+/// Where a clock gets the time from.
+pub trait TimeSource: Send + Sync {
+    /// The time passed since some fixed point, which must never go backwards.
+    fn now(&self) -> Duration;
+}
+
+/// The real time, counted from when it was created.
+pub struct RealTime {
+    start: Instant,
+}
+
+impl Default for RealTime {
+    fn default() -> Self {
+        RealTime {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl TimeSource for RealTime {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// A time that only moves when told to, for tests.
+#[derive(Default)]
+pub struct ManualTime {
+    now: Mutex<Duration>,
+}
+
+impl ManualTime {
+    /// Moves the time forward.
+    pub fn advance(&self, time: Duration) {
+        *self.now.lock().unwrap_or_else(|error| error.into_inner()) += time;
+    }
+}
+
+impl TimeSource for ManualTime {
+    fn now(&self) -> Duration {
+        *self.now.lock().unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+/// What a player gets for each move, on top of the time of the stage.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Bonus {
+    None,
+    /// Fischer increment, added after each move.
+    Increment(Duration),
+    /// Bronstein delay, after each move the time used is given back, but at most this much.
+    Bronstein(Duration),
+    /// Simple (US) delay, the clock waits this long before it starts counting down.
+    Delay(Duration),
+}
+
+/// One stage of a time control.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Stage {
+    /// Number of moves to make in this stage, None for the rest of the game.
+    pub moves: Option<u32>,
+    /// Time added to the clock when the stage starts.
+    pub time: Duration,
+    pub bonus: Bonus,
+}
+
+/// The stages of a time control, played in order. The last stage should have no move limit,
+/// otherwise it is repeated.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimeControl {
+    pub stages: Vec<Stage>,
+}
+
+impl TimeControl {
+    /// The whole game in the given time.
+    pub fn sudden_death(time: Duration) -> TimeControl {
+        TimeControl::single(time, Bonus::None)
+    }
+
+    /// The whole game in the given time, with an increment added after each move.
+    pub fn fischer(time: Duration, increment: Duration) -> TimeControl {
+        TimeControl::single(time, Bonus::Increment(increment))
+    }
+
+    /// The whole game in the given time, with a Bronstein delay.
+    pub fn bronstein(time: Duration, delay: Duration) -> TimeControl {
+        TimeControl::single(time, Bonus::Bronstein(delay))
+    }
+
+    /// The whole game in the given time, with a simple delay.
+    pub fn simple_delay(time: Duration, delay: Duration) -> TimeControl {
+        TimeControl::single(time, Bonus::Delay(delay))
+    }
+
+    fn single(time: Duration, bonus: Bonus) -> TimeControl {
+        TimeControl {
+            stages: vec![Stage {
+                moves: None,
+                time,
+                bonus,
+            }],
+        }
+    }
+
+    /// Reads a time control written like the PGN TimeControl tag, with the stages separated
+    /// by ':' and all times in seconds, e.g. "300+2" or "40/5400+30:1800+30" (40 moves in 90
+    /// minutes then 30 minutes for the rest of the game, with 30 seconds added per move).
+    /// # Examples
+    /// ```
+    /// use std::time::Duration;
+    /// use vprytz_chess::clock::TimeControl;
+    /// let control = TimeControl::parse("300+2").unwrap();
+    /// assert_eq!(control, TimeControl::fischer(Duration::from_secs(300), Duration::from_secs(2)));
+    /// ```
+    pub fn parse(text: &str) -> Option<TimeControl> {
+        let mut stages = Vec::new();
+        for stage in text.trim().split(':') {
+            let (moves, rest) = match stage.split_once('/') {
+                Some((moves, rest)) => (Some(moves.parse().ok()?), rest),
+                None => (None, stage),
+            };
+            let (time, bonus) = match rest.split_once('+') {
+                Some((time, increment)) => (
+                    time,
+                    Bonus::Increment(Duration::from_secs_f64(parse_seconds(increment)?)),
+                ),
+                None => (rest, Bonus::None),
+            };
+            stages.push(Stage {
+                moves,
+                time: Duration::from_secs_f64(parse_seconds(time)?),
+                bonus,
+            });
+        }
+        Some(TimeControl { stages })
+    }
+}
+
+/// Writes the time control like the PGN TimeControl tag, the way `TimeControl::parse()` reads
+/// it. Delays can't be written this way and are left out.
+/// # Examples
+/// ```
+/// use vprytz_chess::clock::TimeControl;
+/// let control = TimeControl::parse("40/5400+30:1800+30").unwrap();
+/// assert_eq!(control.to_string(), "40/5400+30:1800+30");
+/// ```
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, stage) in self.stages.iter().enumerate() {
+            if i > 0 {
+                write!(f, ":")?;
+            }
+            if let Some(moves) = stage.moves {
+                write!(f, "{}/", moves)?;
+            }
+            write!(f, "{}", stage.time.as_secs_f64())?;
+            if let Bonus::Increment(increment) = stage.bonus {
+                write!(f, "+{}", increment.as_secs_f64())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_seconds(text: &str) -> Option<f64> {
+    let seconds: f64 = text.parse().ok()?;
+    if seconds.is_finite() && seconds >= 0.0 {
+        Some(seconds)
+    } else {
+        None
+    }
+}
+
+/// A clock for both players.
+#[derive(Clone)]
+pub struct Clock {
+    control: TimeControl,
+    source: Arc<dyn TimeSource>,
+    // indexed by color, white first
+    remaining: [Duration; 2],
+    stage: [usize; 2],
+    moves_in_stage: [u32; 2],
+    // the player whose time is running, and when their turn started
+    running: Option<Color>,
+    turn_start: Duration,
+}
+
+impl Clock {
+    /// Creates a clock using the real time. The clock doesn't run until it is started.
+    pub fn new(control: TimeControl) -> Clock {
+        Clock::with_time_source(control, Arc::new(RealTime::default()))
+    }
+
+    /// Creates a clock reading the time from the given source.
+    pub fn with_time_source(control: TimeControl, source: Arc<dyn TimeSource>) -> Clock {
+        let time = control
+            .stages
+            .first()
+            .map_or(Duration::ZERO, |stage| stage.time);
+        Clock {
+            control,
+            source,
+            remaining: [time; 2],
+            stage: [0; 2],
+            moves_in_stage: [0; 2],
+            running: None,
+            turn_start: Duration::ZERO,
+        }
+    }
+
+    /// Starts (or restarts) the time of a player, stopping the other one.
+    pub fn start(&mut self, color: Color) {
+        self.stop();
+        self.running = Some(color);
+        self.turn_start = self.source.now();
+    }
+
+    /// Stops the clock, keeping the time used so far in this turn.
+    pub fn stop(&mut self) {
+        if let Some(color) = self.running.take() {
+            self.remaining[index(color)] = self.remaining(color);
+        }
+    }
+
+    /// The player whose time is running, if any.
+    pub fn running(&self) -> Option<Color> {
+        self.running
+    }
+
+    /// The time a player has left.
+    pub fn remaining(&self, color: Color) -> Duration {
+        let remaining = self.remaining[index(color)];
+        if self.running != Some(color) {
+            return remaining;
+        }
+        let used = match self.bonus(color) {
+            Bonus::Delay(delay) => self.turn_time().saturating_sub(delay),
+            _ => self.turn_time(),
+        };
+        remaining.saturating_sub(used)
+    }
+
+    /// True if the player has run out of time.
+    pub fn is_flagged(&self, color: Color) -> bool {
+        self.remaining(color).is_zero()
+    }
+
+    /// The number of moves a player has left until the next stage, None in the last stage.
+    pub fn moves_to_go(&self, color: Color) -> Option<u32> {
+        let moves = self.current_stage(color)?.moves?;
+        Some(moves - self.moves_in_stage[index(color)])
+    }
+
+    /// The increment a player gets after their moves in the current stage.
+    pub fn increment(&self, color: Color) -> Duration {
+        match self.bonus(color) {
+            Bonus::Increment(increment) => increment,
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Ends the turn of the running player and starts the time of the other one,
+    /// like pressing the button of a real clock. Does nothing if the clock isn't running.
+    pub fn press(&mut self) {
+        let color = match self.running {
+            Some(color) => color,
+            None => return,
+        };
+        let spent = self.turn_time();
+        let player = index(color);
+
+        self.remaining[player] = self.remaining(color);
+        if !self.remaining[player].is_zero() {
+            match self.bonus(color) {
+                Bonus::Increment(increment) => self.remaining[player] += increment,
+                Bonus::Bronstein(delay) => self.remaining[player] += spent.min(delay),
+                Bonus::None | Bonus::Delay(_) => {}
+            }
+        }
+
+        // move on to the next stage (or repeat the last one) once its moves are made
+        self.moves_in_stage[player] += 1;
+        if let Some(moves) = self.current_stage(color).and_then(|stage| stage.moves) {
+            if self.moves_in_stage[player] >= moves {
+                self.moves_in_stage[player] = 0;
+                if self.stage[player] + 1 < self.control.stages.len() {
+                    self.stage[player] += 1;
+                }
+                self.remaining[player] += self.control.stages[self.stage[player]].time;
+            }
+        }
+
+        self.running = Some(color.opposite());
+        self.turn_start = self.source.now();
+    }
+
+    // the times left and the stages reached, without the running player
+    pub(crate) fn times(&self) -> ClockTimes {
+        ClockTimes {
+            remaining: [self.remaining(Color::White), self.remaining(Color::Black)],
+            stage: self.stage,
+            moves_in_stage: self.moves_in_stage,
+        }
+    }
+
+    // puts back times saved with times(), and starts the time of the given player
+    pub(crate) fn restore(&mut self, times: ClockTimes, color: Color) {
+        self.remaining = times.remaining;
+        self.stage = times.stage;
+        self.moves_in_stage = times.moves_in_stage;
+        self.running = None;
+        self.start(color);
+    }
+
+    fn turn_time(&self) -> Duration {
+        self.source.now().saturating_sub(self.turn_start)
+    }
+
+    fn current_stage(&self, color: Color) -> Option<&Stage> {
+        self.control.stages.get(self.stage[index(color)])
+    }
+
+    fn bonus(&self, color: Color) -> Bonus {
+        self.current_stage(color)
+            .map_or(Bonus::None, |stage| stage.bonus)
+    }
+}
+
+// the state of a clock at one moment, saved with each move so it can be taken back
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ClockTimes {
+    remaining: [Duration; 2],
+    stage: [usize; 2],
+    moves_in_stage: [u32; 2],
+}
+
+impl fmt::Debug for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Clock")
+            .field("white", &self.remaining(Color::White))
+            .field("black", &self.remaining(Color::Black))
+            .field("running", &self.running)
+            .finish()
+    }
+}
+
+fn index(color: Color) -> usize {
+    match color {
+        Color::White => 0,
+        Color::Black => 1,
+    }
+}
+
+impl<const WIDTH: usize, const HEIGHT: usize> Game<WIDTH, HEIGHT> {
+    /// Sets a clock for the game and starts it for the player to move.
+    pub fn set_clock(&mut self, mut clock: Clock) {
+        clock.start(self.active_color);
+        self.clock = Some(clock);
+    }
+
+    /// Returns the clock of the game, if it has one.
+    pub fn get_clock(&self) -> Option<&Clock> {
+        self.clock.as_ref()
+    }
+
+    /// Removes the clock from the game and returns it.
+    pub fn take_clock(&mut self) -> Option<Clock> {
+        self.clock.take()
+    }
+
+    // true if the player to move has run out of time
+    pub(crate) fn flag_fallen(&self) -> bool {
+        matches!(&self.clock, Some(clock) if clock.is_flagged(self.active_color))
+    }
+
+    // switch the clock after a move, stopping it when the game is over
+    pub(crate) fn press_clock(&mut self) {
+        if let Some(clock) = self.clock.as_mut() {
+            clock.press();
+            if self.state == crate::GameState::GameOver {
+                clock.stop();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Outcome;
+
+    fn secs(seconds: u64) -> Duration {
+        Duration::from_secs(seconds)
+    }
+
+    fn clock(control: TimeControl) -> (Clock, Arc<ManualTime>) {
+        let time = Arc::new(ManualTime::default());
+        let mut clock = Clock::with_time_source(control, time.clone());
+        clock.start(Color::White);
+        (clock, time)
+    }
+
+    #[test]
+    fn bonuses() {
+        // white thinks for 10 seconds, black for 2
+        let play = |control| {
+            let (mut clock, time) = clock(control);
+            time.advance(secs(10));
+            clock.press();
+            time.advance(secs(2));
+            clock.press();
+            (clock.remaining(Color::White), clock.remaining(Color::Black))
+        };
+
+        assert_eq!(
+            play(TimeControl::sudden_death(secs(60))),
+            (secs(50), secs(58))
+        );
+        assert_eq!(
+            play(TimeControl::fischer(secs(60), secs(5))),
+            (secs(55), secs(63))
+        );
+        assert_eq!(
+            play(TimeControl::bronstein(secs(60), secs(5))),
+            (secs(55), secs(60))
+        );
+        assert_eq!(
+            play(TimeControl::simple_delay(secs(60), secs(5))),
+            (secs(55), secs(60))
+        );
+    }
+
+    #[test]
+    fn delay_while_thinking() {
+        let (clock, time) = clock(TimeControl::simple_delay(secs(60), secs(5)));
+
+        time.advance(secs(3));
+        assert_eq!(clock.remaining(Color::White), secs(60));
+        time.advance(secs(4));
+        assert_eq!(clock.remaining(Color::White), secs(58));
+    }
+
+    #[test]
+    fn stages() {
+        let control = TimeControl::parse("2/60+1:30+1").unwrap();
+        assert_eq!(control.stages.len(), 2);
+        let (mut clock, time) = clock(control);
+
+        assert_eq!(clock.moves_to_go(Color::White), Some(2));
+        for _ in 0..4 {
+            time.advance(secs(10));
+            clock.press();
+        }
+        // two moves of 10 seconds with one second back each, and 30 seconds for the next stage
+        assert_eq!(clock.remaining(Color::White), secs(60 - 18 + 30));
+        assert_eq!(clock.moves_to_go(Color::White), None);
+        assert_eq!(clock.increment(Color::White), secs(1));
+
+        assert_eq!(
+            TimeControl::parse("40/5400+30:1800+30").unwrap().stages[1].time,
+            secs(1800)
+        );
+        assert!(TimeControl::parse("40/").is_none());
+        assert!(TimeControl::parse("-5").is_none());
+    }
+
+    #[test]
+    fn flag_fall_in_a_game() {
+        let time = Arc::new(ManualTime::default());
+        let mut game = Game::new();
+        game.set_clock(Clock::with_time_source(
+            TimeControl::sudden_death(secs(60)),
+            time.clone(),
+        ));
+
+        time.advance(secs(30));
+        game.make_move("E2".to_string(), "E4".to_string());
+        assert_eq!(game.get_clock().unwrap().running(), Some(Color::Black));
+
+        time.advance(secs(60));
+        assert_eq!(game.get_game_state(), crate::GameState::GameOver);
+        assert_eq!(
+            game.get_outcome(),
+            Some(Outcome::Timeout {
+                winner: Color::White
+            })
+        );
+        // no more moves once the flag has fallen
+        assert!(game.make_move("E7".to_string(), "E5".to_string()).is_none());
+    }
+
+    #[test]
+    fn taking_back_moves() {
+        let time = Arc::new(ManualTime::default());
+        let mut game = Game::new();
+        game.set_clock(Clock::with_time_source(
+            TimeControl::fischer(secs(60), secs(1)),
+            time.clone(),
+        ));
+
+        time.advance(secs(10));
+        game.make_move("E2".to_string(), "E4".to_string());
+        time.advance(secs(5));
+        game.make_move("E7".to_string(), "E5".to_string());
+        assert_eq!(game.get_clock().unwrap().remaining(Color::Black), secs(56));
+
+        // the increments go, the time spent thinking stays used
+        game.undo_move();
+        let clock = game.get_clock().unwrap();
+        assert_eq!(clock.running(), Some(Color::Black));
+        assert_eq!(clock.remaining(Color::Black), secs(55));
+        game.undo_move();
+        let clock = game.get_clock().unwrap();
+        assert_eq!(clock.running(), Some(Color::White));
+        assert_eq!(clock.remaining(Color::White), secs(50));
+        assert_eq!(clock.remaining(Color::Black), secs(60));
+
+        time.advance(secs(51));
+        assert_eq!(
+            game.get_outcome(),
+            Some(Outcome::Timeout {
+                winner: Color::Black
+            })
+        );
+    }
+
+    #[test]
+    fn flag_fall_against_a_lone_king() {
+        let time = Arc::new(ManualTime::default());
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 0 1").unwrap();
+        game.set_clock(Clock::with_time_source(
+            TimeControl::sudden_death(secs(1)),
+            time.clone(),
+        ));
+
+        // black runs out, but white can still mate with the pawn
+        time.advance(secs(2));
+        assert_eq!(
+            game.get_outcome(),
+            Some(Outcome::Timeout {
+                winner: Color::White
+            })
+        );
+
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+        game.set_clock(Clock::with_time_source(
+            TimeControl::sudden_death(secs(1)),
+            time.clone(),
+        ));
+
+        // white runs out, and black has nothing to mate with
+        time.advance(secs(2));
+        assert_eq!(game.get_outcome(), Some(Outcome::TimeoutDraw));
+    }
+}
@@ -0,0 +1,556 @@
+//! Drives an external UCI engine, e.g. to analyse a game with a stronger engine than ours.
+//!
+//! The engine runs as a child process. Its output is read on a separate thread, so that an
+//! engine that hangs or crashes shows up as an `EngineError` instead of blocking forever.
+//!
+//! # Examples
+//! ```no_run
+//! use std::process::Command;
+//! use vprytz_chess::engine::Engine;
+//! use vprytz_chess::search::Limits;
+//! use vprytz_chess::Game;
+//!
+//! let mut engine = Engine::start(Command::new("stockfish")).unwrap();
+//! engine.set_option("MultiPV", "3").unwrap();
+//!
+//! let mut game = Game::new();
+//! game.make_move("E2".to_string(), "E4".to_string());
+//! let analysis = engine.go(&game, &Limits { depth: Some(20), ..Limits::default() }).unwrap();
+//! println!("{:?} {:?}", analysis.best_move, analysis.info.last());
+//! ```
+
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use crate::fen::STARTING_FEN;
+use crate::search::Limits;
+use crate::{Game, Move};
+
+/// How long the engine may stay silent when it should answer, unless changed with `set_timeout()`.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// how long to give the engine to exit after quit before killing it
+const QUIT_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Things that can go wrong when talking to an engine.
+#[derive(Debug)]
+pub enum EngineError {
+    /// The engine could not be started, or could not be written to.
+    Io(io::Error),
+    /// The engine didn't answer in time.
+    Timeout,
+    /// The engine exited (or closed its output) while we were waiting for an answer.
+    Crashed(Option<ExitStatus>),
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineError::Io(error) => write!(f, "engine i/o error: {}", error),
+            EngineError::Timeout => write!(f, "engine did not answer in time"),
+            EngineError::Crashed(Some(status)) => write!(f, "engine exited ({})", status),
+            EngineError::Crashed(None) => write!(f, "engine closed its output"),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+impl From<io::Error> for EngineError {
+    fn from(error: io::Error) -> Self {
+        EngineError::Io(error)
+    }
+}
+
+/// A score as reported by an engine, from the point of view of the player to move.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Score {
+    /// Advantage in centipawns.
+    Centipawns(i32),
+    /// Mate in this many moves, negative if the player to move is getting mated.
+    Mate(i32),
+}
+
+/// One `info` line sent by the engine while searching. Everything the engine left out is None.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Info {
+    pub depth: Option<u32>,
+    pub seldepth: Option<u32>,
+    /// Which of the lines this is (starting at 1) when the engine searches several (MultiPV).
+    pub multipv: Option<u32>,
+    pub score: Option<Score>,
+    pub nodes: Option<u64>,
+    pub nps: Option<u64>,
+    pub time: Option<Duration>,
+    /// The principal variation, as far as its moves are legal.
+    pub pv: Vec<Move>,
+    /// Free text sent with `info string`.
+    pub string: Option<String>,
+}
+
+/// The outcome of a search by the engine.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Analysis {
+    /// The move the engine wants to play, None if it has no legal moves.
+    pub best_move: Option<Move>,
+    /// The reply the engine expects.
+    pub ponder: Option<Move>,
+    /// Every info line sent during the search, in order.
+    pub info: Vec<Info>,
+}
+
+/// A running UCI engine. The engine is told to quit (and killed if it doesn't) when this is dropped.
+pub struct Engine {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    timeout: Duration,
+    name: Option<String>,
+    author: Option<String>,
+    options: Vec<String>,
+}
+
+impl Engine {
+    /// Starts the engine and waits for it to be ready, using `DEFAULT_TIMEOUT`.
+    pub fn start(command: Command) -> Result<Engine, EngineError> {
+        Engine::start_with_timeout(command, DEFAULT_TIMEOUT)
+    }
+
+    /// Starts the engine and waits for it to be ready, failing if it is silent for longer than `timeout`.
+    pub fn start_with_timeout(
+        mut command: Command,
+        timeout: Duration,
+    ) -> Result<Engine, EngineError> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+
+        // the channel is closed when the engine closes its output
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(line) => {
+                        if sender.send(line).is_err() {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+
+        let mut engine = Engine {
+            child,
+            stdin,
+            lines,
+            timeout,
+            name: None,
+            author: None,
+            options: Vec::new(),
+        };
+
+        engine.send("uci")?;
+        loop {
+            let line = engine.read_line(timeout)?;
+            if let Some(name) = line.strip_prefix("id name ") {
+                engine.name = Some(name.trim().to_string());
+            } else if let Some(author) = line.strip_prefix("id author ") {
+                engine.author = Some(author.trim().to_string());
+            } else if let Some(option) = line.strip_prefix("option name ") {
+                let name = option.split(" type ").next().unwrap_or(option);
+                engine.options.push(name.trim().to_string());
+            } else if line.trim() == "uciok" {
+                break;
+            }
+        }
+        engine.wait_until_ready()?;
+
+        Ok(engine)
+    }
+
+    /// Changes how long the engine may stay silent when it should answer.
+    /// When searching with a time limit, the engine gets that much longer.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// The name the engine gave, if any.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// The author the engine gave, if any.
+    pub fn author(&self) -> Option<&str> {
+        self.author.as_deref()
+    }
+
+    /// The names of the options the engine has, such as "Hash" or "MultiPV".
+    pub fn options(&self) -> &[String] {
+        &self.options
+    }
+
+    /// Sets an option of the engine, e.g. `set_option("Hash", "64")`.
+    pub fn set_option(&mut self, name: &str, value: &str) -> Result<(), EngineError> {
+        self.send(&format!("setoption name {} value {}", name, value))?;
+        self.wait_until_ready()
+    }
+
+    /// Tells the engine that the next position is from a different game.
+    pub fn new_game(&mut self) -> Result<(), EngineError> {
+        self.send("ucinewgame")?;
+        self.wait_until_ready()
+    }
+
+    /// Lets the engine search the current position of the game, sending it the moves that led there.
+    /// The depth, nodes and time of the limits are passed on to the engine (with no limits the
+    /// engine decides for itself). If the engine doesn't answer in time it is told to stop, and
+    /// only if it doesn't answer to that either is `EngineError::Timeout` returned.
+    pub fn go(&mut self, game: &Game, limits: &Limits) -> Result<Analysis, EngineError> {
+        self.send(&position_command(game))?;
+
+        let mut go = "go".to_string();
+        if let Some(depth) = limits.depth {
+            go.push_str(&format!(" depth {}", depth));
+        }
+        if let Some(nodes) = limits.nodes {
+            go.push_str(&format!(" nodes {}", nodes));
+        }
+        if let Some(time) = limits.time {
+            go.push_str(&format!(" movetime {}", time.as_millis()));
+        }
+        self.send(&go)?;
+
+        let mut info = Vec::new();
+        let mut wait = self.timeout + limits.time.unwrap_or_default();
+        let mut stopped = false;
+        loop {
+            let line = match self.read_line(wait) {
+                Ok(line) => line,
+                Err(EngineError::Timeout) if !stopped => {
+                    self.send("stop")?;
+                    stopped = true;
+                    wait = self.timeout;
+                    continue;
+                }
+                Err(error) => return Err(error),
+            };
+
+            if let Some(parsed) = parse_info(&line, game) {
+                info.push(parsed);
+            } else if let Some((best_move, ponder)) = parse_best_move(&line, game) {
+                return Ok(Analysis {
+                    best_move,
+                    ponder,
+                    info,
+                });
+            }
+        }
+    }
+
+    fn wait_until_ready(&mut self) -> Result<(), EngineError> {
+        self.send("isready")?;
+        while self.read_line(self.timeout)?.trim() != "readyok" {}
+        Ok(())
+    }
+
+    fn send(&mut self, line: &str) -> Result<(), EngineError> {
+        writeln!(self.stdin, "{}", line)?;
+        self.stdin.flush()?;
+        Ok(())
+    }
+
+    fn read_line(&mut self, timeout: Duration) -> Result<String, EngineError> {
+        match self.lines.recv_timeout(timeout) {
+            Ok(line) => Ok(line),
+            Err(RecvTimeoutError::Timeout) => Err(EngineError::Timeout),
+            Err(RecvTimeoutError::Disconnected) => {
+                // the output is closed, give the process a moment to exit so we can tell how
+                thread::sleep(Duration::from_millis(10));
+                Err(EngineError::Crashed(self.child.try_wait().ok().flatten()))
+            }
+        }
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let waited = Duration::from_millis(10);
+        for _ in 0..QUIT_TIMEOUT.as_millis() / waited.as_millis() {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(waited);
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// the position command for the current position of the game, with the moves since its start
+fn position_command(game: &Game) -> String {
+    let mut start = game.get_starting_position();
+    let fen = start.to_fen();
+    let mut command = if fen == STARTING_FEN {
+        "position startpos".to_string()
+    } else {
+        format!("position fen {}", fen)
+    };
+
+    let moves = game.get_move_history();
+    if !moves.is_empty() {
+        command.push_str(" moves");
+        for mv in moves {
+            command.push_str(&format!(" {}", start.move_to_uci(mv)));
+            start.do_move(mv);
+        }
+    }
+    command
+}
+
+// reads an info line, the moves of the pv are read from the current position of the game
+fn parse_info(line: &str, game: &Game) -> Option<Info> {
+    let mut tokens = line.split_whitespace();
+    if tokens.next() != Some("info") {
+        return None;
+    }
+
+    let mut info = Info::default();
+    while let Some(token) = tokens.next() {
+        match token {
+            "depth" => info.depth = tokens.next().and_then(|value| value.parse().ok()),
+            "seldepth" => info.seldepth = tokens.next().and_then(|value| value.parse().ok()),
+            "multipv" => info.multipv = tokens.next().and_then(|value| value.parse().ok()),
+            "nodes" => info.nodes = tokens.next().and_then(|value| value.parse().ok()),
+            "nps" => info.nps = tokens.next().and_then(|value| value.parse().ok()),
+            "time" => {
+                info.time = tokens
+                    .next()
+                    .and_then(|value| value.parse().ok())
+                    .map(Duration::from_millis)
+            }
+            "score" => {
+                let kind = tokens.next();
+                let value = tokens.next().and_then(|value| value.parse().ok());
+                info.score = match (kind, value) {
+                    (Some("cp"), Some(value)) => Some(Score::Centipawns(value)),
+                    (Some("mate"), Some(value)) => Some(Score::Mate(value)),
+                    _ => None,
+                };
+            }
+            "pv" => {
+                let mut position = game.clone();
+                for text in tokens.by_ref() {
+                    match position.parse_uci_move(text) {
+                        Some(mv) => {
+                            position.do_move(mv);
+                            info.pv.push(mv);
+                        }
+                        None => break,
+                    }
+                }
+            }
+            // the rest of the line is text
+            "string" => {
+                info.string = Some(tokens.by_ref().collect::<Vec<&str>>().join(" "));
+            }
+            // everything else (currmove, hashfull, lowerbound, ...) is skipped
+            _ => {}
+        }
+    }
+    Some(info)
+}
+
+// reads a bestmove line into the best move and the ponder move, "0000" meaning no move
+fn parse_best_move(line: &str, game: &Game) -> Option<(Option<Move>, Option<Move>)> {
+    let mut tokens = line.split_whitespace();
+    if tokens.next() != Some("bestmove") {
+        return None;
+    }
+
+    let best_move = tokens.next().and_then(|text| game.parse_uci_move(text));
+    let ponder = match (best_move, tokens.next(), tokens.next()) {
+        (Some(mv), Some("ponder"), Some(text)) => {
+            let mut position = game.clone();
+            position.do_move(mv);
+            position.parse_uci_move(text)
+        }
+        _ => None,
+    };
+    Some((best_move, ponder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(moves: &[&str]) -> Game {
+        let mut game = Game::new();
+        for text in moves {
+            let mv = game.parse_uci_move(text).unwrap();
+            game.apply_move(mv);
+        }
+        game
+    }
+
+    // a scripted engine that answers each command with canned lines
+    #[cfg(unix)]
+    fn mock(script: &str) -> Command {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(format!(
+            "while read -r line; do case \"$line\" in {} esac; done",
+            script
+        ));
+        command
+    }
+
+    #[cfg(unix)]
+    const HANDSHAKE: &str = "uci) echo 'id name Mock'; echo 'id author Someone'; echo 'option name Hash type spin default 1 min 1 max 8'; echo uciok ;; isready) echo readyok ;;";
+
+    #[test]
+    fn info_lines() {
+        let game = Game::new();
+
+        assert_eq!(
+            parse_info(
+                "info depth 12 seldepth 17 multipv 2 score cp -35 upperbound nodes 12345 nps 100000 hashfull 3 time 250 pv e2e4 e7e5 g1g3",
+                &game
+            ),
+            Some(Info {
+                depth: Some(12),
+                seldepth: Some(17),
+                multipv: Some(2),
+                score: Some(Score::Centipawns(-35)),
+                nodes: Some(12345),
+                nps: Some(100000),
+                time: Some(Duration::from_millis(250)),
+                // g1g3 isn't legal, so the pv stops before it
+                pv: vec![game.parse_uci_move("e2e4").unwrap(), play(&["e2e4"]).parse_uci_move("e7e5").unwrap()],
+                string: None,
+            })
+        );
+        assert_eq!(
+            parse_info("info string hello there", &game)
+                .unwrap()
+                .string
+                .as_deref(),
+            Some("hello there")
+        );
+        assert_eq!(
+            parse_info("info depth 3 score mate -2", &game)
+                .unwrap()
+                .score,
+            Some(Score::Mate(-2))
+        );
+        assert_eq!(parse_info("bestmove e2e4", &game), None);
+    }
+
+    #[test]
+    fn best_move_lines() {
+        let game = Game::new();
+        let e4 = game.parse_uci_move("e2e4").unwrap();
+        let e5 = play(&["e2e4"]).parse_uci_move("e7e5").unwrap();
+
+        assert_eq!(
+            parse_best_move("bestmove e2e4 ponder e7e5", &game),
+            Some((Some(e4), Some(e5)))
+        );
+        assert_eq!(
+            parse_best_move("bestmove e2e4", &game),
+            Some((Some(e4), None))
+        );
+        assert_eq!(parse_best_move("bestmove 0000", &game), Some((None, None)));
+        assert_eq!(parse_best_move("info depth 1", &game), None);
+    }
+
+    #[test]
+    fn positions_from_history() {
+        assert_eq!(position_command(&Game::new()), "position startpos");
+        assert_eq!(
+            position_command(&play(&["e2e4", "e7e5"])),
+            "position startpos moves e2e4 e7e5"
+        );
+
+        let mut game = Game::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        game.apply_move(game.parse_uci_move("e1g1").unwrap());
+        assert_eq!(
+            position_command(&game),
+            "position fen r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1 moves e1g1"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn talks_to_mock_engine() {
+        let mut engine = Engine::start(mock(&format!(
+            "{} go*) echo 'info depth 1 score cp 20 nodes 10 pv e2e4'; echo 'info depth 2 multipv 2 score mate 3 pv d2d4 d7d5'; echo 'bestmove e2e4 ponder e7e5' ;;",
+            HANDSHAKE
+        )))
+        .unwrap();
+
+        assert_eq!(engine.name(), Some("Mock"));
+        assert_eq!(engine.author(), Some("Someone"));
+        assert_eq!(engine.options(), &["Hash".to_string()]);
+        engine.set_option("Hash", "4").unwrap();
+        engine.new_game().unwrap();
+
+        let game = Game::new();
+        let analysis = engine.go(&game, &Limits::default()).unwrap();
+        assert_eq!(analysis.best_move, game.parse_uci_move("e2e4"));
+        assert!(analysis.ponder.is_some());
+        assert_eq!(analysis.info.len(), 2);
+        assert_eq!(analysis.info[1].multipv, Some(2));
+        assert_eq!(analysis.info[1].score, Some(Score::Mate(3)));
+        assert_eq!(analysis.info[1].pv.len(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn crashes_and_timeouts() {
+        // exits in the middle of a search
+        let mut engine = Engine::start(mock(&format!("{} go*) exit 3 ;;", HANDSHAKE))).unwrap();
+        match engine.go(&Game::new(), &Limits::default()) {
+            Err(EngineError::Crashed(Some(status))) => assert_eq!(status.code(), Some(3)),
+            other => panic!("expected a crash, got {:?}", other),
+        }
+
+        // never finishes the handshake
+        let start = Engine::start_with_timeout(
+            mock("uci) echo 'id name Slow' ;;"),
+            Duration::from_millis(100),
+        );
+        assert!(matches!(start, Err(EngineError::Timeout)));
+
+        // only answers a search once told to stop
+        let mut engine = Engine::start(mock(&format!(
+            "{} stop) echo 'bestmove e2e4' ;;",
+            HANDSHAKE
+        )))
+        .unwrap();
+        engine.set_timeout(Duration::from_millis(100));
+        let analysis = engine.go(&Game::new(), &Limits::default()).unwrap();
+        assert!(analysis.best_move.is_some());
+
+        // doesn't answer at all
+        let mut engine = Engine::start(mock(HANDSHAKE)).unwrap();
+        engine.set_timeout(Duration::from_millis(100));
+        assert!(matches!(
+            engine.go(&Game::new(), &Limits::default()),
+            Err(EngineError::Timeout)
+        ));
+
+        assert!(matches!(
+            Engine::start(Command::new("/nonexistent/engine")),
+            Err(EngineError::Io(_))
+        ));
+    }
+}
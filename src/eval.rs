@@ -0,0 +1,207 @@
+//! Static evaluation of a position, used by the search.
+//!
+//! The evaluation counts material and adds a bonus or penalty depending on where each
+//! piece stands (piece-square tables, from the "simplified evaluation function" on the
+//! chess programming wiki). Scores are in centipawns.
+
+use crate::variant::{Pocket, Variant};
+use crate::{Color, Game, PieceType, BOARD_SIZE};
+
+/// Phase of the starting position, see `phase()`.
+pub const MAX_PHASE: i32 = 24;
+
+// bonus for every rank a king has advanced in Racing Kings
+const RACE_BONUS: i32 = 60;
+
+// the tables are written from whites point of view, with row 0 being rank 8 (like the board)
+#[rustfmt::skip]
+const PAWN_TABLE: [[i32; 8]; 8] = [
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+    [ 50,  50,  50,  50,  50,  50,  50,  50],
+    [ 10,  10,  20,  30,  30,  20,  10,  10],
+    [  5,   5,  10,  25,  25,  10,   5,   5],
+    [  0,   0,   0,  20,  20,   0,   0,   0],
+    [  5,  -5, -10,   0,   0, -10,  -5,   5],
+    [  5,  10,  10, -20, -20,  10,  10,   5],
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+];
+
+#[rustfmt::skip]
+const KNIGHT_TABLE: [[i32; 8]; 8] = [
+    [-50, -40, -30, -30, -30, -30, -40, -50],
+    [-40, -20,   0,   0,   0,   0, -20, -40],
+    [-30,   0,  10,  15,  15,  10,   0, -30],
+    [-30,   5,  15,  20,  20,  15,   5, -30],
+    [-30,   0,  15,  20,  20,  15,   0, -30],
+    [-30,   5,  10,  15,  15,  10,   5, -30],
+    [-40, -20,   0,   5,   5,   0, -20, -40],
+    [-50, -40, -30, -30, -30, -30, -40, -50],
+];
+
+#[rustfmt::skip]
+const BISHOP_TABLE: [[i32; 8]; 8] = [
+    [-20, -10, -10, -10, -10, -10, -10, -20],
+    [-10,   0,   0,   0,   0,   0,   0, -10],
+    [-10,   0,   5,  10,  10,   5,   0, -10],
+    [-10,   5,   5,  10,  10,   5,   5, -10],
+    [-10,   0,  10,  10,  10,  10,   0, -10],
+    [-10,  10,  10,  10,  10,  10,  10, -10],
+    [-10,   5,   0,   0,   0,   0,   5, -10],
+    [-20, -10, -10, -10, -10, -10, -10, -20],
+];
+
+#[rustfmt::skip]
+const ROOK_TABLE: [[i32; 8]; 8] = [
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+    [  5,  10,  10,  10,  10,  10,  10,   5],
+    [ -5,   0,   0,   0,   0,   0,   0,  -5],
+    [ -5,   0,   0,   0,   0,   0,   0,  -5],
+    [ -5,   0,   0,   0,   0,   0,   0,  -5],
+    [ -5,   0,   0,   0,   0,   0,   0,  -5],
+    [ -5,   0,   0,   0,   0,   0,   0,  -5],
+    [  0,   0,   0,   5,   5,   0,   0,   0],
+];
+
+#[rustfmt::skip]
+const QUEEN_TABLE: [[i32; 8]; 8] = [
+    [-20, -10, -10,  -5,  -5, -10, -10, -20],
+    [-10,   0,   0,   0,   0,   0,   0, -10],
+    [-10,   0,   5,   5,   5,   5,   0, -10],
+    [ -5,   0,   5,   5,   5,   5,   0,  -5],
+    [  0,   0,   5,   5,   5,   5,   0,  -5],
+    [-10,   5,   5,   5,   5,   5,   0, -10],
+    [-10,   0,   5,   0,   0,   0,   0, -10],
+    [-20, -10, -10,  -5,  -5, -10, -10, -20],
+];
+
+#[rustfmt::skip]
+const KING_TABLE: [[i32; 8]; 8] = [
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-20, -30, -30, -40, -40, -30, -30, -20],
+    [-10, -20, -20, -20, -20, -20, -20, -10],
+    [ 20,  20,   0,   0,   0,   0,  20,  20],
+    [ 20,  30,  10,   0,   0,  10,  30,  20],
+];
+
+/// Returns the material value of a piece type in centipawns.
+/// The king has no material value since it can never be captured.
+/// # Examples
+/// ```
+/// use vprytz_chess::eval::piece_value;
+/// use vprytz_chess::PieceType;
+/// assert_eq!(piece_value(PieceType::Rook), 500);
+/// ```
+pub fn piece_value(piece: PieceType) -> i32 {
+    match piece {
+        PieceType::Pawn => 100,
+        PieceType::Knight => 320,
+        PieceType::Bishop => 330,
+        PieceType::Rook => 500,
+        PieceType::Queen => 900,
+        PieceType::King => 0,
+        PieceType::Archbishop => 875,
+        PieceType::Chancellor => 900,
+        PieceType::Amazon => 1200,
+        PieceType::Camel => 250,
+        PieceType::Grasshopper => 200,
+        PieceType::Custom(_) => 300,
+        PieceType::Duck => 0,
+    }
+}
+
+/// The phase of the game, from `MAX_PHASE` with all pieces on the board down to 0 when
+/// only kings and pawns are left. Knights and bishops count 1, rooks 2 and queens 4.
+/// # Examples
+/// ```
+/// use vprytz_chess::eval::{phase, MAX_PHASE};
+/// use vprytz_chess::Game;
+/// assert_eq!(phase(&Game::new()), MAX_PHASE);
+/// ```
+pub fn phase(game: &Game) -> i32 {
+    let phase: i32 = game
+        .board
+        .iter()
+        .flatten()
+        .flatten()
+        .map(|piece| match piece.piece {
+            PieceType::Knight | PieceType::Bishop => 1,
+            PieceType::Rook => 2,
+            PieceType::Queen | PieceType::Archbishop | PieceType::Chancellor => 4,
+            PieceType::Amazon => 5,
+            PieceType::Camel | PieceType::Grasshopper | PieceType::Custom(_) => 1,
+            PieceType::King | PieceType::Pawn | PieceType::Duck => 0,
+        })
+        .sum();
+    // promotions can give more than the starting material
+    phase.min(MAX_PHASE)
+}
+
+/// Evaluates the position from the point of view of the player whose turn it is,
+/// positive scores meaning that the player to move is better.
+/// # Examples
+/// ```
+/// use vprytz_chess::eval::evaluate;
+/// use vprytz_chess::Game;
+/// let game = Game::new();
+/// assert_eq!(evaluate(&game), 0); // the starting position is symmetrical
+/// ```
+pub fn evaluate(game: &Game) -> i32 {
+    let mut score = 0;
+    let racing = game.get_variant() == Variant::RacingKings;
+
+    for row in 0..BOARD_SIZE {
+        for col in 0..BOARD_SIZE {
+            // the duck belongs to nobody
+            let piece = match game.board[row][col] {
+                Some(piece) if piece.piece != PieceType::Duck => piece,
+                _ => continue,
+            };
+
+            // black looks at the tables upside down
+            let table_row = match piece.color {
+                Color::White => row,
+                Color::Black => BOARD_SIZE - 1 - row,
+            };
+            let table = match piece.piece {
+                PieceType::Pawn => &PAWN_TABLE,
+                PieceType::Knight => &KNIGHT_TABLE,
+                PieceType::Bishop => &BISHOP_TABLE,
+                PieceType::Rook => &ROOK_TABLE,
+                PieceType::Queen => &QUEEN_TABLE,
+                PieceType::King => &KING_TABLE,
+                PieceType::Archbishop | PieceType::Chancellor | PieceType::Amazon => &QUEEN_TABLE,
+                _ => &KNIGHT_TABLE,
+            };
+            let mut value = piece_value(piece.piece) + table[table_row][col];
+            // in Racing Kings both kings run for the eighth rank instead of staying home
+            if racing && piece.piece == PieceType::King {
+                value = RACE_BONUS * (BOARD_SIZE - 1 - row) as i32;
+            }
+
+            if piece.color == Color::White {
+                score += value;
+            } else {
+                score -= value;
+            }
+        }
+    }
+
+    // pieces in hand (in Crazyhouse) are worth as much as those on the board
+    let (white, black) = (game.get_pocket(Color::White), game.get_pocket(Color::Black));
+    for piece in Pocket::PIECES {
+        score += piece_value(piece) * (white.count(piece) as i32 - black.count(piece) as i32);
+    }
+
+    // in Antichess the aim is to give everything away
+    if game.get_variant() == Variant::Antichess {
+        score = -score;
+    }
+
+    match game.active_color {
+        Color::White => score,
+        Color::Black => -score,
+    }
+}
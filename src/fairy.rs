@@ -0,0 +1,583 @@
+//! Fairy pieces, which move in ways the orthodox pieces don't, described in Betza notation.
+//!
+//! Betza notation builds the moves of a piece out of "atoms", each a leap of a fixed shape
+//! that can be made in every direction:
+//!
+//! | **Atom** | **Leap** | **Atom** | **Leap** |
+//! | -------- | -------- | -------- | -------- |
+//! | `W`      | 1, 0     | `H`      | 3, 0     |
+//! | `F`      | 1, 1     | `C`      | 3, 1     |
+//! | `D`      | 2, 0     | `Z`      | 3, 2     |
+//! | `N`      | 2, 1     | `G`      | 3, 3     |
+//! | `A`      | 2, 2     |          |          |
+//!
+//! `K`, `Q`, `R` and `B` are short for `WF`, `WWFF`, `WW` and `FF`. Writing an atom twice (`WW`)
+//! makes a rider, which keeps going in the same direction until it is blocked, and a number
+//! after the atom (`W3`) limits how far it goes. Lowercase modifiers in front of an atom narrow
+//! it down: `m` only moves and `c` only captures, `f`, `b`, `s` and `v` only go forward,
+//! backward, sideways or forward and backward (seen from the player owning the piece, and
+//! combining them allows any of the directions) and `g` makes it hop like a grasshopper:
+//! it slides to the first piece in its way and lands right behind it. A pawn that can't
+//! move two squares is `fmWfcF`.
+//!
+//! The built in fairy pieces are:
+//!
+//! | **Piece**   | **Letter** | **Betza** |
+//! | ----------- | ---------- | --------- |
+//! | Archbishop  | `A`        | `BN`      |
+//! | Chancellor  | `C`        | `RN`      |
+//! | Amazon      | `M`        | `QN`      |
+//! | Camel       | `L`        | `C`       |
+//! | Grasshopper | `G`        | `gQ`      |
+//!
+//! More pieces can be defined with [`CustomPieces`] and read with
+//! [`Game::from_fen_with_pieces`]. Fairy pieces are put on the board with FEN,
+//! and pawns may promote to the kinds of fairy pieces the game started with, which makes for
+//! Capablanca chess with archbishops and chancellors on a 10x8 board. For games where the fairy
+//! pieces start off the board, give them with [`Game::set_fairy_promotions`].
+//!
+//! # Examples
+//! ```
+//! use vprytz_chess::Game;
+//! let fen = "rnabqkbcnr/pppppppppp/10/10/10/10/PPPPPPPPPP/RNABQKBCNR w KQkq - 0 1";
+//! let game = Game::<10, 8>::from_fen_sized(fen).unwrap();
+//! assert_eq!(game.get_legal_moves().len(), 28);
+//! ```
+
+use std::sync::OnceLock;
+
+use crate::{fen, offset, Color, Game, Move, PieceType};
+
+// the fairy pieces that come with the library, with their FEN letters and moves
+const BUILT_IN: [(PieceType, char, &str); 5] = [
+    (PieceType::Archbishop, 'a', "BN"),
+    (PieceType::Chancellor, 'c', "RN"),
+    (PieceType::Amazon, 'm', "QN"),
+    (PieceType::Camel, 'l', "C"),
+    (PieceType::Grasshopper, 'g', "gQ"),
+];
+
+/// The most pieces that can be defined in one [`CustomPieces`].
+pub const MAX_CUSTOM_PIECES: usize = 16;
+
+/// The moves of a piece, read from Betza notation, see the module documentation.
+/// # Examples
+/// ```
+/// use vprytz_chess::fairy::Betza;
+/// assert!(Betza::parse("fmWfcF").is_some());
+/// assert!(Betza::parse("X").is_none());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Betza {
+    atoms: Vec<Atom>,
+}
+
+// a leap in every direction allowed by the modifiers, repeated up to "range" times (0 for
+// as far as the board goes)
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Atom {
+    leap: (i32, i32),
+    range: usize,
+    moves: bool,
+    captures: bool,
+    hops: bool,
+    // forward, backward, sideways and vertical, all directions when none are set
+    directions: [bool; 4],
+}
+
+impl Betza {
+    /// Reads a piece description in Betza notation, returning None if it can't be read.
+    pub fn parse(text: &str) -> Option<Betza> {
+        let mut atoms = Vec::new();
+        let mut chars = text.chars().peekable();
+
+        while chars.peek().is_some() {
+            let (mut moves, mut captures, mut hops) = (false, false, false);
+            let mut directions = [false; 4];
+            while let Some(c) = chars.next_if(|c| c.is_ascii_lowercase()) {
+                match c {
+                    'm' => moves = true,
+                    'c' => captures = true,
+                    'g' => hops = true,
+                    'f' => directions[0] = true,
+                    'b' => directions[1] = true,
+                    's' => directions[2] = true,
+                    'v' => directions[3] = true,
+                    _ => return None,
+                }
+            }
+            // without m or c the piece both moves and captures
+            if !moves && !captures {
+                (moves, captures) = (true, true);
+            }
+
+            let letter = chars.next()?;
+            let (leaps, rider): (&[(i32, i32)], bool) = match letter {
+                'K' => (&[(1, 0), (1, 1)], false),
+                'Q' => (&[(1, 0), (1, 1)], true),
+                'R' => (&[(1, 0)], true),
+                'B' => (&[(1, 1)], true),
+                _ => (std::slice::from_ref(leap(letter)?), false),
+            };
+
+            // a repeated atom is a rider, a number after it limits how far it goes
+            let mut range = if rider { 0 } else { 1 };
+            if !rider && chars.next_if_eq(&letter).is_some() {
+                range = 0;
+            } else {
+                let mut digits = String::new();
+                while let Some(digit) = chars.next_if(|c| c.is_ascii_digit()) {
+                    digits.push(digit);
+                }
+                if !digits.is_empty() {
+                    range = digits.parse().ok().filter(|range| *range > 0)?;
+                }
+            }
+
+            for leap in leaps {
+                atoms.push(Atom {
+                    leap: *leap,
+                    range,
+                    moves,
+                    captures,
+                    hops,
+                    directions,
+                });
+            }
+        }
+
+        if atoms.is_empty() {
+            None
+        } else {
+            Some(Betza { atoms })
+        }
+    }
+}
+
+impl Atom {
+    // the steps of the atom in every direction it may go, for a piece moving "forward" along
+    // the rows (-1 for white, 1 for black)
+    fn steps(&self, forward: i32) -> Vec<(i32, i32)> {
+        let (a, b) = self.leap;
+        let mut steps = Vec::with_capacity(8);
+        for (row, col) in [(a, b), (b, a)] {
+            for (row, col) in [(row, col), (-row, col), (row, -col), (-row, -col)] {
+                if !steps.contains(&(row, col)) && self.allows((row, col), forward) {
+                    steps.push((row, col));
+                }
+            }
+        }
+        steps
+    }
+
+    fn allows(&self, step: (i32, i32), forward: i32) -> bool {
+        if !self.directions.contains(&true) {
+            return true;
+        }
+        let [forwards, backwards, sideways, vertical] = self.directions;
+        (forwards && step.0 * forward > 0)
+            || (backwards && step.0 * forward < 0)
+            || (sideways && step.0 == 0)
+            || (vertical && step.1 == 0)
+    }
+}
+
+/// Fairy pieces of your own, each written as a letter in FEN and moving as described in Betza
+/// notation. Games read with [`Game::from_fen_with_pieces`] keep the pieces they were given.
+/// # Examples
+/// ```
+/// use vprytz_chess::fairy::CustomPieces;
+/// use vprytz_chess::{Game, PieceType};
+/// let mut pieces = CustomPieces::new();
+/// // a "fers" that may also step straight forward
+/// assert_eq!(pieces.define('e', "FfW"), Some(PieceType::Custom('e')));
+/// assert_eq!(pieces.define('n', "W"), None);
+///
+/// let game: Game = Game::from_fen_with_pieces("4k3/8/8/8/8/8/8/E3K3 w - - 0 1", &pieces).unwrap();
+/// assert_eq!(game.get_possible_moves("A1".to_string()).unwrap(), vec!["B2", "A2"]);
+/// assert!(Game::from_fen("4k3/8/8/8/8/8/8/E3K3 w - - 0 1").is_none());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CustomPieces {
+    pieces: Vec<(char, Betza)>,
+}
+
+impl CustomPieces {
+    pub fn new() -> CustomPieces {
+        CustomPieces::default()
+    }
+
+    /// Adds a piece written as the given letter (uppercase for white, lowercase for black).
+    /// Returns the new piece type, or None if the letter is already used by another piece, the
+    /// moves can't be read or there are already `MAX_CUSTOM_PIECES` of them. Defining the same
+    /// piece again returns the same type.
+    pub fn define(&mut self, letter: char, betza: &str) -> Option<PieceType> {
+        let letter = letter.to_ascii_lowercase();
+        let betza = Betza::parse(betza)?;
+        if let Some((_, other)) = self.pieces.iter().find(|(other, _)| *other == letter) {
+            return (*other == betza).then_some(PieceType::Custom(letter));
+        }
+        let taken = fen::piece_from_char(letter).is_some();
+        if !letter.is_ascii_alphabetic() || taken || self.pieces.len() >= MAX_CUSTOM_PIECES {
+            return None;
+        }
+        self.pieces.push((letter, betza));
+        Some(PieceType::Custom(letter))
+    }
+
+    // the piece written as the letter (either case), if it is one of these
+    pub(crate) fn piece(&self, letter: char) -> Option<PieceType> {
+        let letter = letter.to_ascii_lowercase();
+        self.pieces
+            .iter()
+            .any(|(other, _)| *other == letter)
+            .then_some(PieceType::Custom(letter))
+    }
+
+    fn betza(&self, letter: char) -> Option<&Betza> {
+        self.pieces
+            .iter()
+            .find(|(other, _)| *other == letter)
+            .map(|(_, betza)| betza)
+    }
+}
+
+/// Returns true for every piece but the six orthodox ones (and the duck of Duck chess).
+pub fn is_fairy(piece: PieceType) -> bool {
+    !matches!(
+        piece,
+        PieceType::King
+            | PieceType::Queen
+            | PieceType::Rook
+            | PieceType::Bishop
+            | PieceType::Knight
+            | PieceType::Pawn
+            | PieceType::Duck
+    )
+}
+
+// the built in fairy piece written as the (lowercase) letter in FEN
+pub(crate) fn piece_from_letter(letter: char) -> Option<PieceType> {
+    BUILT_IN
+        .iter()
+        .find(|(_, other, _)| *other == letter)
+        .map(|(piece, _, _)| *piece)
+}
+
+// the lowercase FEN letter of a fairy piece
+pub(crate) fn letter(piece: PieceType) -> Option<char> {
+    if let PieceType::Custom(letter) = piece {
+        return Some(letter);
+    }
+    BUILT_IN
+        .iter()
+        .find(|(other, _, _)| *other == piece)
+        .map(|(_, letter, _)| *letter)
+}
+
+fn leap(letter: char) -> Option<&'static (i32, i32)> {
+    match letter {
+        'W' => Some(&(1, 0)),
+        'F' => Some(&(1, 1)),
+        'D' => Some(&(2, 0)),
+        'N' => Some(&(2, 1)),
+        'A' => Some(&(2, 2)),
+        'H' => Some(&(3, 0)),
+        'C' => Some(&(3, 1)),
+        'Z' => Some(&(3, 2)),
+        'G' => Some(&(3, 3)),
+        _ => None,
+    }
+}
+
+impl<const WIDTH: usize, const HEIGHT: usize> Game<WIDTH, HEIGHT> {
+    // the piece written as a FEN letter (either case), including the custom pieces of the game
+    pub(crate) fn piece_from_char(&self, c: char) -> Option<PieceType> {
+        fen::piece_from_char(c).or_else(|| self.custom_pieces.piece(c))
+    }
+
+    // the moves of a fairy piece, None for the orthodox pieces
+    fn betza(&self, piece: PieceType) -> Option<&Betza> {
+        static BUILT_IN_BETZA: OnceLock<Vec<Betza>> = OnceLock::new();
+
+        if let PieceType::Custom(letter) = piece {
+            return self.custom_pieces.betza(letter);
+        }
+        let built_in = BUILT_IN_BETZA.get_or_init(|| {
+            BUILT_IN
+                .iter()
+                .map(|(_, _, betza)| Betza::parse(betza).expect("built in pieces are valid"))
+                .collect()
+        });
+        let index = BUILT_IN.iter().position(|(other, _, _)| *other == piece)?;
+        Some(&built_in[index])
+    }
+
+    // push all moves of the fairy piece at the given position to "moves", not checking for check
+    pub(crate) fn fairy_moves(&self, pos: (usize, usize), piece: PieceType, moves: &mut Vec<Move>) {
+        let color = match self.board[pos.0][pos.1] {
+            Some(piece) => piece.color,
+            None => return,
+        };
+        if let Some(betza) = self.betza(piece) {
+            self.fairy_targets(pos, color, betza, None, &mut |to| {
+                moves.push(Move {
+                    from: pos,
+                    to,
+                    promotion: None,
+                    drop: None,
+                })
+            });
+        }
+    }
+
+    // true if a fairy piece of the color "by" attacks the square
+    pub(crate) fn fairy_attacks(&self, pos: (usize, usize), by: Color) -> bool {
+        for row in 0..HEIGHT {
+            for col in 0..WIDTH {
+                let piece = match self.board[row][col] {
+                    Some(piece) if piece.color == by && is_fairy(piece.piece) => piece,
+                    _ => continue,
+                };
+                let mut attacks = false;
+                if let Some(betza) = self.betza(piece.piece) {
+                    self.fairy_targets((row, col), by, betza, Some(pos), &mut |to| {
+                        attacks |= to == pos
+                    });
+                }
+                if attacks {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Sets the fairy pieces pawns may promote to besides the usual ones, replacing the kinds
+    /// of fairy pieces the game started with. Orthodox pieces in the list are left out, and so
+    /// are custom pieces the game wasn't given.
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::{Game, PieceType};
+    /// let mut game = Game::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    /// assert!(game.parse_uci_move("b7b8c").is_none());
+    /// game.set_fairy_promotions(&[PieceType::Chancellor]);
+    /// assert!(game.parse_uci_move("b7b8c").is_some());
+    /// ```
+    pub fn set_fairy_promotions(&mut self, pieces: &[PieceType]) {
+        self.fairy_promotions = Vec::new();
+        for piece in pieces {
+            let known = is_fairy(*piece) && self.betza(*piece).is_some();
+            if known && !self.fairy_promotions.contains(piece) {
+                self.fairy_promotions.push(*piece);
+            }
+        }
+        // a promoted fairy piece has to be looked at when finding attacks
+        self.fairy |= !self.fairy_promotions.is_empty();
+    }
+
+    // pawns may also promote to the fairy pieces of the game, added for every promotion
+    // to a queen
+    pub(crate) fn add_fairy_promotions(&self, moves: &mut Vec<Move>) {
+        let promotions: Vec<Move> = moves
+            .iter()
+            .filter(|mv| mv.promotion == Some(PieceType::Queen))
+            .flat_map(|mv| {
+                self.fairy_promotions.iter().map(|piece| Move {
+                    promotion: Some(*piece),
+                    ..*mv
+                })
+            })
+            .collect();
+        moves.extend(promotions);
+    }
+
+    // calls visit with every square the piece of the given color on pos can move to,
+    // pretending that there is an enemy piece on "enemy" (to find attacks on empty squares)
+    fn fairy_targets(
+        &self,
+        pos: (usize, usize),
+        color: Color,
+        betza: &Betza,
+        enemy: Option<(usize, usize)>,
+        visit: &mut dyn FnMut((usize, usize)),
+    ) {
+        // the duck can't be captured, as if it were one of our own pieces
+        let occupant = |square: (usize, usize)| match self.board[square.0][square.1] {
+            _ if Some(square) == enemy => Some(color.opposite()),
+            Some(piece) if piece.piece == PieceType::Duck => Some(color),
+            piece => piece.map(|piece| piece.color),
+        };
+        let forward = match color {
+            Color::White => -1,
+            Color::Black => 1,
+        };
+        let land = |atom: &Atom, square| match occupant(square) {
+            None => atom.moves,
+            Some(other) => other != color && atom.captures,
+        };
+
+        for atom in &betza.atoms {
+            for step in atom.steps(forward) {
+                let mut current = pos;
+                let mut steps = 0;
+                while let Some(target) = offset(current, step, WIDTH, HEIGHT) {
+                    if occupant(target).is_some() {
+                        if atom.hops {
+                            // land right behind the first piece in the way
+                            if let Some(landing) = offset(target, step, WIDTH, HEIGHT) {
+                                if land(atom, landing) {
+                                    visit(landing);
+                                }
+                            }
+                        } else if land(atom, target) {
+                            visit(target);
+                        }
+                        break;
+                    }
+                    if !atom.hops && land(atom, target) {
+                        visit(target);
+                    }
+                    steps += 1;
+                    if steps == atom.range && !atom.hops {
+                        break;
+                    }
+                    current = target;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::perft;
+
+    fn targets(fen: &str, square: &str) -> Vec<String> {
+        let game = Game::from_fen(fen).unwrap();
+        let mut moves = game.get_possible_moves(square.to_string()).unwrap();
+        moves.sort();
+        moves
+    }
+
+    #[test]
+    fn betza_notation() {
+        let knight = Betza::parse("N").unwrap();
+        assert_eq!(knight.atoms.len(), 1);
+        assert_eq!(knight.atoms[0].steps(-1).len(), 8);
+        assert_eq!(Betza::parse("QN").unwrap().atoms.len(), 3);
+        assert_eq!(Betza::parse("WW").unwrap().atoms[0].range, 0);
+        assert_eq!(Betza::parse("W3").unwrap().atoms[0].range, 3);
+        // only the forward step of a wazir, and the two forward captures of a ferz
+        let pawn = Betza::parse("fmWfcF").unwrap();
+        assert_eq!(pawn.atoms[0].steps(-1), vec![(-1, 0)]);
+        assert_eq!(pawn.atoms[1].steps(1), vec![(1, 1), (1, -1)]);
+        assert!(!pawn.atoms[1].moves);
+
+        for text in ["", "x", "W0", "mm", "Y"] {
+            assert!(Betza::parse(text).is_none(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn built_in_pieces() {
+        // archbishop, chancellor and amazon combine the knight with the other pieces
+        assert_eq!(targets("4k3/8/8/8/8/8/8/A3K3 w - - 0 1", "A1").len(), 9);
+        assert_eq!(targets("4k3/8/8/8/8/8/8/C3K3 w - - 0 1", "A1").len(), 12);
+        assert_eq!(targets("4k3/8/8/8/8/8/8/M3K3 w - - 0 1", "A1").len(), 19);
+        assert_eq!(
+            targets("4k3/8/8/8/8/8/8/L3K3 w - - 0 1", "A1"),
+            vec!["B4", "D2"]
+        );
+
+        // the grasshopper needs something to jump over, and lands right behind it
+        assert_eq!(targets("4k3/8/8/8/8/8/1P6/G6K w - - 0 1", "A1"), vec!["C3"]);
+        assert_eq!(
+            targets("4k3/8/8/8/8/1p6/1P6/G6K w - - 0 1", "A1"),
+            vec!["C3"]
+        );
+        assert_eq!(
+            targets("4k3/8/8/8/8/2p5/1P6/G6K w - - 0 1", "A1"),
+            vec!["C3"]
+        );
+        assert_eq!(
+            targets("4k3/8/8/8/8/2P5/1P6/G6K w - - 0 1", "A1"),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn fairy_checks_and_promotions() {
+        // the chancellor gives check like a knight, so the king has to move
+        let game = Game::from_fen("4k3/8/3C4/8/8/8/8/4K3 b - - 0 1").unwrap();
+        assert!(game.is_check());
+        // and the grasshopper gives check over the rook
+        let game = Game::from_fen("4k3/4r3/8/8/8/8/8/4G1K1 b - - 0 1").unwrap();
+        assert!(game.is_check());
+
+        // promotions to the fairy pieces the game started with
+        let mut game = Game::from_fen("a3k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert!(game.parse_uci_move("b7b8a").is_some());
+        assert!(game.parse_uci_move("b7b8c").is_none());
+        game.set_promotion("archbishop".to_string());
+        game.make_move("B7".to_string(), "B8".to_string());
+        assert_eq!(game.to_fen(), "aA2k3/8/8/8/8/8/8/4K3 b - - 0 1");
+
+        // even once they have all been captured
+        let mut game = Game::from_fen("a3k3/1P6/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        for text in ["a1a8", "e8e7", "a8a1", "e7e8"] {
+            game.apply_move(game.parse_uci_move(text).unwrap());
+        }
+        assert!(game.parse_uci_move("b7b8a").is_some());
+
+        // and to the ones given when they start off the board
+        let mut game = Game::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        game.set_fairy_promotions(&[PieceType::Chancellor, PieceType::Queen]);
+        assert!(game.parse_uci_move("b7b8a").is_none());
+        game.apply_move(game.parse_uci_move("b7b8c").unwrap());
+        assert!(game.is_check());
+    }
+
+    #[test]
+    fn custom_pieces() {
+        // the same letter can mean different pieces in different games
+        let mut wazirs = CustomPieces::new();
+        let wazir = wazirs.define('e', "W").unwrap();
+        assert_eq!(wazirs.define('E', "W"), Some(wazir));
+        assert_eq!(wazirs.define('e', "F"), None);
+        let mut ferses = CustomPieces::new();
+        ferses.define('e', "F").unwrap();
+
+        let fen = "4k3/1P6/8/8/8/8/8/E3K3 w - - 0 1";
+        let game: Game = Game::from_fen_with_pieces(fen, &wazirs).unwrap();
+        assert_eq!(game.get_possible_moves("A1".to_string()).unwrap().len(), 2);
+        assert_eq!(game.clone().to_fen(), fen);
+        let game: Game = Game::from_fen_with_pieces(fen, &ferses).unwrap();
+        assert_eq!(
+            game.get_possible_moves("A1".to_string()).unwrap(),
+            vec!["B2"]
+        );
+        assert!(Game::from_fen(fen).is_none());
+
+        // the pieces are known to the moves of the game too
+        let mut game: Game =
+            Game::from_fen_with_pieces("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", &wazirs).unwrap();
+        assert!(game.parse_san("b8=E").is_none());
+        game.set_fairy_promotions(&[wazir, PieceType::Custom('x')]);
+        let mv = game.parse_san("b8=E").unwrap();
+        assert_eq!(game.parse_uci_move("b7b8e"), Some(mv));
+        game.apply_move(mv);
+        assert_eq!(game.to_fen(), "1E2k3/8/8/8/8/8/8/4K3 b - - 0 1");
+        assert!(game.parse_uci_move("b7b8x").is_none());
+    }
+
+    #[test]
+    fn capablanca_perft() {
+        let fen = "rnabqkbcnr/pppppppppp/10/10/10/10/PPPPPPPPPP/RNABQKBCNR w KQkq - 0 1";
+        let mut game = Game::<10, 8>::from_fen_sized(fen).unwrap();
+        assert_eq!(perft(&mut game, 1), 28);
+        assert_eq!(perft(&mut game, 2), 784);
+        assert_eq!(perft(&mut game, 3), 25228);
+    }
+}
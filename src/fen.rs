@@ -0,0 +1,445 @@
+//! Reading and writing positions in Forsyth-Edwards Notation (FEN).
+//!
+//! A FEN string describes a position in six fields: the pieces (rank 8 first, uppercase for
+//! white), the player to move, castling rights, the en passant square, the halfmove clock
+//! and the fullmove number. For example the starting position is
+//! `rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1`.
+//!
+//! For Chess960 the castling rights can also name the file of the rook, either always
+//! (Shredder-FEN, e.g. `HAha`) or only when `K` or `Q` would be ambiguous because there is
+//! another rook further out on the same side of the king (X-FEN).
+//!
+//! Boards of other sizes are written the same way, with as many ranks and files as the board
+//! has, e.g. [`GARDNER_FEN`] for 5x5 minichess. Runs of more than nine empty squares are
+//! written as a single number like `10`.
+
+use std::sync::Arc;
+
+use crate::fairy::CustomPieces;
+use crate::variant::{Pocket, Variant, CHECKS_TO_WIN};
+use crate::{offset, parse_square, zobrist, Color, Game, Piece, PieceType};
+
+/// FEN of the starting position.
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// FEN of the starting position of Gardner minichess, played on a 5x5 board.
+pub const GARDNER_FEN: &str = "rnbqk/ppppp/5/PPPPP/RNBQK w - - 0 1";
+
+/// FEN of the starting position of Los Alamos chess, played on a 6x6 board without bishops.
+pub const LOS_ALAMOS_FEN: &str = "rnqknr/pppppp/6/6/PPPPPP/RNQKNR w - - 0 1";
+
+/// FEN of the starting position of Grand Chess, played on a 10x10 board with the pawns on the
+/// third rank, and a chancellor and an archbishop next to the king and queen.
+pub const GRAND_FEN: &str =
+    "r8r/1nbqkcabn1/pppppppppp/10/10/10/10/PPPPPPPPPP/1NBQKCABN1/R8R w - - 0 1";
+
+impl Game {
+    /// Creates a game from a FEN string, returning None if it can't be read.
+    /// The halfmove clock and fullmove number may be left out. Positions where the castling
+    /// rights only make sense in Chess960 turn on Chess960 castling (see `set_chess960()`).
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::{Color, Game};
+    /// let game = Game::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+    /// assert_eq!(game.get_active_color(), Color::Black);
+    /// ```
+    pub fn from_fen(fen: &str) -> Option<Game> {
+        Game::from_fen_sized(fen)
+    }
+}
+
+impl<const WIDTH: usize, const HEIGHT: usize> Game<WIDTH, HEIGHT> {
+    /// Creates a game on a board of any size from a FEN string, like `from_fen()` does for
+    /// the standard board. The FEN has to have exactly as many ranks and files as the board.
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::fen::LOS_ALAMOS_FEN;
+    /// use vprytz_chess::Game;
+    /// let game = Game::<6, 6>::from_fen_sized(LOS_ALAMOS_FEN).unwrap();
+    /// assert_eq!(game.to_fen(), LOS_ALAMOS_FEN);
+    /// assert!(Game::<8, 8>::from_fen_sized(LOS_ALAMOS_FEN).is_none());
+    /// ```
+    pub fn from_fen_sized(fen: &str) -> Option<Self> {
+        Self::from_fen_with_pieces(fen, &CustomPieces::new())
+    }
+
+    /// Creates a game from a FEN string that may use the letters of the given fairy pieces,
+    /// see [`CustomPieces`]. The game keeps the pieces, so their letters can also be used in
+    /// moves and promotions.
+    pub fn from_fen_with_pieces(fen: &str, pieces: &CustomPieces) -> Option<Self> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.len() < 4 || fields.len() > 6 {
+            return None;
+        }
+
+        let mut game = Self::empty();
+        game.custom_pieces = Arc::new(pieces.clone());
+
+        // pieces, one rank at a time starting from the last rank (which is also row 0 of the board)
+        let ranks: Vec<&str> = fields[0].split('/').collect();
+        if ranks.len() != HEIGHT {
+            return None;
+        }
+        for (row, rank) in ranks.iter().enumerate() {
+            let mut col = 0;
+            let mut empty = 0;
+            for c in rank.chars() {
+                if let Some(digit) = c.to_digit(10) {
+                    empty = empty * 10 + digit as usize;
+                    continue;
+                }
+                col += empty;
+                empty = 0;
+                if col >= WIDTH {
+                    return None;
+                }
+                // the duck ('*') is neither, and is kept as white
+                game.board[row][col] = Some(Piece {
+                    color: if c.is_ascii_lowercase() {
+                        Color::Black
+                    } else {
+                        Color::White
+                    },
+                    piece: game.piece_from_char(c)?,
+                    untouched: false,
+                });
+                col += 1;
+            }
+            if col + empty != WIDTH {
+                return None;
+            }
+        }
+
+        // pawns may promote to the kinds of fairy pieces the game starts with
+        for piece in game.board.iter().flatten().flatten() {
+            if crate::fairy::is_fairy(piece.piece) && !game.fairy_promotions.contains(&piece.piece)
+            {
+                game.fairy_promotions.push(piece.piece);
+            }
+        }
+        game.fairy = !game.fairy_promotions.is_empty();
+
+        game.active_color = match fields[1] {
+            "w" => Color::White,
+            "b" => Color::Black,
+            _ => return None,
+        };
+
+        // pawns on their starting rank may still move two squares, except on small boards
+        let start_rows: Vec<(usize, Color)> = [Color::White, Color::Black]
+            .into_iter()
+            .filter_map(|color| Some((Self::pawn_row(color)?, color)))
+            .collect();
+        for col in 0..WIDTH {
+            for &(row, color) in &start_rows {
+                if let Some(piece) = game.board[row][col].as_mut() {
+                    if piece.piece == PieceType::Pawn && piece.color == color {
+                        piece.untouched = true;
+                    }
+                }
+            }
+        }
+
+        // castling rights are kept as untouched kings and rooks
+        if fields[2] != "-" {
+            for c in fields[2].chars() {
+                let (color, row) = if c.is_ascii_uppercase() {
+                    (Color::White, HEIGHT - 1)
+                } else {
+                    (Color::Black, 0)
+                };
+                let king_col = game.find_piece_in_row(row, color, PieceType::King)?;
+                let rook_col = match c.to_ascii_lowercase() {
+                    'k' => (king_col + 1..WIDTH)
+                        .rev()
+                        .find(|col| game.is_piece(row, *col, color, PieceType::Rook))?,
+                    'q' => (0..king_col)
+                        .find(|col| game.is_piece(row, *col, color, PieceType::Rook))?,
+                    // the file of the rook, for Chess960
+                    file @ 'a'..='z' => {
+                        let col = file as usize - 'a' as usize;
+                        if col >= WIDTH || !game.is_piece(row, col, color, PieceType::Rook) {
+                            return None;
+                        }
+                        col
+                    }
+                    _ => return None,
+                };
+                game.board[row][king_col].as_mut()?.untouched = true;
+                game.board[row][rook_col].as_mut()?.untouched = true;
+                if king_col != WIDTH / 2 || (rook_col != 0 && rook_col != WIDTH - 1) {
+                    game.chess960 = true;
+                }
+            }
+        }
+
+        // only keep the en passant square if a pawn can actually take on it, like after a move
+        if fields[3] != "-" {
+            let square = parse_square(fields[3], WIDTH, HEIGHT)?;
+            let op: i32 = match game.active_color {
+                Color::White => 1,
+                Color::Black => -1,
+            };
+            let can_take =
+                [-1, 1]
+                    .iter()
+                    .any(|side| match offset(square, (op, *side), WIDTH, HEIGHT) {
+                        Some(from) => {
+                            game.is_piece(from.0, from.1, game.active_color, PieceType::Pawn)
+                        }
+                        None => false,
+                    });
+            if can_take {
+                game.en_passant = Some(square);
+            }
+        }
+
+        game.halfmove_clock = match fields.get(4) {
+            Some(clock) => clock.parse().ok()?,
+            None => 0,
+        };
+        game.fullmove_number = match fields.get(5) {
+            Some(number) => number.parse().ok()?,
+            None => 1,
+        };
+
+        game.hash = zobrist::hash(&game);
+        game.update_state();
+        Some(game)
+    }
+
+    /// Returns the current position as a FEN string. Castling rights are written as X-FEN,
+    /// which is the same as normal FEN for everything but some Chess960 positions.
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::fen::STARTING_FEN;
+    /// use vprytz_chess::Game;
+    /// assert_eq!(Game::new().to_fen(), STARTING_FEN);
+    /// ```
+    pub fn to_fen(&self) -> String {
+        self.write_fen(false)
+    }
+
+    /// Returns the current position as a Shredder-FEN string, where castling rights are
+    /// always written as the files of the rooks (`HAha` instead of `KQkq`).
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::Game;
+    /// assert_eq!(Game::new().to_shredder_fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w HAha - 0 1");
+    /// ```
+    pub fn to_shredder_fen(&self) -> String {
+        self.write_fen(true)
+    }
+
+    fn write_fen(&self, shredder: bool) -> String {
+        let mut fen = String::new();
+
+        for (row, rank) in self.board.iter().enumerate() {
+            if row > 0 {
+                fen.push('/');
+            }
+            let mut empty = 0;
+            for (col, square) in rank.iter().enumerate() {
+                match square {
+                    Some(piece) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        fen.push(piece_to_char(*piece));
+                        if self.promoted[row][col] {
+                            fen.push('~');
+                        }
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+        }
+
+        // pieces in hand in Crazyhouse
+        if self.variant == Variant::Crazyhouse {
+            fen.push('[');
+            for color in [Color::White, Color::Black] {
+                let pocket = self.get_pocket(color);
+                for piece in Pocket::PIECES {
+                    for _ in 0..pocket.count(piece) {
+                        fen.push(piece_to_char(Piece {
+                            color,
+                            piece,
+                            untouched: false,
+                        }));
+                    }
+                }
+            }
+            fen.push(']');
+        }
+
+        fen.push_str(match self.active_color {
+            Color::White => " w ",
+            Color::Black => " b ",
+        });
+
+        let mut castling = String::new();
+        for (color, row) in [(Color::White, HEIGHT - 1), (Color::Black, 0)] {
+            let king_col = match self.find_piece_in_row(row, color, PieceType::King) {
+                Some(col) if self.board[row][col].unwrap().untouched => col,
+                _ => continue,
+            };
+            let rook = |col: &usize| self.is_piece(row, *col, color, PieceType::Rook);
+            let untouched_rook =
+                |col: &usize| rook(col) && self.board[row][*col].unwrap().untouched;
+
+            // K and Q mean the outermost rook on that side, any other rook is written as its file
+            let kingside: Vec<usize> = (king_col + 1..WIDTH).rev().collect();
+            let queenside: Vec<usize> = (0..king_col).collect();
+            let mut rights = String::new();
+            for (side, letter) in [(kingside, 'K'), (queenside, 'Q')] {
+                if let Some(col) = side.iter().find(|col| untouched_rook(col)) {
+                    if shredder || side.iter().find(|col| rook(col)) != Some(col) {
+                        rights.push((b'A' + *col as u8) as char);
+                    } else {
+                        rights.push(letter);
+                    }
+                }
+            }
+            if color == Color::Black {
+                rights = rights.to_lowercase();
+            }
+            castling.push_str(&rights);
+        }
+        if castling.is_empty() {
+            castling.push('-');
+        }
+        fen.push_str(&castling);
+
+        match self.en_passant {
+            Some(square) => fen.push_str(&format!(" {}", self.index_to_pos(square).to_lowercase())),
+            None => fen.push_str(" -"),
+        }
+
+        // checks left to give in Three-Check
+        if self.variant == Variant::ThreeCheck {
+            fen.push_str(&format!(
+                " {}+{}",
+                CHECKS_TO_WIN.saturating_sub(self.checks[0]),
+                CHECKS_TO_WIN.saturating_sub(self.checks[1])
+            ));
+        }
+
+        fen.push_str(&format!(
+            " {} {}",
+            self.halfmove_clock, self.fullmove_number
+        ));
+        fen
+    }
+
+    // the column of the first piece of the given kind in a row
+    pub(crate) fn find_piece_in_row(
+        &self,
+        row: usize,
+        color: Color,
+        piece: PieceType,
+    ) -> Option<usize> {
+        (0..WIDTH).find(|col| self.is_piece(row, *col, color, piece))
+    }
+
+    pub(crate) fn is_piece(&self, row: usize, col: usize, color: Color, piece: PieceType) -> bool {
+        matches!(self.board[row][col], Some(other) if other.color == color && other.piece == piece)
+    }
+}
+
+/// Returns the piece type for a FEN letter (either case), e.g. 'n' for a knight.
+/// The built in fairy pieces have letters too, see the [`fairy`](crate::fairy) module.
+pub fn piece_from_char(c: char) -> Option<PieceType> {
+    match c.to_ascii_lowercase() {
+        'k' => Some(PieceType::King),
+        'q' => Some(PieceType::Queen),
+        'r' => Some(PieceType::Rook),
+        'b' => Some(PieceType::Bishop),
+        'n' => Some(PieceType::Knight),
+        'p' => Some(PieceType::Pawn),
+        '*' => Some(PieceType::Duck),
+        c => crate::fairy::piece_from_letter(c),
+    }
+}
+
+/// Returns the FEN letter for a piece, uppercase for white and lowercase for black.
+pub fn piece_to_char(piece: Piece) -> char {
+    let c = match piece.piece {
+        PieceType::King => 'k',
+        PieceType::Queen => 'q',
+        PieceType::Rook => 'r',
+        PieceType::Bishop => 'b',
+        PieceType::Knight => 'n',
+        PieceType::Pawn => 'p',
+        PieceType::Duck => '*',
+        fairy => crate::fairy::letter(fairy).unwrap_or('?'),
+    };
+    match piece.color {
+        Color::White => c.to_ascii_uppercase(),
+        Color::Black => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::perft;
+
+    #[test]
+    fn round_trip() {
+        for fen in [
+            STARTING_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/8/8/8/8/8/8/R3K2R b Kq - 3 20",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        ] {
+            assert_eq!(Game::from_fen(fen).unwrap().to_fen(), fen);
+        }
+    }
+
+    #[test]
+    fn same_as_playing_the_moves() {
+        let mut game = Game::new();
+        game.make_move("E2".to_string(), "E4".to_string());
+        game.make_move("C7".to_string(), "C5".to_string());
+
+        let fen = Game::from_fen("rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2")
+            .unwrap();
+        // nothing can take on c6, so the en passant square is dropped and the positions are the same
+        assert_eq!(fen.get_zobrist_hash(), game.get_zobrist_hash());
+        assert_eq!(fen.to_fen(), game.to_fen());
+    }
+
+    #[test]
+    fn rejects_broken_fens() {
+        for fen in [
+            "",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNX w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBN1 w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq z9 0 1",
+        ] {
+            assert!(Game::from_fen(fen).is_none(), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn perft_from_fen() {
+        // "kiwipete" and position 3 from the chess programming wiki perft results
+        let mut kiwipete =
+            Game::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+        assert_eq!(perft(&mut kiwipete, 1), 48);
+        assert_eq!(perft(&mut kiwipete, 2), 2039);
+
+        let mut position3 = Game::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap();
+        assert_eq!(perft(&mut position3, 3), 2812);
+    }
+}
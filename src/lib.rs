@@ -11,10 +11,38 @@
 //! | ----------------------------------------------------------------------------- | --------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------- |
 //! | `pub fn new() -> Game`                                                        | Initialises a new board with pieces.                                                                                                                                                          |
 //! | `pub fn make_move(&mut self, from: String, to: String) -> Option<GameState>`  | If the current game state is `InProgress` and the move is legal, move a piece and return the resulting state of the game.                                                                     |
-//! | **Not yet implemeted** `pub fn set_promotion(&mut self, piece: String) -> ()`                        | Set the piece type that a peasant becames following a promotion.                                                                                                                              |
+//! | `pub fn set_promotion(&mut self, piece: String)`                              | Set the piece type that a peasant becames following a promotion.                                                                                                                              |
 //! | `pub fn get_game_state(&self) -> GameState`                                   | Get the current game state.                                                                                                                                                                   |
 //! | `pub fn get_possible_moves(&self, position: String) -> Optional<Vec<String>>` | If a piece is standing on the given tile, return all possible new positions of that piece. Don't forget to the rules for check. _(optional)_ Don't forget to include en passent and castling. |
 //!
+//! The [`search`] module can also be used to let the library pick a move by itself.
+//!
+//! Positions can be read and written as FEN with [`Game::from_fen`] and [`Game::to_fen`], and the
+//! `uci` and `xboard` binaries (see the [`uci`] and [`xboard`] modules) let chess GUIs play against
+//! the library.
+//!
+//! Games can be played in the terminal with the `play` binary, see the [`cli`] module, and saved or read as PGN with the [`pgn`] module.
+//! The `tui` binary (see the [`tui`] module) is a full-screen version with a colored board, moved around with the arrow keys.
+//!
+//! The [`engine`] module goes the other way, letting the library analyse games with an external UCI engine.
+//!
+//! Printing a game with `{}` draws the board, and the [`render`] module has other styles (Unicode pieces, ANSI colors, the board seen from Black's side).
+//!
+//! Diagrams of positions, with highlighted squares and arrows, can be drawn as SVG images with the [`svg`] module.
+//! The [`raster`] module draws them as PNG images instead, and whole games as animated GIFs.
+//!
+//! Games can be played with a chess clock, see the [`clock`] module.
+//!
+//! Chess960 games can be started with [`Game::new_chess960`], see the [`chess960`] module.
+//!
+//! Boards of other sizes, like 5x5 minichess or 10x10 boards, are played by giving the dimensions to [`Game`], e.g. `Game<5, 5>`.
+//!
+//! Fairy pieces like the archbishop and chancellor of Capablanca chess, or pieces of your own described in Betza notation, are found in the [`fairy`] module.
+//!
+//! Other variants (King of the Hill, Three-Check, Atomic, Crazyhouse, Antichess, Horde, Racing Kings and Duck chess) are described in the [`variant`] module.
+//!
+//! Bughouse matches, two Crazyhouse games played by teams of two, are found in the [`bughouse`] module.
+//!
 //! # Generate this README
 //!
 //! You need [cargo-readme](https://github.com/livioribeiro/cargo-readme) to generate this README.
@@ -24,9 +52,84 @@
 //! ```
 
 use std::fmt;
-
+use std::sync::Arc;
+
+use variant::{Pocket, Variant};
+
+pub mod bughouse;
+pub mod chess960;
+pub mod cli;
+pub mod clock;
+pub mod engine;
+pub mod eval;
+pub mod fairy;
+pub mod fen;
+mod notation;
+pub mod ordering;
+pub mod pgn;
+#[cfg(feature = "images")]
+pub mod raster;
+pub mod render;
+mod san;
+pub mod search;
+pub mod svg;
+pub mod timeman;
+pub mod tt;
+pub mod tui;
+pub mod uci;
+pub mod variant;
+pub mod xboard;
+mod zobrist;
+
+// the number of files and ranks of a standard board, the default size of a Game
 const BOARD_SIZE: usize = 8;
 
+// the board as a 2d array, row 0 is the last rank and column 0 is the A file
+type Board<const WIDTH: usize = BOARD_SIZE, const HEIGHT: usize = BOARD_SIZE> =
+    [[Option<Piece>; WIDTH]; HEIGHT];
+
+// offsets (row, column) for the pieces that "jump" to their target square
+const KNIGHT_OFFSETS: [(i32, i32); 8] = [
+    (-2, -1),
+    (-2, 1),
+    (2, -1),
+    (2, 1),
+    (-1, -2),
+    (1, -2),
+    (-1, 2),
+    (1, 2),
+];
+const KING_OFFSETS: [(i32, i32); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+
+// directions (row, column) for the pieces that slide until they hit something
+const ROOK_DIRECTIONS: [(i32, i32); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+const BISHOP_DIRECTIONS: [(i32, i32); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+
+// pieces a pawn can be promoted to, in the order they are generated
+const PROMOTION_PIECES: [PieceType; 4] = [
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+];
+
+// stored in the history for a null move, see do_null_move()
+const NULL_MOVE: Move = Move {
+    from: (0, 0),
+    to: (0, 0),
+    promotion: None,
+    drop: None,
+};
+
 /// Possible states of the game is represented using this enum.
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum GameState {
@@ -35,15 +138,59 @@ pub enum GameState {
     GameOver,
 }
 
+/// How a finished game ended, see `Game::get_outcome()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The winner checkmated the other player.
+    Checkmate { winner: Color },
+    /// The player to move has no legal moves but isn't in check, a draw.
+    Stalemate,
+    /// The other player ran out of time.
+    Timeout { winner: Color },
+    /// A player ran out of time, but the other player couldn't possibly have mated, a draw.
+    TimeoutDraw,
+    /// The winner won by a rule of the variant being played, e.g. reaching the center in King of the Hill.
+    VariantWin { winner: Color },
+    /// The game was drawn by a rule of the variant being played.
+    VariantDraw,
+}
+
+impl Outcome {
+    /// Returns the winner, or None for a draw.
+    pub fn winner(&self) -> Option<Color> {
+        match *self {
+            Outcome::Checkmate { winner }
+            | Outcome::Timeout { winner }
+            | Outcome::VariantWin { winner } => Some(winner),
+            Outcome::Stalemate | Outcome::TimeoutDraw | Outcome::VariantDraw => None,
+        }
+    }
+}
+
 /// Possible colors for pieces is represented using this enum.
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Color {
     White,
     Black,
 }
 
+impl Color {
+    /// Returns the other color.
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::Color;
+    /// assert_eq!(Color::White.opposite(), Color::Black);
+    /// ```
+    pub fn opposite(self) -> Color {
+        match self {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        }
+    }
+}
+
 // Possible types of pieces is represented using this enum.
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum PieceType {
     King,
     Queen,
@@ -51,6 +198,20 @@ pub enum PieceType {
     Bishop,
     Knight,
     Pawn,
+    /// Moves like a bishop or a knight, see the [`fairy`] module.
+    Archbishop,
+    /// Moves like a rook or a knight.
+    Chancellor,
+    /// Moves like a queen or a knight.
+    Amazon,
+    /// Leaps three squares one way and one square the other.
+    Camel,
+    /// Moves like a queen, but only by hopping over a piece and landing right behind it.
+    Grasshopper,
+    /// A piece defined with `fairy::CustomPieces`, known by its lowercase FEN letter.
+    Custom(char),
+    /// The neutral piece of Duck chess, which blocks both players and can't be captured.
+    Duck,
 }
 
 // copyed example from https://users.rust-lang.org/t/how-can-i-implement-fmt-display-for-enum/24111/2
@@ -63,6 +224,13 @@ impl fmt::Display for PieceType {
             PieceType::Bishop => write!(f, "B "),
             PieceType::Knight => write!(f, "Kn"),
             PieceType::Pawn => write!(f, "P "),
+            PieceType::Archbishop => write!(f, "A "),
+            PieceType::Chancellor => write!(f, "C "),
+            PieceType::Amazon => write!(f, "M "),
+            PieceType::Camel => write!(f, "L "),
+            PieceType::Grasshopper => write!(f, "G "),
+            PieceType::Duck => write!(f, "* "),
+            PieceType::Custom(letter) => write!(f, "{} ", letter.to_ascii_uppercase()),
         }
     }
 }
@@ -95,17 +263,143 @@ impl fmt::Display for Piece {
     }
 }
 
+/// Represents a single move, using the same (row, column) indices as the board.
+/// Castling is stored as the king moving onto the square of its own rook, so that
+/// the rook taking part in the castling is always known. In Crazyhouse a piece can be dropped
+/// from the pocket instead, which is stored with `drop` set and `from` the same as `to`.
+/// # Examples
+/// ```
+/// use vprytz_chess::Move;
+/// let mv = Move {
+///     from: (6, 4),
+///     to: (4, 4),
+///     promotion: None,
+///     drop: None,
+/// };
+/// assert_eq!(mv.to_string(), "e2e4");
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Move {
+    pub from: (usize, usize),
+    pub to: (usize, usize),
+    pub promotion: Option<PieceType>,
+    pub drop: Option<PieceType>,
+}
+
+/// Prints the move in lowercase coordinate notation, e.g. "e2e4" or "e7e8q".
+/// Drops are written as the piece letter and the square, e.g. "N@f3".
+/// The ranks are those of a standard board, use `Game::move_to_uci()` for other board sizes.
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.coordinates(BOARD_SIZE))
+    }
+}
+
+impl Move {
+    // the move in coordinate notation, on a board with the given number of ranks
+    fn coordinates(&self, height: usize) -> String {
+        if let Some(piece) = self.drop {
+            let letter = fen::piece_to_char(Piece {
+                color: Color::White,
+                piece,
+                untouched: false,
+            });
+            let to = square_name(self.to, height).to_lowercase();
+            return format!("{}@{}", letter, to);
+        }
+        let from = square_name(self.from, height).to_lowercase();
+        let to = square_name(self.to, height).to_lowercase();
+        let promotion = match self.promotion {
+            Some(piece) => fen::piece_to_char(Piece {
+                color: Color::Black,
+                piece,
+                untouched: false,
+            })
+            .to_string(),
+            None => String::new(),
+        };
+        format!("{}{}{}", from, to, promotion)
+    }
+}
+
+// everything needed to take back a move, saved before each move is made
+#[derive(Clone)]
+struct Snapshot<const WIDTH: usize, const HEIGHT: usize> {
+    state: GameState,
+    board: Board<WIDTH, HEIGHT>,
+    active_color: Color,
+    en_passant: Option<(usize, usize)>,
+    halfmove_clock: u32,
+    fullmove_number: u32,
+    hash: u64,
+    checks: [u32; 2],
+    pockets: [Pocket; 2],
+    promoted: variant::Promoted<WIDTH, HEIGHT>,
+    duck_to_move: bool,
+    clock: Option<clock::ClockTimes>,
+}
+
 /// Represents a chess game, holding a board (2d array with all pieces) and the current state of the game.
 /// A new game can be created by calling the new() function.
+///
+/// The board is 8x8 unless other dimensions are given as `Game<WIDTH, HEIGHT>`, e.g. `Game<5, 5>` for
+/// Gardner minichess or `Game<10, 8>` for Capablanca chess. Such games are read from FEN with
+/// `from_fen_sized()` and follow the rules of standard chess, with the king castling to the
+/// second file from either edge. On boards with fewer than eight ranks pawns never move two
+/// squares, and on boards with ten or more ranks they start (and move two squares from) the
+/// third rank, as in Grand Chess. Squares are still named by their file letter and rank
+/// number, like "A1" or "J10".
 /// # Examples
 /// ```
 /// use vprytz_chess::Game;
 /// let mut game = Game::new();
 /// // call functions on game, to move pieces and so on
 /// ```
-pub struct Game {
+/// ```
+/// use vprytz_chess::fen::GARDNER_FEN;
+/// use vprytz_chess::Game;
+/// let game = Game::<5, 5>::from_fen_sized(GARDNER_FEN).unwrap();
+/// assert_eq!(game.get_legal_moves().len(), 7);
+/// ```
+#[derive(Clone)]
+pub struct Game<const WIDTH: usize = BOARD_SIZE, const HEIGHT: usize = BOARD_SIZE> {
     state: GameState,
-    board: [[Option<Piece>; BOARD_SIZE]; BOARD_SIZE],
+    board: Board<WIDTH, HEIGHT>,
+    active_color: Color,
+    // square that can be captured en passant, only set right after a double pawn move
+    en_passant: Option<(usize, usize)>,
+    // number of half moves since the last capture or pawn move (for the fifty move rule)
+    halfmove_clock: u32,
+    fullmove_number: u32,
+    // zobrist hash of the position, updated after every move
+    hash: u64,
+    // piece type used when a pawn is promoted, see set_promotion()
+    promotion: PieceType,
+    history: Vec<(Move, Snapshot<WIDTH, HEIGHT>)>,
+    // see set_clock(), not part of the position (but rewound by undo_move())
+    clock: Option<clock::Clock>,
+    // castling is written as the king taking its rook, see set_chess960()
+    chess960: bool,
+    variant: Variant,
+    // checks given by white and black, only counted in Three-Check
+    checks: [u32; 2],
+    // pieces in hand for white and black, and the pieces that were pawns once (Crazyhouse)
+    pockets: [Pocket; 2],
+    promoted: variant::Promoted<WIDTH, HEIGHT>,
+    // true when the player has made their move and still has to move the duck (Duck chess)
+    duck_to_move: bool,
+    // true if there are fairy pieces in the game, which are slower to find moves for
+    fairy: bool,
+    // the fairy pieces pawns may promote to besides the usual ones, see set_fairy_promotions()
+    fairy_promotions: Vec<PieceType>,
+    // the pieces given to from_fen_with_pieces(), shared by the copies of the game
+    custom_pieces: Arc<fairy::CustomPieces>,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Game {
@@ -116,10 +410,7 @@ impl Game {
     /// let mut game = Game::new();
     /// ```
     pub fn new() -> Game {
-        let mut game = Game {
-            state: GameState::InProgress,
-            board: [[None; BOARD_SIZE]; BOARD_SIZE],
-        };
+        let mut game = Game::empty();
         // add pieces
         game.setup_initial_board();
 
@@ -134,7 +425,7 @@ impl Game {
     /// let mut game = Game::new();
     /// game.setup_initial_board(); // redundant since new() calls this function, but can be called again to "reset" board later on
     /// ```
-    pub fn setup_initial_board(&mut self) -> () {
+    pub fn setup_initial_board(&mut self) {
         // calling this will also "reset board"
         let white_pawn = Some(Piece {
             color: Color::White,
@@ -225,6 +516,46 @@ impl Game {
             white_knight,
             white_rook,
         ];
+
+        // reset everything else as well, white always starts
+        self.state = GameState::InProgress;
+        self.active_color = Color::White;
+        self.en_passant = None;
+        self.halfmove_clock = 0;
+        self.fullmove_number = 1;
+        self.history.clear();
+        self.chess960 = false;
+        self.checks = [0, 0];
+        self.pockets = [Pocket::default(); 2];
+        self.promoted = [[false; BOARD_SIZE]; BOARD_SIZE];
+        self.hash = zobrist::hash(self);
+    }
+}
+
+impl<const WIDTH: usize, const HEIGHT: usize> Game<WIDTH, HEIGHT> {
+    // a game without any pieces on the board, white to move
+    fn empty() -> Self {
+        Game {
+            state: GameState::InProgress,
+            board: [[None; WIDTH]; HEIGHT],
+            active_color: Color::White,
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            hash: 0,
+            promotion: PieceType::Queen,
+            history: Vec::new(),
+            clock: None,
+            chess960: false,
+            variant: Variant::Standard,
+            checks: [0, 0],
+            pockets: [Pocket::default(); 2],
+            promoted: [[false; WIDTH]; HEIGHT],
+            duck_to_move: false,
+            fairy: false,
+            fairy_promotions: Vec::new(),
+            custom_pieces: Arc::default(),
+        }
     }
 
     /// If the current game state is InProgress and the move is legal,
@@ -236,41 +567,162 @@ impl Game {
     /// game.make_move("D2".to_string(), "D4".to_string()); // move white pawn at D2 to D4 (will only be allowed if move is legal, checked by get_possible_moves())
     /// ```
     pub fn make_move(&mut self, from: String, to: String) -> Option<GameState> {
-        // check if move is legal by checking if "to" position is in get_possible_moves()
-        let possible_moves = self.get_possible_moves(from.to_string());
+        if self.get_game_state() == GameState::GameOver {
+            return None;
+        }
 
-        // check that "to" is in possible_moves
-        // sourcde https://stackoverflow.com/questions/58368801/how-do-i-check-if-a-thing-is-in-a-vector#58368936
-        if possible_moves.unwrap().contains(&to) {
-            // move piece
-            let from_index = self.pos_to_index(from.to_string());
-            let to_index = self.pos_to_index(to.to_string());
+        let from_index = parse_square(&from, WIDTH, HEIGHT)?;
+        let to_index = parse_square(&to, WIDTH, HEIGHT)?;
+
+        // find the legal move going between the two squares, "to" is compared the same way
+        // it is shown by get_possible_moves() (so castling is given as the square the king ends up on),
+        // but castling can also be given as the king moving onto its rook, which is the only way
+        // when the king would end up on a square it can also move to normally (in Chess960)
+        let moves: Vec<Move> = self
+            .get_legal_moves()
+            .into_iter()
+            .filter(|mv| {
+                // the duck is moved from its square (or placed with "from" and "to" the same)
+                mv.from == from_index
+                    && matches!(mv.drop, None | Some(PieceType::Duck))
+                    && (mv.promotion.is_none() || mv.promotion == Some(self.promotion))
+            })
+            .collect();
+        let mv = moves
+            .iter()
+            .find(|mv| mv.to == to_index)
+            .or_else(|| moves.iter().find(|mv| self.target_square(**mv) == to_index))
+            .copied()?;
+
+        Some(self.apply_move(mv))
+    }
 
-            let piece = self.board[from_index.0][from_index.1];
+    /// Plays a move (e.g. one returned by get_legal_moves() or the search) and returns
+    /// the resulting state of the game. The move is expected to be legal.
+    /// If the player to move has run out of time the move isn't made.
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::Game;
+    /// let mut game = Game::new();
+    /// let mv = game.get_legal_moves()[0];
+    /// game.apply_move(mv);
+    /// ```
+    pub fn apply_move(&mut self, mv: Move) -> GameState {
+        if self.flag_fallen() {
+            return GameState::GameOver;
+        }
+        self.do_move(mv);
+        self.update_state();
+        // in Duck chess the clock keeps running until the duck has been moved too
+        if !self.duck_to_move {
+            self.press_clock();
+        }
+        self.state
+    }
 
-            // set piece as touched
-            let mut piece = piece.unwrap();
-            piece.untouched = false;
+    /// Takes back the last move made, returning it (or None if no moves have been made).
+    /// The clock, if the game has one, is set back to the times from before the move and
+    /// runs for the player to move.
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::Game;
+    /// let mut game = Game::new();
+    /// game.make_move("E2".to_string(), "E4".to_string());
+    /// assert!(game.undo_move().is_some());
+    /// assert!(game.undo_move().is_none());
+    /// ```
+    pub fn undo_move(&mut self) -> Option<Move> {
+        let (mv, snapshot) = self.history.pop()?;
+
+        self.state = snapshot.state;
+        self.board = snapshot.board;
+        self.active_color = snapshot.active_color;
+        self.en_passant = snapshot.en_passant;
+        self.halfmove_clock = snapshot.halfmove_clock;
+        self.fullmove_number = snapshot.fullmove_number;
+        self.hash = snapshot.hash;
+        self.checks = snapshot.checks;
+        self.pockets = snapshot.pockets;
+        self.promoted = snapshot.promoted;
+        self.duck_to_move = snapshot.duck_to_move;
+        // moves made before the clock was set only switch it back
+        if let Some(clock) = self.clock.as_mut() {
+            match snapshot.clock {
+                Some(times) => clock.restore(times, self.active_color),
+                None => clock.start(self.active_color),
+            }
+        }
 
-            self.board[from_index.0][from_index.1] = None;
-            self.board[to_index.0][to_index.1] = Some(piece);
+        Some(mv)
+    }
 
-            // check if game is over
-            // if self.is_checkmate() {
-            //     self.state = GameState::GameOver;
-            // } else if self.is_check() {
-            //     self.state = GameState::Check;
-            // }
+    /// Returns the moves made so far, first move first.
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::Game;
+    /// let mut game = Game::new();
+    /// game.make_move("E2".to_string(), "E4".to_string());
+    /// assert_eq!(game.get_move_history()[0].to_string(), "e2e4");
+    /// ```
+    pub fn get_move_history(&self) -> Vec<Move> {
+        self.history.iter().map(|(mv, _)| *mv).collect()
+    }
 
-            return Some(self.get_game_state());
-        } else {
-            return None;
+    /// Returns the position the game started from (before any moves were made),
+    /// which is not always the usual starting position, e.g. for games read from FEN.
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::Game;
+    /// let mut game = Game::new();
+    /// game.make_move("E2".to_string(), "E4".to_string());
+    /// assert_eq!(game.get_starting_position().get_zobrist_hash(), Game::new().get_zobrist_hash());
+    /// ```
+    pub fn get_starting_position(&self) -> Self {
+        let mut start = self.position();
+        if let Some((_, snapshot)) = self.history.first() {
+            start.state = snapshot.state;
+            start.board = snapshot.board;
+            start.active_color = snapshot.active_color;
+            start.en_passant = snapshot.en_passant;
+            start.halfmove_clock = snapshot.halfmove_clock;
+            start.fullmove_number = snapshot.fullmove_number;
+            start.hash = snapshot.hash;
+            start.checks = snapshot.checks;
+            start.pockets = snapshot.pockets;
+            start.promoted = snapshot.promoted;
+            start.duck_to_move = snapshot.duck_to_move;
         }
+        start
     }
 
     /// Set the piece type that a peasant becames following a promotion.
-    pub fn set_promotion(&mut self, piece: String) -> () {
-        ()
+    /// Accepts the name of the piece ("queen", "rook", "bishop", "knight") or its letter,
+    /// anything else is ignored. Fairy pieces are given the same way, e.g. "chancellor" or "c".
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::Game;
+    /// let mut game = Game::new();
+    /// game.set_promotion("knight".to_string()); // pawns reaching the last rank now become knights
+    /// ```
+    pub fn set_promotion(&mut self, piece: String) {
+        self.promotion = match piece.to_lowercase().as_str() {
+            "queen" | "q" => PieceType::Queen,
+            "rook" | "r" => PieceType::Rook,
+            "bishop" | "b" => PieceType::Bishop,
+            "knight" | "n" | "kn" => PieceType::Knight,
+            // only in Antichess
+            "king" | "k" => PieceType::King,
+            "archbishop" => PieceType::Archbishop,
+            "chancellor" => PieceType::Chancellor,
+            "amazon" => PieceType::Amazon,
+            "camel" => PieceType::Camel,
+            "grasshopper" => PieceType::Grasshopper,
+            letter => match letter.chars().next().and_then(|c| self.piece_from_char(c)) {
+                Some(PieceType::Pawn | PieceType::Duck) => return,
+                Some(piece) if letter.len() == 1 => piece,
+                _ => return,
+            },
+        };
     }
 
     /// Get the current game state.
@@ -281,9 +733,59 @@ impl Game {
     /// game.get_game_state(); // returns GameState::InProgress
     /// ```
     pub fn get_game_state(&self) -> GameState {
+        if self.flag_fallen() {
+            return GameState::GameOver;
+        }
         self.state
     }
 
+    /// Returns how the game ended, or None if it is still going on.
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::{Color, Game, Outcome};
+    /// let mut game = Game::new();
+    /// for (from, to) in [("F2", "F3"), ("E7", "E5"), ("G2", "G4"), ("D8", "H4")] {
+    ///     game.make_move(from.to_string(), to.to_string());
+    /// }
+    /// assert_eq!(game.get_outcome(), Some(Outcome::Checkmate { winner: Color::Black }));
+    /// ```
+    pub fn get_outcome(&self) -> Option<Outcome> {
+        let winner = self.active_color.opposite();
+        if self.flag_fallen() {
+            return Some(if self.has_mating_material(winner) {
+                Outcome::Timeout { winner }
+            } else {
+                Outcome::TimeoutDraw
+            });
+        }
+        if let Some(outcome) = self.variant_outcome() {
+            return Some(outcome);
+        }
+        match self.state {
+            // in Antichess the player without moves (or pieces) wins, and so does a player
+            // without moves in Duck chess
+            GameState::GameOver if matches!(self.variant, Variant::Antichess | Variant::Duck) => {
+                Some(Outcome::VariantWin {
+                    winner: self.active_color,
+                })
+            }
+            GameState::GameOver if self.is_check() => Some(Outcome::Checkmate { winner }),
+            GameState::GameOver => Some(Outcome::Stalemate),
+            _ => None,
+        }
+    }
+
+    /// Get the color of the player whose turn it is.
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::{Color, Game};
+    /// let game = Game::new();
+    /// assert_eq!(game.get_active_color(), Color::White);
+    /// ```
+    pub fn get_active_color(&self) -> Color {
+        self.active_color
+    }
+
     /// If a piece is standing on the given tile, return all possible
     /// new positions of that piece.
     /// # Examples
@@ -292,11 +794,8 @@ impl Game {
     /// let mut game = Game::new();
     /// game.get_possible_moves("D2".to_string()); // returns all possible moves for white pawn at D2
     /// ```
-    /// # TODO
-    /// - check if move is legal (e.g. if king is in check after move)
-    /// - check if pawn can be promoted
-    /// - check if pawn can be captured en passant
-    /// - check for all types of pieces (not yet done)
+    /// Moves leaving the own king in check are not included. Castling is given as the square
+    /// the king ends up on and a promotion only shows up once.
     /// # Panics
     /// Panics if the given position is not on the board.
     /// # Errors
@@ -305,181 +804,649 @@ impl Game {
         let pos = self.pos_to_index(postion);
 
         // get piece at given position
-        let piece = self.board[pos.0][pos.1];
+        let piece = self.board[pos.0][pos.1]?;
+
+        let mut moves = Vec::with_capacity(28);
+        self.piece_moves(pos, &mut moves);
+        // in Antichess pieces that can't capture can't move at all when another piece can
+        if self.variant == Variant::Antichess && self.must_capture(piece.color) {
+            moves.retain(|mv| self.is_capture(*mv));
+        }
+
+        let mut vec: Vec<String> = Vec::with_capacity(moves.len());
+        for mv in moves {
+            if !self.is_legal(mv, piece.color) {
+                continue;
+            }
+            // the four promotions all end up on the same square
+            let square = self.index_to_pos(self.target_square(mv));
+            if !vec.contains(&square) {
+                vec.push(square);
+            }
+        }
+
+        Some(vec)
+    }
+
+    /// Returns every legal move for the player whose turn it is.
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::Game;
+    /// let game = Game::new();
+    /// assert_eq!(game.get_legal_moves().len(), 20);
+    /// ```
+    pub fn get_legal_moves(&self) -> Vec<Move> {
+        if self.variant_outcome().is_some() {
+            return Vec::new();
+        }
+        let mut moves = self.pseudo_legal_moves();
+        moves.retain(|mv| self.is_legal(*mv, self.active_color));
+        moves
+    }
+
+    /// Returns true if the player whose turn it is has their king attacked.
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::Game;
+    /// let game = Game::new();
+    /// assert!(!game.is_check());
+    /// ```
+    pub fn is_check(&self) -> bool {
+        self.king_attacked(self.active_color)
+    }
+
+    /// Returns false if the player can't checkmate by any series of legal moves, whatever the other player does.
+    /// A lone king or a single minor piece can't mate, unless the other player has pieces that can get in the
+    /// way of their own king.
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::{Color, Game};
+    /// let game = Game::from_fen("4k3/8/8/8/8/8/8/3NK3 w - - 0 1").unwrap();
+    /// assert!(!game.has_mating_material(Color::White));
+    /// ```
+    pub fn has_mating_material(&self, color: Color) -> bool {
+        let mut minors = Vec::new();
+        let mut other_pieces = 0;
+        for (row, rank) in self.board.iter().enumerate() {
+            for (col, square) in rank.iter().enumerate() {
+                let piece = match square {
+                    Some(piece) if !matches!(piece.piece, PieceType::King | PieceType::Duck) => {
+                        piece
+                    }
+                    _ => continue,
+                };
+                if piece.color != color {
+                    other_pieces += 1;
+                    continue;
+                }
+                match piece.piece {
+                    PieceType::Knight | PieceType::Bishop => {
+                        minors.push((piece.piece, (row + col) % 2))
+                    }
+                    _ => return true,
+                }
+            }
+        }
+
+        match minors.as_slice() {
+            [] => false,
+            [_] => other_pieces > 0,
+            // bishops all on the same color can't mate a lone king either
+            _ => {
+                other_pieces > 0
+                    || !minors
+                        .iter()
+                        .all(|minor| minor.0 == PieceType::Bishop && minor.1 == minors[0].1)
+            }
+        }
+    }
 
-        let op: i32 = match piece.unwrap().color {
+    // all moves for the active color, without checking if they leave the own king in check
+    pub(crate) fn pseudo_legal_moves(&self) -> Vec<Move> {
+        let mut moves = Vec::with_capacity(64);
+        if self.duck_to_move {
+            self.duck_moves(&mut moves);
+            return moves;
+        }
+        for row in 0..HEIGHT {
+            for col in 0..WIDTH {
+                if let Some(piece) = self.board[row][col] {
+                    if piece.color == self.active_color {
+                        self.piece_moves((row, col), &mut moves);
+                    }
+                }
+            }
+        }
+        match self.variant {
+            Variant::Crazyhouse => self.drop_moves(&mut moves),
+            Variant::Antichess => self.antichess_moves(&mut moves),
+            _ => {}
+        }
+        if !self.fairy_promotions.is_empty() {
+            self.add_fairy_promotions(&mut moves);
+        }
+        moves
+    }
+
+    // push all moves of the piece at the given position to "moves", not checking for check
+    fn piece_moves(&self, pos: (usize, usize), moves: &mut Vec<Move>) {
+        let piece = match self.board[pos.0][pos.1] {
+            Some(piece) => piece,
+            None => return,
+        };
+        // in the middle of a turn in Duck chess only the duck moves, and the duck only then
+        if self.duck_to_move != (piece.piece == PieceType::Duck) {
+            return;
+        }
+
+        // different move sets for different PieceTypes
+        match piece.piece {
+            PieceType::Pawn => self.pawn_moves(pos, piece, moves),
+            PieceType::Knight => self.jump_moves(pos, piece, &KNIGHT_OFFSETS, moves),
+            PieceType::Bishop => self.slide_moves(pos, piece, &BISHOP_DIRECTIONS, moves),
+            PieceType::Rook => self.slide_moves(pos, piece, &ROOK_DIRECTIONS, moves),
+            PieceType::Queen => {
+                self.slide_moves(pos, piece, &ROOK_DIRECTIONS, moves);
+                self.slide_moves(pos, piece, &BISHOP_DIRECTIONS, moves);
+            }
+            PieceType::King => {
+                let first = moves.len();
+                self.jump_moves(pos, piece, &KING_OFFSETS, moves);
+                // kings can't capture in Atomic, they would explode
+                if self.variant == Variant::Atomic {
+                    let steps: Vec<Move> = moves
+                        .drain(first..)
+                        .filter(|mv| self.board[mv.to.0][mv.to.1].is_none())
+                        .collect();
+                    moves.extend(steps);
+                }
+                self.castling_moves(pos, piece, moves);
+            }
+            PieceType::Duck => self.duck_moves(moves),
+            fairy => self.fairy_moves(pos, fairy, moves),
+        }
+    }
+
+    fn pawn_moves(&self, pos: (usize, usize), piece: Piece, moves: &mut Vec<Move>) {
+        // white pawns move "up" the array (towards row 0) and black pawns "down"
+        let op: i32 = match piece.color {
             Color::White => 1,
             Color::Black => -1,
         };
 
-        // different move sets for different PieceTypes
-        match piece {
-            //
-            // PAWN
-            //
-            Some(Piece {
-                piece: PieceType::Pawn,
-                ..
-            }) => {
-                let mut vec: Vec<String> = Vec::with_capacity(5);
-
-                // add possible moves only if they are empty
-                if self.board[(pos.0 as i32 - 1 * op) as usize][pos.1].is_none() {
-                    vec.push(self.index_to_pos(((pos.0 as i32 - 1 * op) as usize, pos.1)));
-                    // forward (up/down) one
+        let forward = match offset(pos, (-op, 0), WIDTH, HEIGHT) {
+            Some(forward) => forward,
+            None => return,
+        };
+
+        // forward (up/down) one, only if empty
+        if self.board[forward.0][forward.1].is_none() {
+            push_pawn_move(pos, forward, HEIGHT, moves);
+
+            // forward (up/down) two (only if first move!)
+            if piece.untouched || self.horde_double_step(pos, piece) {
+                if let Some(two) = offset(pos, (-2 * op, 0), WIDTH, HEIGHT) {
+                    if self.board[two.0][two.1].is_none() {
+                        moves.push(Move {
+                            from: pos,
+                            to: two,
+                            promotion: None,
+                            drop: None,
+                        });
+                    }
                 }
+            }
+        }
 
-                if self.board[(pos.0 as i32 - 2 * op) as usize][pos.1].is_none()
-                    && self.board[(pos.0 as i32 - 1 * op) as usize][pos.1].is_none()
-                    && piece.unwrap().untouched
-                {
-                    vec.push(self.index_to_pos(((pos.0 as i32 - 2 * op) as usize, pos.1)));
-                    // forward (up/down) two (only if first move!)
+        // attack moves only if the specified positions is occupied by an enemy piece,
+        // or if it is the square that can be captured en passant
+        for side in [-1, 1] {
+            if let Some(target) = offset(pos, (-op, side), WIDTH, HEIGHT) {
+                let enemy = match self.board[target.0][target.1] {
+                    Some(other) => other.color != piece.color && other.piece != PieceType::Duck,
+                    None => self.en_passant == Some(target) && self.active_color == piece.color,
+                };
+                if enemy {
+                    push_pawn_move(pos, target, HEIGHT, moves);
                 }
+            }
+        }
+    }
 
-                // attack moves only if the specified positions is occupied by an enemy piece
-                // we check that there is something there and that the piece there actually has a different color
-                // than our piece
-                if self.board[(pos.0 as i32 - 1 * op) as usize][pos.1 + 1].is_some()
-                    && self.board[(pos.0 as i32 - 1 * op) as usize][pos.1 + 1]
-                        .unwrap()
-                        .color
-                        != piece.unwrap().color
-                {
-                    vec.push(self.index_to_pos(((pos.0 as i32 - 1 * op) as usize, pos.1 + 1)));
-                    // forward (up/down) one and right (attack right)
+    // moves for pieces that jump directly to a square (knight and king)
+    fn jump_moves(
+        &self,
+        pos: (usize, usize),
+        piece: Piece,
+        offsets: &[(i32, i32)],
+        moves: &mut Vec<Move>,
+    ) {
+        // check for each position that it is on the board and that it is either empty or occupied by an enemy piece
+        for step in offsets {
+            if let Some(target) = offset(pos, *step, WIDTH, HEIGHT) {
+                match self.board[target.0][target.1] {
+                    Some(other) if other.color == piece.color || other.piece == PieceType::Duck => {
+                    }
+                    _ => moves.push(Move {
+                        from: pos,
+                        to: target,
+                        promotion: None,
+                        drop: None,
+                    }),
                 }
+            }
+        }
+    }
 
-                if self.board[(pos.0 as i32 - 1 * op) as usize][pos.1 - 1].is_some()
-                    && self.board[(pos.0 as i32 - 1 * op) as usize][pos.1 - 1]
-                        .unwrap()
-                        .color
-                        != piece.unwrap().color
-                {
-                    vec.push(self.index_to_pos(((pos.0 as i32 - 1 * op) as usize, pos.1 - 1)));
-                    // forward (up/down) one and left (attack left)
+    // moves for pieces that slide along lines until they hit something (bishop, rook and queen)
+    fn slide_moves(
+        &self,
+        pos: (usize, usize),
+        piece: Piece,
+        directions: &[(i32, i32)],
+        moves: &mut Vec<Move>,
+    ) {
+        for direction in directions {
+            let mut current = pos;
+            while let Some(target) = offset(current, *direction, WIDTH, HEIGHT) {
+                match self.board[target.0][target.1] {
+                    None => moves.push(Move {
+                        from: pos,
+                        to: target,
+                        promotion: None,
+                        drop: None,
+                    }),
+                    Some(other) => {
+                        // we may take an enemy piece, but can't go any further either way
+                        if other.color != piece.color && other.piece != PieceType::Duck {
+                            moves.push(Move {
+                                from: pos,
+                                to: target,
+                                promotion: None,
+                                drop: None,
+                            });
+                        }
+                        break;
+                    }
                 }
+                current = target;
+            }
+        }
+    }
 
-                return Some(vec);
+    // castling with any untouched rook on the same row as the untouched king, the king always
+    // ends up on the second file from the edge (C or G) and the rook next to it (like in Chess960)
+    fn castling_moves(&self, pos: (usize, usize), piece: Piece, moves: &mut Vec<Move>) {
+        let enemy = piece.color.opposite();
+        if !piece.untouched || self.variant == Variant::Antichess || self.king_attacked(piece.color)
+        {
+            return;
+        }
+
+        let row = pos.0;
+        for rook_col in 0..WIDTH {
+            let rook = match self.board[row][rook_col] {
+                Some(rook) => rook,
+                None => continue,
+            };
+            if rook.piece != PieceType::Rook || rook.color != piece.color || !rook.untouched {
+                continue;
             }
-            //
-            // ROOK
-            //
-            Some(Piece {
-                piece: PieceType::Rook,
-                ..
-            }) => {
-                let vec: Vec<String> = Vec::with_capacity(5);
-
-                // get all possible moves in all directions
-
-                return Some(vec);
+
+            let (king_to, rook_to) = Self::castling_columns(rook_col > pos.1);
+
+            // every square the king and rook passes or lands on must be empty (except for the two of them)
+            let low = pos.1.min(rook_col).min(king_to).min(rook_to);
+            let high = pos.1.max(rook_col).max(king_to).max(rook_to);
+            let blocked = (low..=high)
+                .any(|col| col != pos.1 && col != rook_col && self.board[row][col].is_some());
+
+            // and the king may not pass through an attacked square, which is checked without
+            // the king on the board so that it doesn't hide attacks along the row (the rook
+            // still does, it only moves once the king has passed). The square the king ends up on
+            // is checked after the move like for any other move. In Atomic squares next to the
+            // enemy king are always safe, and there is no check at all in Duck chess.
+            let mut without = self.position();
+            without.board[row][pos.1] = None;
+            let passes_check = (pos.1.min(king_to)..=pos.1.max(king_to))
+                .filter(|col| *col != king_to)
+                .any(|col| {
+                    self.variant != Variant::Duck
+                        && without.is_attacked((row, col), enemy)
+                        && !(self.variant == Variant::Atomic
+                            && without.next_to_king((row, col), enemy))
+                });
+
+            if !blocked && !passes_check {
+                moves.push(Move {
+                    from: pos,
+                    to: (row, rook_col),
+                    promotion: None,
+                    drop: None,
+                });
             }
-            //
-            // BISHOP
-            //
-            Some(Piece {
-                piece: PieceType::Bishop,
-                ..
-            }) => {
-                let vec: Vec<String> = Vec::with_capacity(5);
-
-                return Some(vec);
+        }
+    }
+
+    // true if the move can be made by "color" without leaving their own king in check
+    fn is_legal(&self, mv: Move, color: Color) -> bool {
+        let mut game = self.position();
+        game.active_color = color;
+        game.do_move(mv);
+        // giving check isn't allowed in Racing Kings
+        let gives_check =
+            self.variant == Variant::RacingKings && game.king_attacked(color.opposite());
+        !game.king_attacked(color) && !gives_check
+    }
+
+    // returns true if any king of the given color is attacked
+    pub(crate) fn king_attacked(&self, color: Color) -> bool {
+        match self.variant {
+            Variant::Atomic => return self.atomic_king_attacked(color),
+            // the king isn't royal in Antichess, and may be left attacked in Duck chess
+            Variant::Antichess | Variant::Duck => return false,
+            _ => {}
+        }
+        for row in 0..HEIGHT {
+            for col in 0..WIDTH {
+                if let Some(Piece {
+                    piece: PieceType::King,
+                    color: king_color,
+                    ..
+                }) = self.board[row][col]
+                {
+                    if king_color == color && self.is_attacked((row, col), color.opposite()) {
+                        return true;
+                    }
+                }
             }
-            //
-            // KNIGHT
-            //
-            Some(Piece {
-                piece: PieceType::Knight,
-                ..
-            }) => {
-                let mut vec: Vec<String> = Vec::with_capacity(5);
-
-                // make list of positions to check
-                let positions = [
-                    // forward and left/right
-                    ((pos.0 as i32 - 2 * op), (pos.1 as i32 + 1)), // two pieces "forward" and one right
-                    ((pos.0 as i32 - 2 * op), (pos.1 as i32 - 1)), // two pieces "forward" and one left
-                    // backwards and left/right
-                    ((pos.0 as i32 + 2 * op), (pos.1 as i32 + 1)), // two pieces "backward" and one right
-                    ((pos.0 as i32 + 2 * op), (pos.1 as i32 - 1)), // two pieces "backward" and one left
-                    // left and up/down
-                    ((pos.0 as i32 + 1 * op), (pos.1 as i32 - 2)), // two pieces "left" and one "down"
-                    ((pos.0 as i32 - 1 * op), (pos.1 as i32 - 2)), // two pieces "left" and one "up"
-                    // right and up/down
-                    ((pos.0 as i32 + 1 * op), (pos.1 as i32 + 2)), // two pieces "right" and one "down"
-                    ((pos.0 as i32 - 1 * op), (pos.1 as i32 + 2)), // two pieces "right" and one "up"
-                ];
-
-                // check for each position that it is on the board and that it is either empty or occupied by an enemy piece
-                for pos in positions.iter() {
-                    if pos.0 < 8
-                        && pos.0 >= 0
-                        && pos.1 < 8
-                        && pos.1 >= 0
-                        && (self.board[pos.0 as usize][pos.1 as usize].is_none()
-                            || self.board[pos.0 as usize][pos.1 as usize].unwrap().color
-                                != piece.unwrap().color)
-                    {
-                        vec.push(self.index_to_pos((pos.0 as usize, pos.1 as usize)));
+        }
+        false
+    }
+
+    /// Returns true if any piece of the color "by" attacks the given square.
+    pub(crate) fn is_attacked(&self, pos: (usize, usize), by: Color) -> bool {
+        let offset = |pos, step| offset(pos, step, WIDTH, HEIGHT);
+        let is = |target: Option<(usize, usize)>, types: &[PieceType]| match target {
+            Some(target) => match self.board[target.0][target.1] {
+                Some(piece) => piece.color == by && types.contains(&piece.piece),
+                None => false,
+            },
+            None => false,
+        };
+
+        // pawns attack diagonally forward, so look "behind" the square from the attackers view
+        let op: i32 = match by {
+            Color::White => 1,
+            Color::Black => -1,
+        };
+        if is(offset(pos, (op, -1)), &[PieceType::Pawn])
+            || is(offset(pos, (op, 1)), &[PieceType::Pawn])
+        {
+            return true;
+        }
+
+        // kings can't capture in Atomic
+        if KNIGHT_OFFSETS
+            .iter()
+            .any(|step| is(offset(pos, *step), &[PieceType::Knight]))
+            || (self.variant != Variant::Atomic
+                && KING_OFFSETS
+                    .iter()
+                    .any(|step| is(offset(pos, *step), &[PieceType::King])))
+        {
+            return true;
+        }
+
+        // follow each line until the first piece, checking if it can slide back to us
+        let lines = [
+            (&ROOK_DIRECTIONS, [PieceType::Rook, PieceType::Queen]),
+            (&BISHOP_DIRECTIONS, [PieceType::Bishop, PieceType::Queen]),
+        ];
+        for (directions, types) in lines.iter() {
+            for direction in directions.iter() {
+                let mut current = pos;
+                while let Some(target) = offset(current, *direction) {
+                    if self.board[target.0][target.1].is_some() {
+                        if is(Some(target), types) {
+                            return true;
+                        }
+                        break;
                     }
+                    current = target;
                 }
+            }
+        }
 
-                return Some(vec);
+        self.fairy && self.fairy_attacks(pos, by)
+    }
+
+    /// Returns the Zobrist hash of the current position. Two positions with the same pieces,
+    /// player to move, castling rights and en passant square get the same hash.
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::Game;
+    /// let mut game = Game::new();
+    /// let start = game.get_zobrist_hash();
+    /// game.make_move("G1".to_string(), "F3".to_string());
+    /// game.make_move("G8".to_string(), "F6".to_string());
+    /// game.make_move("F3".to_string(), "G1".to_string());
+    /// game.make_move("F6".to_string(), "G8".to_string());
+    /// assert_eq!(game.get_zobrist_hash(), start);
+    /// ```
+    pub fn get_zobrist_hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// Returns true if the current position has already been seen since the last capture or pawn move.
+    pub fn is_repetition(&self) -> bool {
+        self.history
+            .iter()
+            .rev()
+            .take(self.halfmove_clock as usize)
+            .any(|(_, snapshot)| snapshot.hash == self.hash)
+    }
+
+    // true if the move is the king moving onto its own rook
+    pub(crate) fn is_castling(&self, mv: Move) -> bool {
+        match (
+            self.board[mv.from.0][mv.from.1],
+            self.board[mv.to.0][mv.to.1],
+        ) {
+            (Some(king), Some(rook)) => king.piece == PieceType::King && king.color == rook.color,
+            _ => false,
+        }
+    }
+
+    // the square a move is shown as going to, for castling this is where the king ends up
+    fn target_square(&self, mv: Move) -> (usize, usize) {
+        if self.is_castling(mv) {
+            (mv.from.0, Self::castling_columns(mv.to.1 > mv.from.1).0)
+        } else {
+            mv.to
+        }
+    }
+
+    // the row the pawns of a color start on and may move two squares from: the second rank,
+    // or the third on boards of ten or more ranks where the pieces take up the first two (as
+    // in Grand Chess), and none on boards of fewer than eight ranks
+    pub(crate) fn pawn_row(color: Color) -> Option<usize> {
+        let rank = match HEIGHT {
+            0..=7 => return None,
+            8..=9 => 2,
+            _ => 3,
+        };
+        Some(match color {
+            Color::White => HEIGHT - rank,
+            Color::Black => rank - 1,
+        })
+    }
+
+    // the columns the king and the rook end up on when castling to either side
+    fn castling_columns(kingside: bool) -> (usize, usize) {
+        if kingside {
+            (WIDTH - 2, WIDTH - 3)
+        } else {
+            (2, 3)
+        }
+    }
+
+    // make a move without checking if it is legal or updating the game state
+    pub(crate) fn do_move(&mut self, mv: Move) {
+        self.history.push((mv, self.snapshot()));
+        if mv.drop == Some(PieceType::Duck) {
+            self.move_duck(mv);
+            self.hash = zobrist::hash(self);
+            return;
+        }
+        if self.variant == Variant::Crazyhouse {
+            self.update_pockets(mv);
+        }
+
+        let mut piece = match (mv.drop, self.board[mv.from.0][mv.from.1]) {
+            (Some(dropped), _) => self.dropped_piece(mv, dropped),
+            (None, Some(piece)) => piece,
+            (None, None) => return,
+        };
+        let color = piece.color;
+        let was_pawn = piece.piece == PieceType::Pawn && mv.drop.is_none();
+        let mut capture = self.board[mv.to.0][mv.to.1].is_some();
+
+        if mv.drop.is_some() {
+            self.board[mv.to.0][mv.to.1] = Some(piece);
+        } else if self.is_castling(mv) {
+            // pick up both king and rook first, since they may land on each others squares
+            let mut rook = self.board[mv.to.0][mv.to.1].unwrap();
+            let (king_to, rook_to) = Self::castling_columns(mv.to.1 > mv.from.1);
+            let row = mv.from.0;
+
+            piece.untouched = false;
+            rook.untouched = false;
+            self.board[row][mv.from.1] = None;
+            self.board[row][mv.to.1] = None;
+            self.board[row][king_to] = Some(piece);
+            self.board[row][rook_to] = Some(rook);
+            capture = false;
+        } else {
+            // en passant, the captured pawn is beside us and not on the target square
+            if was_pawn && !capture && mv.from.1 != mv.to.1 {
+                self.board[mv.from.0][mv.to.1] = None;
+                capture = true;
             }
-            //
-            // QUEEN
-            //
-            Some(Piece {
-                piece: PieceType::Queen,
-                ..
-            }) => {
-                let vec: Vec<String> = Vec::with_capacity(5);
-
-                return Some(vec);
+
+            // set piece as touched
+            piece.untouched = false;
+            if let Some(promotion) = mv.promotion {
+                piece.piece = promotion;
             }
-            //
-            // KING
-            //
-            Some(Piece {
-                piece: PieceType::King,
-                ..
-            }) => {
-                let mut vec: Vec<String> = Vec::with_capacity(5);
-
-                // make list of positions to check
-                let positions = [
-                    // one step, each direction (including diagonally)
-                    ((pos.0 as i32 - 1 * op), (pos.1 as i32 + 1)), // one square "forward" and one right
-                    ((pos.0 as i32 - 1 * op), (pos.1 as i32 - 1)), // one square "forward" and one left
-                    ((pos.0 as i32 + 1 * op), (pos.1 as i32 + 1)), // one square "backward" and one right
-                    ((pos.0 as i32 + 1 * op), (pos.1 as i32 - 1)), // one square "backward" and one left
-                    ((pos.0 as i32 + 1 * op), (pos.1 as i32)),     // one square "backward"
-                    ((pos.0 as i32 - 1 * op), (pos.1 as i32)),     // one square "forward"
-                    ((pos.0 as i32), (pos.1 as i32 + 1)),          // one square "right"
-                    ((pos.0 as i32), (pos.1 as i32 - 1)),          // one square "left"
-                ];
-
-                // TODO: check that any of the speicifed moves causes an enemy piece to be able to attack the king
-
-                // check for each position that it is on the board and that it is either empty or occupied by an enemy piece
-                for pos in positions.iter() {
-                    if pos.0 < 8
-                        && pos.0 >= 0
-                        && pos.1 < 8
-                        && pos.1 >= 0
-                        && (self.board[pos.0 as usize][pos.1 as usize].is_none()
-                            || self.board[pos.0 as usize][pos.1 as usize].unwrap().color
-                                != piece.unwrap().color)
-                    {
-                        vec.push(self.index_to_pos((pos.0 as usize, pos.1 as usize)));
-                    }
-                }
 
-                return Some(vec);
+            self.board[mv.from.0][mv.from.1] = None;
+            self.board[mv.to.0][mv.to.1] = Some(piece);
+        }
+
+        // remember the skipped square after a double pawn move, but only if an enemy pawn could take it
+        // (and not after a double step from the first rank in Horde)
+        self.en_passant = None;
+        let start_rank = Self::pawn_row(color) == Some(mv.from.0);
+        if was_pawn && start_rank && mv.from.0.abs_diff(mv.to.0) == 2 {
+            let skipped = ((mv.from.0 + mv.to.0) / 2, mv.from.1);
+            let enemy_beside = [-1, 1].iter().any(|side| {
+                match offset(mv.to, (0, *side), WIDTH, HEIGHT) {
+                    Some(beside) => matches!(
+                        self.board[beside.0][beside.1],
+                        Some(Piece { piece: PieceType::Pawn, color: other, .. }) if other != color
+                    ),
+                    None => false,
+                }
+            });
+            if enemy_beside {
+                self.en_passant = Some(skipped);
             }
-            None => return None,
         }
+
+        if was_pawn || capture {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        if color == Color::Black {
+            self.fullmove_number += 1;
+        }
+        self.active_color = color.opposite();
+        self.variant_after_move(mv, capture);
+        self.hash = zobrist::hash(self);
+    }
+
+    // the opposite of do_move, used by the search
+    pub(crate) fn undo(&mut self) {
+        self.undo_move();
+    }
+
+    // pass the turn without moving (a "null move"), used by the search and undone with undo()
+    pub(crate) fn do_null_move(&mut self) {
+        self.history.push((NULL_MOVE, self.snapshot()));
+        self.en_passant = None;
+        self.halfmove_clock += 1;
+        self.active_color = self.active_color.opposite();
+        self.hash = zobrist::hash(self);
+    }
+
+    // true if the last move was a null move
+    pub(crate) fn last_move_was_null(&self) -> bool {
+        matches!(self.history.last(), Some((mv, _)) if *mv == NULL_MOVE)
+    }
+
+    // a copy of the game without the move history, which is cheaper to make than a clone
+    pub(crate) fn position(&self) -> Self {
+        Game {
+            state: self.state,
+            board: self.board,
+            active_color: self.active_color,
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+            hash: self.hash,
+            promotion: self.promotion,
+            history: Vec::new(),
+            clock: None,
+            chess960: self.chess960,
+            variant: self.variant,
+            checks: self.checks,
+            pockets: self.pockets,
+            promoted: self.promoted,
+            duck_to_move: self.duck_to_move,
+            fairy: self.fairy,
+            fairy_promotions: self.fairy_promotions.clone(),
+            custom_pieces: self.custom_pieces.clone(),
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot<WIDTH, HEIGHT> {
+        Snapshot {
+            state: self.state,
+            board: self.board,
+            active_color: self.active_color,
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+            hash: self.hash,
+            checks: self.checks,
+            pockets: self.pockets,
+            promoted: self.promoted,
+            duck_to_move: self.duck_to_move,
+            clock: self.clock.as_ref().map(clock::Clock::times),
+        }
+    }
+
+    // check if the player to move is checkmated, stalemated or in check
+    fn update_state(&mut self) {
+        self.state = if self.get_legal_moves().is_empty() {
+            GameState::GameOver
+        } else if self.is_check() {
+            GameState::Check
+        } else {
+            GameState::InProgress
+        };
     }
 
     /// Converts a string position on the board to a tuple of the row and column (index for 2d array)
@@ -488,31 +1455,74 @@ impl Game {
     /// # Returns
     /// * A tuple of the row and column (index for 2d array)
     fn pos_to_index(&self, pos: String) -> (usize, usize) {
-        // convert pos to lowercase non-borrowed string and then chars
-        let pos = pos.to_lowercase();
-        let mut chars = pos.chars();
+        parse_square(&pos, WIDTH, HEIGHT).expect("the position should be on the board")
+    }
 
-        // when using "as usize", A will be 97, B will be 98 and so on ...
-        // meaning if we subtract 97 we will get the correct index
-        let y = chars.next().unwrap() as usize - 97;
+    // convert index in 2d array to two letter position
+    fn index_to_pos(&self, index: (usize, usize)) -> String {
+        square_name(index, HEIGHT)
+    }
+}
+
+// convert index in 2d array to a position like "E4", on a board with the given number of ranks
+fn square_name(index: (usize, usize), height: usize) -> String {
+    // we convert the column to "ascii value" and then to char,
+    // the rows increase from top to bottom while the ranks increase from bottom to top
+    let file = (index.1 as u8 + b'A') as char;
+    format!("{}{}", file, height - index.0)
+}
 
-        // same here, but instead of subtracting 97 we subtract 49
-        // since 49 is the ascii value of 1
-        // our array increases index from top to bottom, so we need to
-        // include "7 -" since chess uses increasing from bottom to top
-        let x = 7 - (chars.next().unwrap() as usize - 49);
+// like pos_to_index, but returns None instead of panicking on anything that isn't a square
+// on a board of the given size, the rank may have more than one digit (e.g. "a10")
+fn parse_square(pos: &str, width: usize, height: usize) -> Option<(usize, usize)> {
+    let pos = pos.to_lowercase();
+    let file = pos.chars().next()?;
+    let rank = &pos[file.len_utf8()..];
+    if !file.is_ascii_lowercase() || !rank.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    let col = file as usize - 'a' as usize;
+    let rank: usize = rank.parse().ok()?;
+    if col >= width || rank == 0 || rank > height {
+        return None;
+    }
+    Some((height - rank, col))
+}
 
-        (x, y)
+// step from a position, returning None if we end up outside a board of the given size
+fn offset(
+    pos: (usize, usize),
+    step: (i32, i32),
+    width: usize,
+    height: usize,
+) -> Option<(usize, usize)> {
+    let row = pos.0 as i32 + step.0;
+    let col = pos.1 as i32 + step.1;
+    if (0..height as i32).contains(&row) && (0..width as i32).contains(&col) {
+        Some((row as usize, col as usize))
+    } else {
+        None
     }
+}
 
-    // convert index in 2d array to two letter position
-    fn index_to_pos(&self, index: (usize, usize)) -> String {
-        // basically the reverse of pos_to_index
-        // we convert the index to "ascii value" and then to char
-        // we use as u8 since usize cannot be converted to char directly
-        let y = (index.1 + 97) as u8 as char;
-        let x = (7 - index.0 + 49) as u8 as char;
-        format!("{}{}", y.to_uppercase(), x)
+// push a pawn move, turning it into one move per promotion piece if it reaches the last row
+fn push_pawn_move(from: (usize, usize), to: (usize, usize), height: usize, moves: &mut Vec<Move>) {
+    if to.0 == 0 || to.0 == height - 1 {
+        for promotion in PROMOTION_PIECES {
+            moves.push(Move {
+                from,
+                to,
+                promotion: Some(promotion),
+                drop: None,
+            });
+        }
+    } else {
+        moves.push(Move {
+            from,
+            to,
+            promotion: None,
+            drop: None,
+        });
     }
 }
 
@@ -529,13 +1539,14 @@ impl Game {
 /// | P  P  P  P  P  P  P  P |
 /// | R  Kn B  K  Q  B  Kn R |
 /// |:----------------------:|
-impl fmt::Debug for Game {
+impl<const WIDTH: usize, const HEIGHT: usize> fmt::Debug for Game<WIDTH, HEIGHT> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         /* build board representation string */
         let mut board = String::new();
+        let border = format!("\n|:{}:|", "-".repeat(3 * WIDTH - 2));
 
         // add top border
-        board.push_str("\n|:----------------------:|");
+        board.push_str(&border);
 
         // iterate over board and print each piece as letter representation
         for row in self.board.iter() {
@@ -546,16 +1557,35 @@ impl fmt::Debug for Game {
                     None => board.push_str(" * "),
                 }
             }
-            board.push_str("|");
+            board.push('|');
         }
 
         // add bottom border
-        board.push_str("\n|:----------------------:|");
+        board.push_str(&border);
 
         write!(f, "{}", board)
     }
 }
 
+// counts the positions reached after the given number of moves, to check the move generation
+// against known numbers
+#[cfg(test)]
+pub(crate) fn perft<const WIDTH: usize, const HEIGHT: usize>(
+    game: &mut Game<WIDTH, HEIGHT>,
+    depth: u32,
+) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let mut nodes = 0;
+    for mv in game.get_legal_moves() {
+        game.do_move(mv);
+        nodes += perft(game, depth - 1);
+        game.undo();
+    }
+    nodes
+}
+
 // --------------------------
 // ######### TESTS ##########
 // --------------------------
@@ -565,6 +1595,12 @@ mod tests {
     use super::Game;
     use super::GameState;
 
+    // sort a list of positions so that it can be compared regardless of order
+    fn sorted(mut moves: Vec<String>) -> Vec<String> {
+        moves.sort();
+        moves
+    }
+
     // check test framework
     #[test]
     fn it_works() {
@@ -613,19 +1649,19 @@ mod tests {
         // test pawn moves
         // try white pawn
         assert_eq!(
-            game.get_possible_moves("D2".to_string()).unwrap().sort(),
-            vec!["D3".to_string(), "D4".to_string(),].sort()
+            sorted(game.get_possible_moves("D2".to_string()).unwrap()),
+            sorted(vec!["D3".to_string(), "D4".to_string()])
         );
         // try black pawn
         assert_eq!(
-            game.get_possible_moves("D7".to_string()).unwrap().sort(),
-            vec!["D6".to_string(), "D5".to_string(),].sort()
+            sorted(game.get_possible_moves("D7".to_string()).unwrap()),
+            sorted(vec!["D6".to_string(), "D5".to_string()])
         );
 
         // try white pawn at the very left
         assert_eq!(
-            game.get_possible_moves("D2".to_string()).unwrap().sort(),
-            vec!["D3".to_string(), "D4".to_string(),].sort()
+            sorted(game.get_possible_moves("A2".to_string()).unwrap()),
+            sorted(vec!["A3".to_string(), "A4".to_string()])
         );
 
         // try moving D2 pawn to D4
@@ -636,10 +1672,10 @@ mod tests {
 
         println!("{:?}", game);
 
-        // check that we have right moves for this newly moved pawn
+        // check that we have right moves for this newly moved pawn (it can't go back)
         assert_eq!(
-            game.get_possible_moves("D4".to_string()).unwrap().sort(),
-            vec!["D5".to_string(), "D3".to_string(),].sort()
+            game.get_possible_moves("D4".to_string()).unwrap(),
+            vec!["D5".to_string()]
         );
         // then move a black pawn down, C7 to C5
         assert_eq!(
@@ -650,8 +1686,8 @@ mod tests {
 
         // now check that the white pawn has right moves, that it can attack the black pawn
         assert_eq!(
-            game.get_possible_moves("D4".to_string()).unwrap().sort(),
-            vec!["D5".to_string(), "D3".to_string(), "C5".to_string()].sort()
+            sorted(game.get_possible_moves("D4".to_string()).unwrap()),
+            sorted(vec!["D5".to_string(), "C5".to_string()])
         );
         // then attack the black pawn
         assert_eq!(
@@ -668,23 +1704,24 @@ mod tests {
         println!("{:?}", game);
 
         assert_eq!(
-            game.get_possible_moves("B1".to_string()).unwrap().sort(),
-            vec!["A3".to_string(), "C3".to_string(),].sort()
+            sorted(game.get_possible_moves("B1".to_string()).unwrap()),
+            sorted(vec!["A3".to_string(), "C3".to_string()])
         );
         // move B1 to C3
         assert_eq!(
             game.make_move("B1".to_string(), "C3".to_string()),
             Some(GameState::InProgress)
         );
+        // the knight can also go back to B1, which is now empty
         assert_eq!(
-            game.get_possible_moves("C3".to_string()).unwrap().sort(),
-            vec![
+            sorted(game.get_possible_moves("C3".to_string()).unwrap()),
+            sorted(vec![
                 "B5".to_string(),
                 "D5".to_string(),
                 "A4".to_string(),
                 "E4".to_string(),
-            ]
-            .sort()
+                "B1".to_string(),
+            ])
         );
         println!("{:?}", game);
         // move B7 to B5
@@ -720,22 +1757,208 @@ mod tests {
             untouched: true,
         });
 
-        // assert that this newly created (fake) king can move only one square in any direction
+        // assert that this newly created (fake) king can move only one square in any direction,
+        // except forward where the black pawns are attacking
         assert_eq!(
-            game.get_possible_moves("D5".to_string()).unwrap().sort(),
-            vec![
-                "D6".to_string(),
+            sorted(game.get_possible_moves("D5".to_string()).unwrap()),
+            sorted(vec![
                 "D4".to_string(),
-                "C6".to_string(),
                 "C5".to_string(),
                 "C4".to_string(),
-                "E6".to_string(),
                 "E5".to_string(),
                 "E4".to_string(),
-            ]
-            .sort()
+            ])
+        );
+
+        println!("{:?}", game);
+    }
+
+    // test that the sliding pieces get their moves
+    #[test]
+    fn test_sliding_moves() {
+        let mut game = Game::new();
+
+        // blocked in by their own pieces at the start
+        assert_eq!(game.get_possible_moves("A1".to_string()), Some(vec![]));
+        assert_eq!(game.get_possible_moves("D1".to_string()), Some(vec![]));
+
+        game.make_move("E2".to_string(), "E4".to_string());
+        game.make_move("E7".to_string(), "E5".to_string());
+
+        assert_eq!(
+            sorted(game.get_possible_moves("F1".to_string()).unwrap()),
+            sorted(vec![
+                "E2".to_string(),
+                "D3".to_string(),
+                "C4".to_string(),
+                "B5".to_string(),
+                "A6".to_string(),
+            ])
+        );
+        assert_eq!(
+            sorted(game.get_possible_moves("D1".to_string()).unwrap()),
+            sorted(vec![
+                "E2".to_string(),
+                "F3".to_string(),
+                "G4".to_string(),
+                "H5".to_string(),
+            ])
+        );
+    }
+
+    // test castling, en passant and promotion
+    #[test]
+    fn test_special_moves() {
+        let mut game = Game::new();
+
+        for (from, to) in [
+            ("E2", "E4"),
+            ("A7", "A6"),
+            ("E4", "E5"),
+            ("D7", "D5"),
+            ("G1", "F3"),
+            ("A6", "A5"),
+            ("F1", "E2"),
+            ("A5", "A4"),
+        ] {
+            assert!(game.make_move(from.to_string(), to.to_string()).is_some());
+        }
+
+        // the D5 pawn moved two squares last turn, but that was two moves ago so no en passant
+        assert_eq!(
+            game.get_possible_moves("E5".to_string()),
+            Some(vec!["E6".to_string()])
         );
 
+        // castling shows up as the square the king ends up on
+        assert!(game
+            .get_possible_moves("E1".to_string())
+            .unwrap()
+            .contains(&"G1".to_string()));
+        game.make_move("E1".to_string(), "G1".to_string());
         println!("{:?}", game);
+        assert_eq!(game.board[7][6].unwrap().piece, super::PieceType::King);
+        assert_eq!(game.board[7][5].unwrap().piece, super::PieceType::Rook);
+
+        // en passant right after the double move
+        game.make_move("F7".to_string(), "F5".to_string());
+        assert!(game.make_move("E5".to_string(), "F6".to_string()).is_some());
+        assert!(game.board[3][5].is_none());
+
+        // push a pawn down the A file and promote it to a knight
+        game.make_move("A4".to_string(), "A3".to_string());
+        game.make_move("F6".to_string(), "G7".to_string());
+        game.make_move("A3".to_string(), "B2".to_string());
+        game.set_promotion("knight".to_string());
+        assert!(game.make_move("G7".to_string(), "H8".to_string()).is_some());
+        assert_eq!(game.board[0][7].unwrap().piece, super::PieceType::Knight);
+
+        // and undo it again
+        game.undo_move();
+        assert_eq!(game.board[1][6].unwrap().piece, super::PieceType::Pawn);
+    }
+
+    // test that repeated positions are found using the zobrist hash
+    #[test]
+    fn test_repetition() {
+        let mut game = Game::new();
+
+        // shuffle the knights back and forth
+        for (from, to) in [("G1", "F3"), ("G8", "F6"), ("F3", "G1")] {
+            game.make_move(from.to_string(), to.to_string());
+            assert!(!game.is_repetition());
+        }
+        game.make_move("F6".to_string(), "G8".to_string());
+        assert!(game.is_repetition());
+
+        // the castling rights are part of the position, so moving the rook out
+        // and back again does not repeat the starting position
+        game.setup_initial_board();
+        let start = game.get_zobrist_hash();
+        for (from, to) in [
+            ("G1", "F3"),
+            ("B8", "C6"),
+            ("H1", "G1"),
+            ("C6", "B8"),
+            ("G1", "H1"),
+            ("B8", "C6"),
+            ("F3", "G1"),
+            ("C6", "B8"),
+        ] {
+            game.make_move(from.to_string(), to.to_string());
+        }
+        assert_ne!(game.get_zobrist_hash(), start);
+        assert!(!game.is_repetition());
+    }
+
+    // test that a checkmate ends the game
+    #[test]
+    fn test_checkmate() {
+        let mut game = Game::new();
+
+        // fool's mate
+        game.make_move("F2".to_string(), "F3".to_string());
+        game.make_move("E7".to_string(), "E5".to_string());
+        game.make_move("G2".to_string(), "G4".to_string());
+        assert_eq!(
+            game.make_move("D8".to_string(), "H4".to_string()),
+            Some(GameState::GameOver)
+        );
+        assert!(game.is_check());
+
+        // no more moves can be made
+        assert_eq!(game.make_move("A2".to_string(), "A3".to_string()), None);
+    }
+
+    // test boards of other sizes, with two digit ranks
+    #[test]
+    fn test_other_board_sizes() {
+        let fen = "r4k3r/10/10/10/10/10/10/10/P9/R4K3R w KQkq - 0 1";
+        let mut game = Game::<10, 10>::from_fen_sized(fen).unwrap();
+        assert_eq!(game.to_fen(), fen);
+        assert_eq!(game.pos_to_index("J10".to_string()), (0, 9));
+        assert_eq!(game.index_to_pos((9, 0)), "A1");
+
+        // the king castles to the I file, the second from the edge
+        let moves = sorted(game.get_possible_moves("F1".to_string()).unwrap());
+        assert_eq!(moves, vec!["C1", "E1", "E2", "F2", "G1", "G2", "I1"]);
+        let castling = game.parse_uci_move("f1i1").unwrap();
+        assert_eq!(game.move_to_uci(castling), "f1i1");
+        game.apply_move(castling);
+        assert_eq!(
+            game.to_fen(),
+            "r4k3r/10/10/10/10/10/10/10/P9/R6RK1 b kq - 1 1"
+        );
+
+        // and moves to the tenth rank are written with both digits
+        let mut game =
+            Game::<10, 10>::from_fen_sized("5k4/P9/10/10/10/10/10/10/10/5K4 w - - 0 1").unwrap();
+        let promotion = game.parse_uci_move("a9a10q").unwrap();
+        assert_eq!(game.move_to_uci(promotion), "a9a10q");
+        assert!(game
+            .make_move("A9".to_string(), "A10".to_string())
+            .is_some());
+        assert_eq!(game.board[0][0].unwrap().piece, super::PieceType::Queen);
+
+        // in Grand Chess the pawns start on the third rank, and can take en passant there too
+        let mut game = Game::<10, 10>::from_fen_sized(crate::fen::GRAND_FEN).unwrap();
+        assert_eq!(game.to_fen(), crate::fen::GRAND_FEN);
+        let moves = sorted(game.get_possible_moves("E3".to_string()).unwrap());
+        assert_eq!(moves, vec!["E4", "E5"]);
+        for (from, to) in [("E3", "E5"), ("A8", "A7"), ("E5", "E6"), ("D8", "D6")] {
+            assert!(game.make_move(from.to_string(), to.to_string()).is_some());
+        }
+        assert_eq!(game.get_possible_moves("E6".to_string()).unwrap().len(), 2);
+        assert!(game.make_move("E6".to_string(), "D7".to_string()).is_some());
+        assert!(game.board[4][3].is_none());
+
+        // pawns only move one square at a time on small boards
+        let game = Game::<6, 6>::from_fen_sized(crate::fen::LOS_ALAMOS_FEN).unwrap();
+        assert_eq!(game.get_legal_moves().len(), 10);
+        assert_eq!(
+            game.get_possible_moves("A2".to_string()),
+            Some(vec!["A3".to_string()])
+        );
+        assert!(Game::<6, 6>::from_fen_sized(crate::fen::STARTING_FEN).is_none());
     }
 }
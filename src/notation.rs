@@ -0,0 +1,124 @@
+//! Reading and writing moves in coordinate notation, as used by UCI and XBoard engines.
+//!
+//! A move is written as the square it comes from and the square it goes to, followed by the
+//! promotion piece if any, e.g. "e2e4" or "e7e8q". Castling is written as the king moving two
+//! squares ("e1g1"), but the king taking its own rook ("e1h1") is understood as well.
+//! In Chess960 castling is always written as the king taking its own rook, since the king
+//! may only have to move one square or not at all. Drops in Crazyhouse are written as the
+//! piece letter, an `@` and the square, e.g. "N@f3". On boards with more than nine ranks the
+//! rank may have two digits, e.g. "a10a9".
+
+use crate::{parse_square, Game, Move, PieceType};
+
+impl<const WIDTH: usize, const HEIGHT: usize> Game<WIDTH, HEIGHT> {
+    /// Finds the legal move written in coordinate notation, returning None if there is no such move.
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::Game;
+    /// let mut game = Game::new();
+    /// let mv = game.parse_uci_move("g1f3").unwrap();
+    /// game.apply_move(mv);
+    /// assert!(game.parse_uci_move("g1f3").is_none()); // not white's turn anymore
+    /// ```
+    pub fn parse_uci_move(&self, text: &str) -> Option<Move> {
+        let text = text.trim();
+        if !text.is_ascii() || text.len() < 4 {
+            return None;
+        }
+
+        if let Some((piece, square)) = text.split_once('@') {
+            let mut letters = piece.chars();
+            let piece = self.piece_from_char(letters.next()?)?;
+            let to = parse_square(square, WIDTH, HEIGHT)?;
+            if letters.next().is_some() {
+                return None;
+            }
+            return self
+                .get_legal_moves()
+                .into_iter()
+                .find(|mv| mv.drop == Some(piece) && mv.to == to);
+        }
+
+        let (from, rest) = split_square(text);
+        let (to, promotion) = split_square(rest);
+        let from = parse_square(from, WIDTH, HEIGHT)?;
+        let to = parse_square(to, WIDTH, HEIGHT)?;
+        let mut letters = promotion.chars();
+        let promotion = match (letters.next(), letters.next()) {
+            (None, _) => None,
+            (Some(letter), None) => match self.piece_from_char(letter)? {
+                PieceType::Pawn => return None,
+                piece => Some(piece),
+            },
+            _ => return None,
+        };
+
+        self.get_legal_moves().into_iter().find(|mv| {
+            mv.drop.is_none()
+                && mv.from == from
+                && (mv.to == to || (!self.chess960 && self.target_square(*mv) == to))
+                && mv.promotion == promotion
+        })
+    }
+
+    /// Writes a move in coordinate notation, with castling written as the king moving two squares
+    /// (or as the king taking its own rook in Chess960).
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::Game;
+    /// let game = Game::new();
+    /// let mv = game.parse_uci_move("b1c3").unwrap();
+    /// assert_eq!(game.move_to_uci(mv), "b1c3");
+    /// ```
+    pub fn move_to_uci(&self, mv: Move) -> String {
+        let mv = if self.is_castling(mv) && !self.chess960 {
+            Move {
+                to: self.target_square(mv),
+                ..mv
+            }
+        } else {
+            mv
+        };
+        mv.coordinates(HEIGHT)
+    }
+}
+
+// splits off the square at the start of the text, a file letter followed by the rank digits
+fn split_square(text: &str) -> (&str, &str) {
+    let end = text
+        .char_indices()
+        .skip(1)
+        .find(|(_, c)| !c.is_ascii_digit())
+        .map_or(text.len(), |(index, _)| index);
+    text.split_at(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Game;
+
+    #[test]
+    fn castling_both_ways() {
+        let game = Game::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+
+        let short = game.parse_uci_move("e1g1").unwrap();
+        assert_eq!(game.parse_uci_move("e1h1"), Some(short));
+        assert_eq!(game.move_to_uci(short), "e1g1");
+        assert_eq!(
+            game.move_to_uci(game.parse_uci_move("e1a1").unwrap()),
+            "e1c1"
+        );
+    }
+
+    #[test]
+    fn promotions_and_bad_input() {
+        let game = Game::from_fen("8/P7/8/8/8/8/8/k6K w - - 0 1").unwrap();
+
+        assert!(game.parse_uci_move("a7a8n").is_some());
+        assert!(game.parse_uci_move("a7a8").is_none());
+        assert!(game.parse_uci_move("a7a8k").is_none());
+        assert!(game.parse_uci_move("a7").is_none());
+        assert!(game.parse_uci_move("z9a8q").is_none());
+        assert!(game.parse_uci_move("ä7a8q").is_none());
+    }
+}
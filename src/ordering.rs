@@ -0,0 +1,396 @@
+//! Move ordering for the search, and static exchange evaluation.
+//!
+//! Alpha-beta prunes the most when the best move is searched first, so the moves are sorted:
+//! first the move from the transposition table, then captures that don't lose material
+//! (sorted by most valuable victim and least valuable attacker, MVV-LVA), then quiet moves that
+//! caused cutoffs at the same ply (killer moves) and finally the other quiet moves sorted by how
+//! often they caused cutoffs before (history heuristic). Captures that lose material go last.
+//!
+//! Static exchange evaluation (SEE) works out what a capture wins or loses if both players keep
+//! recapturing on the same square with their least valuable piece. It can be used on its own,
+//! for example to mark losing captures when analysing a game.
+
+use crate::eval::piece_value;
+use crate::search::MAX_PLY;
+use crate::{
+    offset, Board, Color, Game, Move, Piece, PieceType, BISHOP_DIRECTIONS, BOARD_SIZE,
+    KING_OFFSETS, KNIGHT_OFFSETS, ROOK_DIRECTIONS,
+};
+
+// value of the king in exchanges, more than all other pieces together
+const KING_VALUE: i32 = 20_000;
+
+// scores used to sort the different kinds of moves, from searched first to searched last
+const HASH_MOVE_SCORE: i32 = 1_000_000;
+const GOOD_CAPTURE_SCORE: i32 = 500_000;
+const FIRST_KILLER_SCORE: i32 = 400_000;
+const SECOND_KILLER_SCORE: i32 = 390_000;
+const BAD_CAPTURE_SCORE: i32 = -500_000;
+
+// history scores are kept below this, so that they never reach the killers
+const HISTORY_MAX: i32 = 300_000;
+
+const SQUARES: usize = BOARD_SIZE * BOARD_SIZE;
+
+/// Returns the type of the piece captured by the move, if it is a capture (including en passant).
+/// # Examples
+/// ```
+/// use vprytz_chess::ordering::captured_piece;
+/// use vprytz_chess::Game;
+/// let game = Game::new();
+/// assert_eq!(captured_piece(&game, game.get_legal_moves()[0]), None);
+/// ```
+pub fn captured_piece(game: &Game, mv: Move) -> Option<PieceType> {
+    if game.is_castling(mv) {
+        return None;
+    }
+    match game.board[mv.to.0][mv.to.1] {
+        Some(piece) => Some(piece.piece),
+        None => match game.board[mv.from.0][mv.from.1] {
+            // a pawn moving diagonally onto an empty square takes en passant
+            Some(piece) if piece.piece == PieceType::Pawn && mv.from.1 != mv.to.1 => {
+                Some(PieceType::Pawn)
+            }
+            _ => None,
+        },
+    }
+}
+
+/// Most valuable victim, least valuable attacker: higher for captures that should be tried first.
+/// Quiet moves get 0, promotions count the value gained by promoting.
+pub fn mvv_lva(game: &Game, mv: Move) -> i32 {
+    let victim = captured_piece(game, mv).map_or(0, piece_value);
+    let promotion = mv
+        .promotion
+        .map_or(0, |piece| piece_value(piece) - piece_value(PieceType::Pawn));
+    let attacker = match game.board[mv.from.0][mv.from.1] {
+        Some(piece) => exchange_value(piece.piece),
+        None => 0,
+    };
+    if victim == 0 && promotion == 0 {
+        return 0;
+    }
+    (victim + promotion) * 10 - attacker / 10
+}
+
+/// Static exchange evaluation: the material (in centipawns) won by the moving side if the move
+/// is answered by the best sequence of recaptures on the target square. Negative values mean
+/// that the capture loses material. Pins are not taken into account.
+/// # Examples
+/// ```
+/// use vprytz_chess::ordering::see;
+/// use vprytz_chess::Game;
+/// let mut game = Game::new();
+/// game.make_move("E2".to_string(), "E4".to_string());
+/// game.make_move("D7".to_string(), "D5".to_string());
+/// // exd5 Qxd5, a pawn for a pawn
+/// let capture = game.get_legal_moves().into_iter().find(|mv| mv.to_string() == "e4d5").unwrap();
+/// assert_eq!(see(&game, capture), 0);
+/// ```
+pub fn see(game: &Game, mv: Move) -> i32 {
+    let mut piece = match game.board[mv.from.0][mv.from.1] {
+        Some(piece) => piece,
+        None => return 0,
+    };
+    if game.is_castling(mv) {
+        return 0;
+    }
+
+    let mut board = game.board;
+    let target = mv.to;
+
+    // what the first capture wins, including what the pawn becomes when promoting
+    let mut gains = [0; 32];
+    gains[0] = captured_piece(game, mv).map_or(0, piece_value);
+    if let Some(promotion) = mv.promotion {
+        gains[0] += piece_value(promotion) - piece_value(PieceType::Pawn);
+        piece.piece = promotion;
+    }
+
+    // make the first capture, removing the pawn taken en passant
+    if piece.piece == PieceType::Pawn
+        && mv.from.1 != target.1
+        && board[target.0][target.1].is_none()
+    {
+        board[mv.from.0][target.1] = None;
+    }
+    board[mv.from.0][mv.from.1] = None;
+    board[target.0][target.1] = Some(piece);
+
+    // let the players take turns recapturing with their least valuable piece
+    let mut side = piece.color.opposite();
+    let mut depth = 0;
+    while depth + 1 < gains.len() {
+        let from = match least_valuable_attacker(&board, target, side) {
+            Some(from) => from,
+            None => break,
+        };
+        let attacker = board[from.0][from.1].unwrap();
+
+        // the king may only recapture if the square isn't defended anymore
+        if attacker.piece == PieceType::King {
+            let mut after = board;
+            after[from.0][from.1] = None;
+            after[target.0][target.1] = Some(attacker);
+            if least_valuable_attacker(&after, target, side.opposite()).is_some() {
+                break;
+            }
+        }
+
+        depth += 1;
+        let victim = board[target.0][target.1].unwrap();
+        gains[depth] = exchange_value(victim.piece) - gains[depth - 1];
+
+        board[from.0][from.1] = None;
+        board[target.0][target.1] = Some(attacker);
+        side = side.opposite();
+    }
+
+    // each player may also choose to stop recapturing, so work backwards picking the best option
+    while depth > 0 {
+        gains[depth - 1] = -(-gains[depth - 1]).max(gains[depth]);
+        depth -= 1;
+    }
+
+    gains[0]
+}
+
+fn exchange_value(piece: PieceType) -> i32 {
+    match piece {
+        PieceType::King => KING_VALUE,
+        _ => piece_value(piece),
+    }
+}
+
+// the square of the cheapest piece of the given color attacking the target on the board
+fn least_valuable_attacker(
+    board: &Board,
+    target: (usize, usize),
+    color: Color,
+) -> Option<(usize, usize)> {
+    let offset = |pos, step| offset(pos, step, BOARD_SIZE, BOARD_SIZE);
+    let mut best: Option<((usize, usize), i32)> = None;
+    let mut consider = |square: (usize, usize), types: &[PieceType]| {
+        if let Some(Piece {
+            piece,
+            color: piece_color,
+            ..
+        }) = board[square.0][square.1]
+        {
+            if piece_color == color && types.contains(&piece) {
+                let value = exchange_value(piece);
+                if best.is_none_or(|(_, best_value)| value < best_value) {
+                    best = Some((square, value));
+                }
+            }
+        }
+    };
+
+    // pawns attack diagonally forward, so look "behind" the target from the attackers view
+    let op: i32 = match color {
+        Color::White => 1,
+        Color::Black => -1,
+    };
+    for side in [-1, 1] {
+        if let Some(square) = offset(target, (op, side)) {
+            consider(square, &[PieceType::Pawn]);
+        }
+    }
+    for step in KNIGHT_OFFSETS {
+        if let Some(square) = offset(target, step) {
+            consider(square, &[PieceType::Knight]);
+        }
+    }
+    for step in KING_OFFSETS {
+        if let Some(square) = offset(target, step) {
+            consider(square, &[PieceType::King]);
+        }
+    }
+
+    // the first piece on each line, pieces that already captured are gone so x-rays are found too
+    let lines = [
+        (ROOK_DIRECTIONS, [PieceType::Rook, PieceType::Queen]),
+        (BISHOP_DIRECTIONS, [PieceType::Bishop, PieceType::Queen]),
+    ];
+    for (directions, types) in lines {
+        for direction in directions {
+            let mut current = target;
+            while let Some(square) = offset(current, direction) {
+                if board[square.0][square.1].is_some() {
+                    consider(square, &types);
+                    break;
+                }
+                current = square;
+            }
+        }
+    }
+
+    best.map(|(square, _)| square)
+}
+
+/// Killer moves and history scores, learned during a search and used to sort quiet moves.
+pub(crate) struct MoveOrderer {
+    // two quiet moves per ply that recently caused a beta cutoff
+    killers: Vec<[Option<Move>; 2]>,
+    // how much each quiet move (by color, from square and to square) has caused cutoffs
+    history: Vec<i32>,
+}
+
+impl MoveOrderer {
+    pub(crate) fn new() -> MoveOrderer {
+        MoveOrderer {
+            killers: vec![[None; 2]; MAX_PLY + 1],
+            history: vec![0; 2 * SQUARES * SQUARES],
+        }
+    }
+
+    // called at the start of each search, killers are forgotten and history fades out
+    pub(crate) fn new_search(&mut self) {
+        for killers in self.killers.iter_mut() {
+            *killers = [None; 2];
+        }
+        for score in self.history.iter_mut() {
+            *score /= 8;
+        }
+    }
+
+    // sort the moves, best first
+    pub(crate) fn order(
+        &self,
+        game: &Game,
+        moves: &mut [Move],
+        hash_move: Option<Move>,
+        ply: usize,
+    ) {
+        let killers = self.killers[ply.min(MAX_PLY)];
+        moves.sort_by_cached_key(|mv| {
+            let score = if Some(*mv) == hash_move {
+                HASH_MOVE_SCORE
+            } else if captured_piece(game, *mv).is_some() || mv.promotion.is_some() {
+                if see(game, *mv) >= 0 {
+                    GOOD_CAPTURE_SCORE + mvv_lva(game, *mv)
+                } else {
+                    BAD_CAPTURE_SCORE + mvv_lva(game, *mv)
+                }
+            } else if Some(*mv) == killers[0] {
+                FIRST_KILLER_SCORE
+            } else if Some(*mv) == killers[1] {
+                SECOND_KILLER_SCORE
+            } else {
+                self.history[history_index(game.active_color, *mv)]
+            };
+            -score
+        });
+    }
+
+    // remember a quiet move that caused a beta cutoff
+    pub(crate) fn record_cutoff(&mut self, color: Color, mv: Move, depth: u32, ply: usize) {
+        let killers = &mut self.killers[ply.min(MAX_PLY)];
+        if killers[0] != Some(mv) {
+            killers[1] = killers[0];
+            killers[0] = Some(mv);
+        }
+
+        let index = history_index(color, mv);
+        self.history[index] += (depth * depth) as i32;
+
+        // halve everything when a score gets too large, keeping the relative order
+        if self.history[index] > HISTORY_MAX {
+            for score in self.history.iter_mut() {
+                *score /= 2;
+            }
+        }
+    }
+}
+
+fn history_index(color: Color, mv: Move) -> usize {
+    let color = match color {
+        Color::White => 0,
+        Color::Black => 1,
+    };
+    let from = mv.from.0 * BOARD_SIZE + mv.from.1;
+    let to = mv.to.0 * BOARD_SIZE + mv.to.1;
+    (color * SQUARES + from) * SQUARES + to
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // play a list of moves given as pairs of squares, returning the game and its legal moves
+    fn play(moves: &[(&str, &str)]) -> Game {
+        let mut game = Game::new();
+        for (from, to) in moves {
+            assert!(game.make_move(from.to_string(), to.to_string()).is_some());
+        }
+        game
+    }
+
+    fn find(game: &Game, mv: &str) -> Move {
+        game.get_legal_moves()
+            .into_iter()
+            .find(|legal| legal.to_string() == mv)
+            .unwrap()
+    }
+
+    #[test]
+    fn see_of_defended_and_undefended_pieces() {
+        let game = play(&[("E2", "E4"), ("E7", "E5"), ("D1", "H5"), ("B8", "C6")]);
+
+        // the queen takes a pawn defended by the knight
+        assert_eq!(see(&game, find(&game, "h5e5")), 100 - 900);
+        // the queen takes the undefended pawn on f7, the king can take back
+        assert_eq!(see(&game, find(&game, "h5f7")), 100 - 900);
+        // quiet moves win nothing
+        assert_eq!(see(&game, find(&game, "g1f3")), 0);
+    }
+
+    #[test]
+    fn see_counts_pieces_behind_each_other() {
+        let mut game = Game::new();
+        game.board = [[None; BOARD_SIZE]; BOARD_SIZE];
+        let piece = |color, piece| {
+            Some(Piece {
+                color,
+                piece,
+                untouched: false,
+            })
+        };
+        game.board[7][4] = piece(Color::White, PieceType::King);
+        game.board[0][4] = piece(Color::Black, PieceType::King);
+        // white rooks on d1 and d2 against a black knight on d6 defended by a rook on d8
+        game.board[7][3] = piece(Color::White, PieceType::Rook);
+        game.board[6][3] = piece(Color::White, PieceType::Rook);
+        game.board[2][3] = piece(Color::Black, PieceType::Knight);
+        game.board[0][3] = piece(Color::Black, PieceType::Rook);
+
+        // Rxd6 Rxd6 Rxd6 wins a knight and a rook for a rook
+        assert_eq!(see(&game, find(&game, "d2d6")), 320);
+    }
+
+    #[test]
+    fn mvv_lva_prefers_cheap_attackers_and_valuable_victims() {
+        let game = play(&[("E2", "E4"), ("D7", "D5"), ("D1", "G4"), ("C8", "D7")]);
+
+        // exd5 takes a pawn with a pawn, Qxd7+ takes a bishop with the queen
+        let pawn_takes_pawn = mvv_lva(&game, find(&game, "e4d5"));
+        let queen_takes_bishop = mvv_lva(&game, find(&game, "g4d7"));
+        assert!(queen_takes_bishop > pawn_takes_pawn);
+        assert_eq!(mvv_lva(&game, find(&game, "g1f3")), 0);
+    }
+
+    #[test]
+    fn hash_move_and_killers_go_first() {
+        let game = Game::new();
+        let mut orderer = MoveOrderer::new();
+        let mut moves = game.get_legal_moves();
+        let hash_move = find(&game, "a2a3");
+        let killer = find(&game, "h2h4");
+
+        orderer.record_cutoff(Color::White, killer, 3, 0);
+        orderer.order(&game, &mut moves, Some(hash_move), 0);
+
+        assert_eq!(moves[0], hash_move);
+        assert_eq!(moves[1], killer);
+    }
+}
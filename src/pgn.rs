@@ -0,0 +1,356 @@
+//! Reading and writing games in Portable Game Notation (PGN).
+//!
+//! A PGN file starts with tags like `[White "Magnus"]`, followed by the moves in Standard
+//! Algebraic Notation (see [`Game::move_to_san`]) and the result. Games that don't start from
+//! the usual starting position get a `FEN` tag, and games of other variants a `Variant` tag.
+//!
+//! When reading, comments (`{...}` and `;` to the end of the line), variations in parentheses
+//! and annotation glyphs like `$1` are skipped.
+//!
+//! # Examples
+//! ```
+//! use vprytz_chess::Game;
+//! let mut game = Game::new();
+//! game.make_move("E2".to_string(), "E4".to_string());
+//! game.make_move("E7".to_string(), "E5".to_string());
+//! let pgn = game.to_pgn(&[("White", "Alice"), ("Black", "Bob")]);
+//! assert!(pgn.contains("[White \"Alice\"]"));
+//! assert!(pgn.ends_with("1. e4 e5 *\n"));
+//!
+//! let read = Game::from_pgn(&pgn).unwrap();
+//! assert_eq!(read.to_fen(), game.to_fen());
+//! ```
+
+use crate::fen::STARTING_FEN;
+use crate::variant::Variant;
+use crate::{Color, Game, Outcome};
+
+// the tags every PGN game has, in the order they are written
+const SEVEN_TAG_ROSTER: [(&str, &str); 7] = [
+    ("Event", "?"),
+    ("Site", "?"),
+    ("Date", "????.??.??"),
+    ("Round", "?"),
+    ("White", "?"),
+    ("Black", "?"),
+    ("Result", "*"),
+];
+
+/// Returns the PGN result token for an outcome, "*" if the game is still going on.
+/// # Examples
+/// ```
+/// use vprytz_chess::pgn::result_token;
+/// use vprytz_chess::{Color, Outcome};
+/// assert_eq!(result_token(Some(Outcome::Checkmate { winner: Color::Black })), "0-1");
+/// assert_eq!(result_token(Some(Outcome::Stalemate)), "1/2-1/2");
+/// assert_eq!(result_token(None), "*");
+/// ```
+pub fn result_token(outcome: Option<Outcome>) -> &'static str {
+    match outcome {
+        None => "*",
+        Some(outcome) => match outcome.winner() {
+            Some(Color::White) => "1-0",
+            Some(Color::Black) => "0-1",
+            None => "1/2-1/2",
+        },
+    }
+}
+
+impl<const WIDTH: usize, const HEIGHT: usize> Game<WIDTH, HEIGHT> {
+    /// Writes the game as PGN. The given tags are added to (or replace the values of) the
+    /// seven tags every game has. The `Result` tag is taken from [`Game::get_outcome`] unless
+    /// it is given, e.g. for a resigned game.
+    pub fn to_pgn(&self, tags: &[(&str, &str)]) -> String {
+        let mut game = self.get_starting_position();
+        let start = game.to_fen();
+
+        let mut all: Vec<(String, String)> = SEVEN_TAG_ROSTER
+            .iter()
+            .map(|(tag, value)| (tag.to_string(), value.to_string()))
+            .collect();
+        all[6].1 = result_token(self.get_outcome()).to_string();
+        if self.variant != Variant::Standard {
+            all.push(("Variant".to_string(), self.variant.name().to_string()));
+        } else if self.chess960 {
+            all.push(("Variant".to_string(), "chess960".to_string()));
+        }
+        if start != STARTING_FEN || WIDTH != 8 || HEIGHT != 8 {
+            all.push(("SetUp".to_string(), "1".to_string()));
+            all.push(("FEN".to_string(), start));
+        }
+        for (tag, value) in tags {
+            match all.iter_mut().find(|(name, _)| name == tag) {
+                Some(existing) => existing.1 = value.to_string(),
+                None => all.push((tag.to_string(), value.to_string())),
+            }
+        }
+
+        let mut pgn = String::new();
+        for (tag, value) in &all {
+            pgn.push_str(&format!(
+                "[{} \"{}\"]\n",
+                tag,
+                value.replace('\\', "\\\\").replace('"', "\\\"")
+            ));
+        }
+        pgn.push('\n');
+
+        // the moves, numbered at the start of every white turn (a Duck chess turn is two moves)
+        let mut tokens = Vec::new();
+        let mut previous = None;
+        for mv in self.get_move_history() {
+            let color = game.active_color;
+            if previous.is_none() && color == Color::Black {
+                tokens.push(format!("{}...", game.fullmove_number));
+            } else if color == Color::White && previous != Some(Color::White) {
+                tokens.push(format!("{}.", game.fullmove_number));
+            }
+            tokens.push(game.move_to_san(mv));
+            game.do_move(mv);
+            previous = Some(color);
+        }
+        tokens.push(all[6].1.clone());
+
+        // with lines kept under 80 characters
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() >= 80 {
+                pgn.push_str(&line);
+                pgn.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        pgn.push_str(&line);
+        pgn.push('\n');
+        pgn
+    }
+}
+
+impl Game {
+    /// Reads the first game of a PGN file, returning None if a move or tag in it isn't
+    /// understood or a move is illegal.
+    pub fn from_pgn(pgn: &str) -> Option<Game> {
+        let tags = pgn_tags(pgn);
+        let tag = |name: &str| {
+            tags.iter()
+                .find(|(tag, _)| tag == name)
+                .map(|(_, value)| value.as_str())
+        };
+
+        let variant = match tag("Variant") {
+            None => Variant::Standard,
+            Some(name) => {
+                let lowercase = name.to_lowercase();
+                if lowercase.contains("960") || lowercase.starts_with("fischer") {
+                    Variant::Standard
+                } else {
+                    Variant::from_name(name)?
+                }
+            }
+        };
+        let mut game = match tag("FEN") {
+            Some(fen) => Game::from_fen_variant(fen, variant)?,
+            None => Game::new_variant(variant),
+        };
+
+        for token in movetext(pgn) {
+            let mv = game.parse_san(&token)?;
+            game.apply_move(mv);
+        }
+        Some(game)
+    }
+}
+
+// the tags of the first game, with their values unescaped
+fn pgn_tags(pgn: &str) -> Vec<(String, String)> {
+    let mut tags = Vec::new();
+    for line in pgn.lines().map(str::trim) {
+        if line.is_empty() && !tags.is_empty() {
+            break;
+        }
+        let Some(line) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        else {
+            continue;
+        };
+        let Some((name, value)) = line.split_once(' ') else {
+            continue;
+        };
+        let value = value.trim();
+        let value = value.strip_prefix('"').unwrap_or(value);
+        let value = value.strip_suffix('"').unwrap_or(value);
+        let mut unescaped = String::new();
+        let mut chars = value.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => unescaped.extend(chars.next()),
+                c => unescaped.push(c),
+            }
+        }
+        tags.push((name.to_string(), unescaped));
+    }
+    tags
+}
+
+// the moves of the first game, without move numbers, comments, variations and annotations
+fn movetext(pgn: &str) -> Vec<String> {
+    let mut text = String::new();
+    let mut started = false;
+    for line in pgn.lines() {
+        if line.trim_start().starts_with('[') {
+            if started {
+                break;
+            }
+            continue;
+        }
+        if !line.trim().is_empty() {
+            started = true;
+        }
+        text.push_str(line);
+        text.push('\n');
+    }
+
+    let mut moves = Vec::new();
+    let mut token = String::new();
+    let mut depth = 0;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        break;
+                    }
+                }
+            }
+            ';' => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ if depth > 0 => continue,
+            c if c.is_whitespace() => {}
+            c => {
+                token.push(c);
+                continue;
+            }
+        }
+        if !token.is_empty() {
+            moves.push(std::mem::take(&mut token));
+        }
+    }
+    if !token.is_empty() {
+        moves.push(token);
+    }
+
+    let mut result = Vec::new();
+    for token in moves {
+        if matches!(token.as_str(), "1-0" | "0-1" | "1/2-1/2" | "*") {
+            break;
+        }
+        // a move number, possibly written together with the move ("1.e4"), but not the zeros
+        // of castling ("0-0")
+        let digits = token.len() - token.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let token = match token[digits..].trim_start_matches('.') {
+            rest if digits > 0 && rest.len() < token.len() - digits => rest,
+            _ => token.as_str(),
+        };
+        if token.is_empty() || token.starts_with('$') {
+            continue;
+        }
+        result.push(token.to_string());
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::variant::Variant;
+    use crate::Game;
+
+    #[test]
+    fn reading() {
+        let pgn = r#"[Event "Casual game"]
+[White "Anderssen, Adolf"]
+[Black "Kieseritzky, Lionel"]
+[Result "1-0"]
+
+1. e4 e5 2. f4 exf4 3. Bc4 Qh4+ {the immortal game} 4. Kf1 b5 5. Bxb5 Nf6 6. Nf3
+Qh6 7. d3 Nh5 8. Nh4 Qg5 9. Nf5 c6 10. g4 Nf6 11. Rg1 cxb5 12. h4 Qg6 13. h5 Qg5
+14. Qf3 Ng8 15. Bxf4 Qf6 16. Nc3 Bc5 17. Nd5 Qxb2 18. Bd6 Bxg1 (18... Qxa1+ 19.
+Ke2 Qb2) 19. e5 $1 Qxa1+ 20. Ke2 Na6 21. Nxg7+ Kd8 22. Qf6+! Nxf6 23.Be7# 1-0
+
+[Event "Another game"]
+
+1. d4 *
+"#;
+        let game = Game::from_pgn(pgn).unwrap();
+        assert_eq!(game.get_move_history().len(), 45);
+        assert_eq!(
+            game.to_fen(),
+            "r1bk3r/p2pBpNp/n4n2/1p1NP2P/6P1/3P4/P1P1K3/q5b1 b - - 1 23"
+        );
+
+        // castling written with zeros, also right after the move number
+        for (zeros, letters) in [
+            (
+                "1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4. 0-0 Nf6 *",
+                "1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4. O-O Nf6 *",
+            ),
+            (
+                "1. d4 d5 2. Nc3 Nc6 3. Bf4 Bf5 4. Qd2 Qd7 5.0-0-0 0-0-0 *",
+                "1. d4 d5 2. Nc3 Nc6 3. Bf4 Bf5 4. Qd2 Qd7 5. O-O-O O-O-O *",
+            ),
+        ] {
+            let game = Game::from_pgn(zeros).unwrap();
+            assert_eq!(game.to_fen(), Game::from_pgn(letters).unwrap().to_fen());
+        }
+
+        // illegal moves and unknown variants
+        assert!(Game::from_pgn("1. e4 e5 2. Ke3").is_none());
+        assert!(Game::from_pgn("[Variant \"Shogi\"]\n\n1. e4").is_none());
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut game = Game::from_fen_variant(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[] b KQkq - 0 1",
+            Variant::Crazyhouse,
+        )
+        .unwrap();
+        for text in ["e7e5", "d2d4", "e5d4", "d1d4", "P@e5"] {
+            game.apply_move(game.parse_uci_move(text).unwrap());
+        }
+        let pgn = game.to_pgn(&[("Event", "Test \"game\""), ("Annotator", "me")]);
+        assert_eq!(
+            pgn,
+            r#"[Event "Test \"game\""]
+[Site "?"]
+[Date "????.??.??"]
+[Round "?"]
+[White "?"]
+[Black "?"]
+[Result "*"]
+[Variant "crazyhouse"]
+[SetUp "1"]
+[FEN "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[] b KQkq - 0 1"]
+[Annotator "me"]
+
+1... e5 2. d4 exd4 3. Qxd4 P@e5 *
+"#
+        );
+        let read = Game::from_pgn(&pgn).unwrap();
+        assert_eq!(read.get_variant(), Variant::Crazyhouse);
+        assert_eq!(super::pgn_tags(&pgn)[0].1, "Test \"game\"");
+        assert_eq!(read.to_fen(), game.to_fen());
+    }
+}
@@ -0,0 +1,645 @@
+//! Drawing positions as PNG images, and whole games as animated GIFs, e.g. for chat messages
+//! where SVG images aren't shown.
+//!
+//! The images look like the SVG diagrams of the [`svg`](crate::svg) module and take the same
+//! [`Diagram`] options. Everything is drawn by the library itself, without fonts or system
+//! graphics libraries, so images can be made on a server without a display. Colors of highlights
+//! and arrows are written as "#rgb", "#rrggbb" or one of the basic color names like "red";
+//! highlights and arrows in other colors are left out.
+//!
+//! These need the `images` feature, which is on by default.
+//!
+//! # Examples
+//! ```
+//! use std::time::Duration;
+//! use vprytz_chess::svg::Diagram;
+//! use vprytz_chess::Game;
+//! let mut game = Game::new();
+//! game.make_move("E2".to_string(), "E4".to_string());
+//! let diagram = Diagram {
+//!     last_move: true,
+//!     ..Diagram::default()
+//! };
+//! let png = game.to_png(&diagram).unwrap();
+//! assert!(png.starts_with(b"\x89PNG"));
+//!
+//! // one frame for the starting position and one after each move, half a second apart
+//! let gif = game.to_gif(&diagram, Duration::from_millis(500)).unwrap();
+//! assert!(gif.starts_with(b"GIF89a"));
+//! ```
+
+use std::time::Duration;
+
+use crate::svg::{arrow_polygon, piece_shapes, Diagram, Shape, DARK, DUCK, LAST_MOVE, LIGHT};
+use crate::{parse_square, Color, Game, Piece, PieceType};
+
+type Rgb = [u8; 3];
+type Contour = Vec<(f32, f32)>;
+
+const WHITE: Rgb = [255, 255, 255];
+const BLACK: Rgb = [0, 0, 0];
+const COORDINATES: Rgb = [85, 85, 85];
+
+// the number of lines each row of pixels is split into when working out how much of a pixel
+// a shape covers, for smooth edges
+const SUBSAMPLES: usize = 4;
+
+// the most pixels an image may have, 8192 by 8192, so a large square size fails instead of
+// running out of memory
+const MAX_PIXELS: usize = 1 << 26;
+
+impl<const WIDTH: usize, const HEIGHT: usize> Game<WIDTH, HEIGHT> {
+    /// Draws the position as a PNG image. Returns None if the image can't be made, e.g. for
+    /// squares of size 0 or images of more than 8192 × 8192 pixels.
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::svg::Diagram;
+    /// use vprytz_chess::Game;
+    /// let diagram = Diagram {
+    ///     coordinates: false,
+    ///     ..Diagram::default()
+    /// };
+    /// let png = Game::new().to_png(&diagram).unwrap();
+    /// // the width and height, 8 squares of 45 pixels
+    /// assert_eq!(png[16..24], [0, 0, 1, 104, 0, 0, 1, 104]);
+    /// ```
+    pub fn to_png(&self, diagram: &Diagram) -> Option<Vec<u8>> {
+        let (width, height) = Self::image_size(diagram);
+        if width == 0 || height == 0 || width.saturating_mul(height) > MAX_PIXELS {
+            return None;
+        }
+        let canvas = self.canvas(diagram);
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, canvas.width as u32, canvas.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().ok()?;
+        writer.write_image_data(&canvas.rgb()).ok()?;
+        writer.finish().ok()?;
+        Some(png)
+    }
+
+    /// Draws the game as an animated GIF, with a frame for the starting position and one after
+    /// each move, shown for `delay` each (rounded down to hundredths of a second). The
+    /// highlights and arrows of the diagram are only drawn on the last frame, the last move is
+    /// marked on all of them if asked for. Returns None if the image can't be made, e.g. when
+    /// it's wider or higher than 65535 pixels or has more than 8192 × 8192 pixels.
+    pub fn to_gif(&self, diagram: &Diagram, delay: Duration) -> Option<Vec<u8>> {
+        let (width, height) = Self::image_size(diagram);
+        let width = u16::try_from(width).ok()?;
+        let height = u16::try_from(height).ok()?;
+        if width == 0 || height == 0 || width as usize * height as usize > MAX_PIXELS {
+            return None;
+        }
+        let plain = Diagram {
+            highlights: Vec::new(),
+            arrows: Vec::new(),
+            ..diagram.clone()
+        };
+        let delay = (delay.as_millis() / 10).min(u16::MAX as u128) as u16;
+        let mut encoder = gif::Encoder::new(Vec::new(), width, height, &[]).ok()?;
+        encoder.set_repeat(gif::Repeat::Infinite).ok()?;
+
+        // each frame is written as soon as it is drawn, so only one is kept at a time
+        let mut game = self.get_starting_position();
+        let moves = self.get_move_history();
+        for i in 0..=moves.len() {
+            if i > 0 {
+                game.do_move(moves[i - 1]);
+            }
+            let canvas = game.canvas(if i == moves.len() { diagram } else { &plain });
+            // picking the colors of a frame more carefully takes much longer for long games
+            let mut frame = gif::Frame::from_rgb_speed(width, height, &canvas.rgb(), 10);
+            frame.delay = delay;
+            encoder.write_frame(&frame).ok()?;
+        }
+        encoder.into_inner().ok()
+    }
+
+    // the width and height of an image, with the margin kept to whole pixels so the squares
+    // stay sharp
+    fn image_size(diagram: &Diagram) -> (usize, usize) {
+        let size = diagram.square_size as usize;
+        let margin = if diagram.coordinates { size / 2 } else { 0 };
+        (
+            margin.saturating_add(size.saturating_mul(WIDTH)),
+            margin.saturating_add(size.saturating_mul(HEIGHT)),
+        )
+    }
+
+    // the position drawn the same way as by to_svg()
+    fn canvas(&self, diagram: &Diagram) -> Canvas {
+        let size = diagram.square_size as f32;
+        let (width, height) = Self::image_size(diagram);
+        let margin = (width - diagram.square_size as usize * WIDTH) as f32;
+        let corner = |(row, col): (usize, usize)| {
+            let (x, y) = match diagram.flipped {
+                false => (col, row),
+                true => (WIDTH - 1 - col, HEIGHT - 1 - row),
+            };
+            (margin + x as f32 * size, y as f32 * size)
+        };
+        let center = |square: (usize, usize)| {
+            let (x, y) = corner(square);
+            (x + size / 2.0, y + size / 2.0)
+        };
+        let square = |name: &str| parse_square(name, WIDTH, HEIGHT);
+
+        let mut canvas = Canvas::new(width, height);
+        for row in 0..HEIGHT {
+            for col in 0..WIDTH {
+                let (x, y) = corner((row, col));
+                let color = if (row + col) % 2 == 0 { LIGHT } else { DARK };
+                canvas.fill(&[rect(x, y, size, size)], color_rgb(color).unwrap(), 1.0);
+            }
+        }
+
+        if diagram.last_move {
+            if let Some((mv, _)) = self.history.last() {
+                let squares = match mv.drop {
+                    Some(_) => vec![mv.to],
+                    None => vec![mv.from, mv.to],
+                };
+                for square in squares {
+                    let (x, y) = corner(square);
+                    let color = color_rgb(LAST_MOVE).unwrap();
+                    canvas.fill(&[rect(x, y, size, size)], color, 0.41);
+                }
+            }
+        }
+        for highlight in &diagram.highlights {
+            if let (Some(square), Some(color)) =
+                (square(&highlight.square), color_rgb(&highlight.color))
+            {
+                let (x, y) = corner(square);
+                canvas.fill(&[rect(x, y, size, size)], color, 0.5);
+            }
+        }
+
+        if diagram.coordinates {
+            // whole pixels for the letters, or they get blurry at these sizes
+            let pixel = (size * 0.03).round().max(1.0);
+            for row in 0..HEIGHT {
+                let (_, y) = center((row, 0));
+                let rank = (HEIGHT - row).to_string();
+                canvas.text((margin / 2.0, y), pixel, &rank, COORDINATES, true);
+            }
+            for col in 0..WIDTH {
+                let (x, _) = center((0, col));
+                let file = ((b'a' + col as u8) as char).to_string();
+                canvas.text(
+                    (x, height as f32 - margin / 2.0),
+                    pixel,
+                    &file,
+                    COORDINATES,
+                    true,
+                );
+            }
+        }
+
+        for row in 0..HEIGHT {
+            for col in 0..WIDTH {
+                if let Some(piece) = self.board[row][col] {
+                    canvas.piece(piece, corner((row, col)), size);
+                }
+            }
+        }
+
+        for arrow in &diagram.arrows {
+            let (Some(from), Some(to), Some(color)) = (
+                square(&arrow.from),
+                square(&arrow.to),
+                color_rgb(&arrow.color),
+            ) else {
+                continue;
+            };
+            if from == to {
+                let ring = ring(center(from), size * 0.45, size * 0.08);
+                canvas.fill(&ring, color, 0.8);
+                continue;
+            }
+            let arrow = arrow_polygon(center(from), center(to), size);
+            canvas.fill(&[arrow], color, 0.8);
+        }
+        canvas
+    }
+}
+
+// the pixels of an image, in rows from the top
+struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<Rgb>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Canvas {
+        Canvas {
+            width,
+            height,
+            pixels: vec![WHITE; width * height],
+        }
+    }
+
+    fn rgb(&self) -> Vec<u8> {
+        self.pixels.concat()
+    }
+
+    fn blend(&mut self, x: usize, y: usize, color: Rgb, opacity: f32) {
+        let pixel = &mut self.pixels[y * self.width + x];
+        for (old, new) in pixel.iter_mut().zip(color) {
+            *old = (*old as f32 * (1.0 - opacity) + new as f32 * opacity).round() as u8;
+        }
+    }
+
+    // fills the inside of the outlines, where they go around a point a different number of
+    // times clockwise than counterclockwise, so outlines turned the other way make holes
+    fn fill(&mut self, contours: &[Contour], color: Rgb, opacity: f32) {
+        let points = || contours.iter().flatten();
+        let left = points().map(|p| p.0).fold(f32::INFINITY, f32::min).max(0.0);
+        let right = points().map(|p| p.0).fold(f32::NEG_INFINITY, f32::max);
+        let top = points().map(|p| p.1).fold(f32::INFINITY, f32::min).max(0.0);
+        let bottom = points().map(|p| p.1).fold(f32::NEG_INFINITY, f32::max);
+        let (x0, y0) = (left.floor() as usize, top.floor() as usize);
+        let x1 = (right.ceil().max(0.0) as usize).min(self.width);
+        let y1 = (bottom.ceil().max(0.0) as usize).min(self.height);
+        if x0 >= x1 || y0 >= y1 {
+            return;
+        }
+
+        let mut coverage = vec![0.0; x1 - x0];
+        for y in y0..y1 {
+            coverage.fill(0.0);
+            for sub in 0..SUBSAMPLES {
+                let line = y as f32 + (sub as f32 + 0.5) / SUBSAMPLES as f32;
+                // where the outlines cross the line, and which way
+                let mut crossings = Vec::new();
+                for contour in contours {
+                    for (i, &(ax, ay)) in contour.iter().enumerate() {
+                        let (bx, by) = contour[(i + 1) % contour.len()];
+                        if (ay <= line) != (by <= line) {
+                            let x = ax + (line - ay) / (by - ay) * (bx - ax);
+                            crossings.push((x, if ay < by { 1 } else { -1 }));
+                        }
+                    }
+                }
+                crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+                let mut winding = 0;
+                for (i, &(x, direction)) in crossings.iter().enumerate() {
+                    winding += direction;
+                    if winding == 0 || i + 1 == crossings.len() {
+                        continue;
+                    }
+                    let start = x.max(x0 as f32);
+                    let end = crossings[i + 1].0.min(x1 as f32);
+                    let mut pixel = start.floor();
+                    while pixel < end {
+                        let covered = end.min(pixel + 1.0) - start.max(pixel);
+                        coverage[pixel as usize - x0] += covered / SUBSAMPLES as f32;
+                        pixel += 1.0;
+                    }
+                }
+            }
+            for (i, &covered) in coverage.iter().enumerate() {
+                if covered > 0.0 {
+                    self.blend(x0 + i, y, color, opacity * covered.min(1.0));
+                }
+            }
+        }
+    }
+
+    // the outline of a shape drawn with round corners, like SVG strokes
+    fn stroke(&mut self, contour: &[(f32, f32)], width: f32, color: Rgb) {
+        let half = width / 2.0;
+        let mut parts = Vec::new();
+        for (i, &(ax, ay)) in contour.iter().enumerate() {
+            let (bx, by) = contour[(i + 1) % contour.len()];
+            let length = ((bx - ax).powi(2) + (by - ay).powi(2)).sqrt();
+            if length > 0.0 {
+                let (nx, ny) = (-(by - ay) / length * half, (bx - ax) / length * half);
+                parts.push(clockwise(vec![
+                    (ax + nx, ay + ny),
+                    (bx + nx, by + ny),
+                    (bx - nx, by - ny),
+                    (ax - nx, ay - ny),
+                ]));
+            }
+            parts.push(circle((ax, ay), half));
+        }
+        self.fill(&parts, color, 1.0);
+    }
+
+    // a piece drawn in the square with its top left corner at the given point
+    fn piece(&mut self, piece: Piece, (x, y): (f32, f32), size: f32) {
+        let (fill, stroke, letter) = match (piece.piece, piece.color) {
+            (PieceType::Duck, _) => (color_rgb(DUCK).unwrap(), BLACK, BLACK),
+            (_, Color::White) => (WHITE, BLACK, BLACK),
+            (_, Color::Black) => (BLACK, BLACK, WHITE),
+        };
+        let scale = size / 45.0;
+        let place = |(px, py): (f32, f32)| (x + px * scale, y + py * scale);
+        let (shapes, text) = piece_shapes(piece.piece);
+        for shape in shapes {
+            let contour: Contour = match shape {
+                Shape::Polygon(corners) => corners.iter().map(|&p| place(p)).collect(),
+                Shape::Circle(cx, cy, r) => circle(place((*cx, *cy)), r * scale),
+            };
+            self.fill(std::slice::from_ref(&contour), fill, 1.0);
+            self.stroke(&contour, 1.5 * scale, stroke);
+        }
+        if let Some(text) = text {
+            self.text(
+                place((22.5, 22.5)),
+                2.0 * scale,
+                &text.to_string(),
+                letter,
+                false,
+            );
+        }
+    }
+
+    // writes text centered on a point, with each pixel of the font the given size
+    fn text(&mut self, (x, y): (f32, f32), pixel: f32, text: &str, color: Rgb, sharp: bool) {
+        let glyphs: Vec<&str> = text.chars().filter_map(glyph).collect();
+        let count = glyphs.len() as f32;
+        let mut left = x - (count * 6.0 - 1.0) * pixel / 2.0;
+        let mut top = y - 7.0 * pixel / 2.0;
+        if sharp {
+            (left, top) = (left.round(), top.round());
+        }
+        let mut parts = Vec::new();
+        for (i, glyph) in glyphs.iter().enumerate() {
+            for (row, line) in glyph.split('/').enumerate() {
+                for (col, dot) in line.chars().enumerate() {
+                    if dot == '#' {
+                        let px = left + (i * 6 + col) as f32 * pixel;
+                        parts.push(rect(px, top + row as f32 * pixel, pixel, pixel));
+                    }
+                }
+            }
+        }
+        self.fill(&parts, color, 1.0);
+    }
+}
+
+fn rect(x: f32, y: f32, width: f32, height: f32) -> Contour {
+    vec![
+        (x, y),
+        (x + width, y),
+        (x + width, y + height),
+        (x, y + height),
+    ]
+}
+
+// a circle as a polygon, going clockwise (with y going down) like the rectangles
+fn circle((cx, cy): (f32, f32), r: f32) -> Contour {
+    let sides = 32;
+    (0..sides)
+        .map(|i| {
+            let angle = i as f32 / sides as f32 * std::f32::consts::TAU;
+            (cx + r * angle.cos(), cy + r * angle.sin())
+        })
+        .collect()
+}
+
+// a circle drawn with a line of the given width
+fn ring(center: (f32, f32), r: f32, width: f32) -> Vec<Contour> {
+    let mut inner = circle(center, r - width / 2.0);
+    inner.reverse();
+    vec![circle(center, r + width / 2.0), inner]
+}
+
+// turns an outline clockwise, so it adds to the others instead of cutting a hole in them
+fn clockwise(mut contour: Contour) -> Contour {
+    let area: f32 = (0..contour.len())
+        .map(|i| {
+            let (ax, ay) = contour[i];
+            let (bx, by) = contour[(i + 1) % contour.len()];
+            ax * by - bx * ay
+        })
+        .sum();
+    if area < 0.0 {
+        contour.reverse();
+    }
+    contour
+}
+
+// the colors understood in highlights and arrows
+fn color_rgb(color: &str) -> Option<Rgb> {
+    let color = color.trim().to_lowercase();
+    let named = match color.as_str() {
+        "black" => Some("#000000"),
+        "white" => Some("#ffffff"),
+        "red" => Some("#ff0000"),
+        "green" => Some("#008000"),
+        "blue" => Some("#0000ff"),
+        "yellow" => Some("#ffff00"),
+        "orange" => Some("#ffa500"),
+        "purple" => Some("#800080"),
+        "gray" | "grey" => Some("#808080"),
+        _ => None,
+    };
+    let hex = named.unwrap_or(&color).strip_prefix('#')?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let digit = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).unwrap();
+    match hex.len() {
+        3 => Some([digit(0) * 17, digit(1) * 17, digit(2) * 17]),
+        6 => Some([
+            digit(0) * 16 + digit(1),
+            digit(2) * 16 + digit(3),
+            digit(4) * 16 + digit(5),
+        ]),
+        _ => None,
+    }
+}
+
+// a small font for the coordinates and the letters of fairy pieces, 5 pixels wide and 7 high
+// with one more row for the tails of letters like "g"
+fn glyph(c: char) -> Option<&'static str> {
+    let glyph = match c {
+        '0' => ".###./#...#/#..##/#.#.#/##..#/#...#/.###./.....",
+        '1' => "..#../.##../..#../..#../..#../..#../.###./.....",
+        '2' => ".###./#...#/....#/...#./..#../.#.../#####/.....",
+        '3' => "#####/...#./..#../...#./....#/#...#/.###./.....",
+        '4' => "...#./..##./.#.#./#..#./#####/...#./...#./.....",
+        '5' => "#####/#..../####./....#/....#/#...#/.###./.....",
+        '6' => "..##./.#.../#..../####./#...#/#...#/.###./.....",
+        '7' => "#####/....#/...#./..#../.#.../.#.../.#.../.....",
+        '8' => ".###./#...#/#...#/.###./#...#/#...#/.###./.....",
+        '9' => ".###./#...#/#...#/.####/....#/...#./.##../.....",
+        'a' => "...../...../.###./....#/.####/#...#/.####/.....",
+        'b' => "#..../#..../#.##./##..#/#...#/#...#/####./.....",
+        'c' => "...../...../.###./#..../#..../#...#/.###./.....",
+        'd' => "....#/....#/.##.#/#..##/#...#/#...#/.####/.....",
+        'e' => "...../...../.###./#...#/#####/#..../.###./.....",
+        'f' => "..##./.#..#/.#.../###../.#.../.#.../.#.../.....",
+        'g' => "...../...../.####/#...#/#...#/.####/....#/.###.",
+        'h' => "#..../#..../#.##./##..#/#...#/#...#/#...#/.....",
+        'i' => "..#../...../.##../..#../..#../..#../.###./.....",
+        'j' => "...#./...../..##./...#./...#./...#./#..#./.##..",
+        'k' => "#..../#..../#..#./#.#../##.../#.#../#..#./.....",
+        'l' => ".##../..#../..#../..#../..#../..#../.###./.....",
+        'm' => "...../...../##.#./#.#.#/#.#.#/#...#/#...#/.....",
+        'n' => "...../...../#.##./##..#/#...#/#...#/#...#/.....",
+        'o' => "...../...../.###./#...#/#...#/#...#/.###./.....",
+        'p' => "...../...../####./#...#/#...#/####./#..../#....",
+        'q' => "...../...../.####/#...#/#...#/.####/....#/....#",
+        'r' => "...../...../#.##./##..#/#..../#..../#..../.....",
+        's' => "...../...../.####/#..../.###./....#/####./.....",
+        't' => ".#.../.#.../###../.#.../.#.../.#..#/..##./.....",
+        'u' => "...../...../#...#/#...#/#...#/#..##/.##.#/.....",
+        'v' => "...../...../#...#/#...#/#...#/.#.#./..#../.....",
+        'w' => "...../...../#...#/#...#/#.#.#/#.#.#/.#.#./.....",
+        'x' => "...../...../#...#/.#.#./..#../.#.#./#...#/.....",
+        'y' => "...../...../#...#/#...#/#...#/.####/....#/.###.",
+        'z' => "...../...../#####/...#./..#../.#.../#####/.....",
+        'A' => ".###./#...#/#...#/#####/#...#/#...#/#...#/.....",
+        'B' => "####./#...#/#...#/####./#...#/#...#/####./.....",
+        'C' => ".###./#...#/#..../#..../#..../#...#/.###./.....",
+        'D' => "####./#...#/#...#/#...#/#...#/#...#/####./.....",
+        'E' => "#####/#..../#..../####./#..../#..../#####/.....",
+        'F' => "#####/#..../#..../####./#..../#..../#..../.....",
+        'G' => ".###./#...#/#..../#.###/#...#/#...#/.####/.....",
+        'H' => "#...#/#...#/#...#/#####/#...#/#...#/#...#/.....",
+        'I' => ".###./..#../..#../..#../..#../..#../.###./.....",
+        'J' => "..###/...#./...#./...#./...#./#..#./.##../.....",
+        'K' => "#...#/#..#./#.#../##.../#.#../#..#./#...#/.....",
+        'L' => "#..../#..../#..../#..../#..../#..../#####/.....",
+        'M' => "#...#/##.##/#.#.#/#.#.#/#...#/#...#/#...#/.....",
+        'N' => "#...#/#...#/##..#/#.#.#/#..##/#...#/#...#/.....",
+        'O' => ".###./#...#/#...#/#...#/#...#/#...#/.###./.....",
+        'P' => "####./#...#/#...#/####./#..../#..../#..../.....",
+        'Q' => ".###./#...#/#...#/#...#/#.#.#/#..#./.##.#/.....",
+        'R' => "####./#...#/#...#/####./#.#../#..#./#...#/.....",
+        'S' => ".####/#..../#..../.###./....#/....#/####./.....",
+        'T' => "#####/..#../..#../..#../..#../..#../..#../.....",
+        'U' => "#...#/#...#/#...#/#...#/#...#/#...#/.###./.....",
+        'V' => "#...#/#...#/#...#/#...#/#...#/.#.#./..#../.....",
+        'W' => "#...#/#...#/#...#/#.#.#/#.#.#/#.#.#/.#.#./.....",
+        'X' => "#...#/#...#/.#.#./..#../.#.#./#...#/#...#/.....",
+        'Y' => "#...#/#...#/.#.#./..#../..#../..#../..#../.....",
+        'Z' => "#####/....#/...#./..#../.#.../#..../#####/.....",
+        _ => return None,
+    };
+    Some(glyph)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::glyph;
+    use crate::svg::{Arrow, Diagram, Highlight};
+    use crate::Game;
+
+    // the width, height and pixels of a PNG image
+    fn decode(png: &[u8]) -> (usize, usize, Vec<u8>) {
+        let mut reader = png::Decoder::new(png).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        (info.width as usize, info.height as usize, pixels)
+    }
+
+    fn pixel(image: &(usize, usize, Vec<u8>), x: usize, y: usize) -> [u8; 3] {
+        let i = (y * image.0 + x) * 3;
+        [image.2[i], image.2[i + 1], image.2[i + 2]]
+    }
+
+    #[test]
+    fn png_image() {
+        let image = decode(&Game::new().to_png(&Diagram::default()).unwrap());
+        // 8 squares of 45 pixels and a margin of 22 for the coordinates
+        assert_eq!((image.0, image.1), (382, 382));
+        // the corners of a8 and b8, and the middles of the a1 rook and the e8 king
+        assert_eq!(pixel(&image, 23, 1), [240, 217, 181]);
+        assert_eq!(pixel(&image, 68, 1), [181, 136, 99]);
+        assert_eq!(pixel(&image, 22 + 22, 315 + 25), [255, 255, 255]);
+        assert_eq!(pixel(&image, 22 + 4 * 45 + 22, 28), [0, 0, 0]);
+        // the coordinates are gray on white
+        assert!((0..22).any(|x| (0..360).any(|y| pixel(&image, x, y) == [85, 85, 85])));
+
+        let diagram = Diagram {
+            square_size: 0,
+            ..Diagram::default()
+        };
+        assert!(Game::new().to_png(&diagram).is_none());
+        // too large to draw, failing before the pixels are allocated
+        for square_size in [1200, 10_000, u32::MAX] {
+            let diagram = Diagram {
+                square_size,
+                ..Diagram::default()
+            };
+            assert!(Game::new().to_png(&diagram).is_none());
+            assert!(Game::new().to_gif(&diagram, Duration::ZERO).is_none());
+        }
+        for c in "0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ".chars() {
+            let rows: Vec<&str> = glyph(c).unwrap().split('/').collect();
+            assert_eq!(rows.len(), 8);
+            assert!(rows.iter().all(|row| row.len() == 5));
+        }
+    }
+
+    #[test]
+    fn highlights_and_arrows() {
+        let mut game = Game::new();
+        game.make_move("E2".to_string(), "E4".to_string());
+        let diagram = Diagram {
+            coordinates: false,
+            flipped: true,
+            highlights: vec![
+                Highlight {
+                    square: "h1".to_string(),
+                    color: "red".to_string(),
+                },
+                Highlight {
+                    square: "h2".to_string(),
+                    color: "url(#pattern)".to_string(),
+                },
+            ],
+            arrows: vec![Arrow::new("a4a4", "#00f").unwrap()],
+            last_move: true,
+            ..Diagram::default()
+        };
+        let image = decode(&game.to_png(&diagram).unwrap());
+        // h1 is a light square in the top left corner, half red
+        assert_eq!(pixel(&image, 1, 1), [248, 109, 91]);
+        // the color of h2 isn't understood
+        assert_eq!(pixel(&image, 1, 46), [181, 136, 99]);
+        // e2 and e4 are marked
+        assert_eq!(pixel(&image, 3 * 45 + 1, 46), [205, 210, 107]);
+        assert_eq!(pixel(&image, 3 * 45 + 1, 3 * 45 + 1), [205, 210, 107]);
+        // the circle on a4 goes through the middle of its right side, but not its middle
+        assert_eq!(pixel(&image, 7 * 45 + 22 + 20, 3 * 45 + 22), [48, 43, 240]);
+        assert_eq!(pixel(&image, 7 * 45 + 22, 3 * 45 + 22), [240, 217, 181]);
+    }
+
+    #[test]
+    fn gif_animation() {
+        let mut game = Game::new();
+        for (from, to) in [("E2", "E4"), ("E7", "E5"), ("G1", "F3")] {
+            game.make_move(from.to_string(), to.to_string());
+        }
+        let diagram = Diagram {
+            square_size: 20,
+            ..Diagram::default()
+        };
+        let gif = game.to_gif(&diagram, Duration::from_millis(250)).unwrap();
+
+        let mut decoder = gif::DecodeOptions::new().read_info(&gif[..]).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (170, 170));
+        let mut delays = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            delays.push(frame.delay);
+        }
+        assert_eq!(delays, vec![25; 4]);
+
+        let diagram = Diagram {
+            square_size: 10_000,
+            ..Diagram::default()
+        };
+        assert!(Game::new().to_gif(&diagram, Duration::ZERO).is_none());
+    }
+}
@@ -0,0 +1,242 @@
+//! Drawing the board as text.
+//!
+//! Pieces are written as FEN letters, uppercase for White and lowercase for Black, or as
+//! Unicode figurines. The board can have its ranks and files written next to it, be turned
+//! around to show it the way Black sees it, and be colored with ANSI escape codes for terminals
+//! (the squares get their colors, and the pieces are drawn in white or black).
+//!
+//! Printing a game with `{}` draws it with the default [`Style`].
+//!
+//! # Examples
+//! ```
+//! use vprytz_chess::render::{Pieces, Style};
+//! use vprytz_chess::Game;
+//! let game = Game::new();
+//! assert!(game.to_string().starts_with("8 r n b q k b n r\n"));
+//!
+//! let style = Style {
+//!     pieces: Pieces::Unicode,
+//!     flipped: true,
+//!     ..Style::default()
+//! };
+//! assert!(game.render(&style).starts_with("1 ♖ ♘ ♗ ♔ ♕ ♗ ♘ ♖\n"));
+//! ```
+
+use std::fmt;
+
+use crate::fen::piece_to_char;
+use crate::{Color, Game, Piece, PieceType};
+
+/// How the pieces are drawn.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Pieces {
+    /// FEN letters, e.g. "N" for a white knight and "n" for a black one.
+    Ascii,
+    /// Figurines, e.g. "♘" for a white knight and "♞" for a black one.
+    Unicode,
+}
+
+/// How the board is drawn, see `Game::render()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Style {
+    pub pieces: Pieces,
+    /// Colors the squares and pieces with ANSI escape codes.
+    pub colors: bool,
+    /// Writes the ranks to the left of the board and the files below it.
+    pub coordinates: bool,
+    /// Shows the board the way Black sees it, with the first rank at the top.
+    pub flipped: bool,
+}
+
+impl Default for Style {
+    /// Letters, no colors, with coordinates and White at the bottom.
+    fn default() -> Style {
+        Style {
+            pieces: Pieces::Ascii,
+            colors: false,
+            coordinates: true,
+            flipped: false,
+        }
+    }
+}
+
+const LIGHT: (u8, u8, u8) = (240, 217, 181);
+const DARK: (u8, u8, u8) = (181, 136, 99);
+const WHITE_PIECE: (u8, u8, u8) = (255, 255, 255);
+const BLACK_PIECE: (u8, u8, u8) = (0, 0, 0);
+const RESET: &str = "\x1b[0m";
+
+impl<const WIDTH: usize, const HEIGHT: usize> Game<WIDTH, HEIGHT> {
+    /// Draws the board in the given style, one line per rank.
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::render::Style;
+    /// use vprytz_chess::Game;
+    /// let game = Game::from_fen("4k3/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+    /// let style = Style {
+    ///     coordinates: false,
+    ///     ..Style::default()
+    /// };
+    /// assert_eq!(game.render(&style).lines().last(), Some(". . . . K . . R"));
+    /// ```
+    pub fn render(&self, style: &Style) -> String {
+        let label = HEIGHT.to_string().len();
+        let mut rows: Vec<usize> = (0..HEIGHT).collect();
+        let mut cols: Vec<usize> = (0..WIDTH).collect();
+        if style.flipped {
+            rows.reverse();
+            cols.reverse();
+        }
+
+        let mut lines = Vec::new();
+        for &row in &rows {
+            let mut line = String::new();
+            if style.coordinates {
+                line.push_str(&format!("{:>width$} ", HEIGHT - row, width = label));
+            }
+            let squares: Vec<String> = cols
+                .iter()
+                .map(|&col| {
+                    let piece = self.board[row][col];
+                    if style.colors {
+                        let (r, g, b) = if (row + col) % 2 == 0 { LIGHT } else { DARK };
+                        let (fr, fg, fb) = match piece {
+                            Some(piece) if piece.color == Color::Black => BLACK_PIECE,
+                            _ => WHITE_PIECE,
+                        };
+                        let symbol = piece.map_or(' ', |piece| symbol(piece, style.pieces, true));
+                        format!(
+                            "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m {} ",
+                            fr, fg, fb, r, g, b, symbol
+                        )
+                    } else {
+                        let empty = match style.pieces {
+                            Pieces::Ascii => '.',
+                            Pieces::Unicode => '·',
+                        };
+                        piece
+                            .map_or(empty, |piece| symbol(piece, style.pieces, false))
+                            .to_string()
+                    }
+                })
+                .collect();
+            if style.colors {
+                line.push_str(&squares.concat());
+                line.push_str(RESET);
+            } else {
+                line.push_str(&squares.join(" "));
+            }
+            lines.push(line);
+        }
+
+        if style.coordinates {
+            // the files line up with the middle of the squares
+            let files: Vec<String> = cols
+                .iter()
+                .map(|&col| ((b'a' + col as u8) as char).to_string())
+                .collect();
+            let files = match style.colors {
+                true => format!(" {} ", files.join("  ")),
+                false => files.join(" "),
+            };
+            lines.push(format!("{} {}", " ".repeat(label), files));
+        }
+        lines.join("\n")
+    }
+}
+
+/// Draws the board with the default style: letters, coordinates and White at the bottom.
+impl<const WIDTH: usize, const HEIGHT: usize> fmt::Display for Game<WIDTH, HEIGHT> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.render(&Style::default()))
+    }
+}
+
+// the letter or figurine of a piece, solid figurines are used for both colors when the pieces
+// are colored anyway
+pub(crate) fn symbol(piece: Piece, pieces: Pieces, solid: bool) -> char {
+    let white = piece.color == Color::White && !solid;
+    let figurine = match (pieces, piece.piece) {
+        (Pieces::Ascii, _) => None,
+        (_, PieceType::King) => Some(if white { '♔' } else { '♚' }),
+        (_, PieceType::Queen) => Some(if white { '♕' } else { '♛' }),
+        (_, PieceType::Rook) => Some(if white { '♖' } else { '♜' }),
+        (_, PieceType::Bishop) => Some(if white { '♗' } else { '♝' }),
+        (_, PieceType::Knight) => Some(if white { '♘' } else { '♞' }),
+        (_, PieceType::Pawn) => Some(if white { '♙' } else { '♟' }),
+        _ => None,
+    };
+    figurine.unwrap_or_else(|| piece_to_char(piece))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pieces, Style};
+    use crate::Game;
+
+    #[test]
+    fn letters_and_figurines() {
+        let mut game = Game::new();
+        game.make_move("E2".to_string(), "E4".to_string());
+        assert_eq!(
+            game.to_string(),
+            "8 r n b q k b n r
+7 p p p p p p p p
+6 . . . . . . . .
+5 . . . . . . . .
+4 . . . . P . . .
+3 . . . . . . . .
+2 P P P P . P P P
+1 R N B Q K B N R
+  a b c d e f g h"
+        );
+
+        let style = Style {
+            pieces: Pieces::Unicode,
+            flipped: true,
+            ..Style::default()
+        };
+        let text = game.render(&style);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "1 ♖ ♘ ♗ ♔ ♕ ♗ ♘ ♖");
+        assert_eq!(lines[3], "4 · · · ♙ · · · ·");
+        assert_eq!(lines[7], "8 ♜ ♞ ♝ ♚ ♛ ♝ ♞ ♜");
+        assert_eq!(lines[8], "  h g f e d c b a");
+    }
+
+    #[test]
+    fn colors() {
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let style = Style {
+            colors: true,
+            ..Style::default()
+        };
+        let text = game.render(&style);
+        let lines: Vec<&str> = text.lines().collect();
+        // e8 is a light square with a black king
+        assert!(lines[0].starts_with("8 \x1b[38;2;255;255;255m\x1b[48;2;240;217;181m   "));
+        assert!(lines[0].contains("\x1b[38;2;0;0;0m\x1b[48;2;240;217;181m k "));
+        assert!(lines.iter().take(8).all(|line| line.ends_with("\x1b[0m")));
+        assert_eq!(lines[8], "   a  b  c  d  e  f  g  h ");
+
+        // the figurines are solid, the colors tell them apart
+        let style = Style {
+            pieces: Pieces::Unicode,
+            ..style
+        };
+        assert!(game.render(&style).contains("\x1b[48;2;181;136;99m ♚ "));
+    }
+
+    #[test]
+    fn other_board_sizes() {
+        let game = Game::<10, 10>::from_fen_sized(
+            "rnbqkbnrrr/pppppppppp/10/10/10/10/10/10/PPPPPPPPPP/RNBQKBNRRR w - - 0 1",
+        )
+        .unwrap();
+        let text = game.to_string();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "10 r n b q k b n r r r");
+        assert_eq!(lines[9], " 1 R N B Q K B N R R R");
+        assert_eq!(lines[10], "   a b c d e f g h i j");
+    }
+}
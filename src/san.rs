@@ -0,0 +1,270 @@
+//! Reading and writing moves in Standard Algebraic Notation (SAN), as used in PGN files.
+//!
+//! A move is written as the letter of the piece (none for pawns), the file and/or rank it
+//! comes from if another piece of the same kind could go to the same square, an `x` for
+//! captures and the square it goes to, e.g. "Nf3", "Rad1" or "exd5". Promotions add an `=`
+//! and the new piece ("e8=Q"), castling is "O-O" or "O-O-O" and drops are written like "N@f3".
+//! A `+` is added to moves giving check and a `#` to moves giving checkmate.
+//!
+//! When reading, the check marks and annotations like `!?` are ignored, castling may be written
+//! with zeros ("0-0"), and the `x`, a `-` between the squares or the `=` of a promotion may be
+//! left out, so "Ng1-f3" and "e8Q" are understood as well.
+
+use crate::fen::piece_to_char;
+use crate::{parse_square, square_name, Color, Game, Move, Piece, PieceType};
+
+impl<const WIDTH: usize, const HEIGHT: usize> Game<WIDTH, HEIGHT> {
+    /// Writes a legal move in Standard Algebraic Notation, e.g. "Nf3" or "e8=Q+".
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::Game;
+    /// let game = Game::from_fen("r3k3/1P6/8/8/8/8/8/R3K2R w KQq - 0 1").unwrap();
+    /// let san = |text| game.move_to_san(game.parse_uci_move(text).unwrap());
+    /// assert_eq!(san("e1g1"), "O-O");
+    /// assert_eq!(san("b7a8q"), "bxa8=Q+");
+    /// assert_eq!(san("a1a8"), "Rxa8+");
+    /// ```
+    pub fn move_to_san(&self, mv: Move) -> String {
+        let mut san = self.san_without_check(mv);
+
+        let mut game = self.position();
+        game.do_move(mv);
+        if game.is_check() {
+            san.push(if game.get_legal_moves().is_empty() {
+                '#'
+            } else {
+                '+'
+            });
+        }
+        san
+    }
+
+    /// Finds the legal move written in Standard Algebraic Notation, returning None if there is
+    /// no such move or if the text could mean more than one move.
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::Game;
+    /// let game = Game::new();
+    /// assert_eq!(game.parse_san("Nf3"), game.parse_uci_move("g1f3"));
+    /// assert_eq!(game.parse_san("e4!?"), game.parse_uci_move("e2e4"));
+    /// assert!(game.parse_san("Nd2").is_none());
+    /// ```
+    pub fn parse_san(&self, text: &str) -> Option<Move> {
+        let text = text.trim().trim_end_matches(['+', '#', '!', '?']);
+        let moves = self.get_legal_moves();
+
+        if let Some(queenside) = match text {
+            "O-O" | "0-0" => Some(false),
+            "O-O-O" | "0-0-0" => Some(true),
+            _ => None,
+        } {
+            return moves
+                .into_iter()
+                .find(|mv| self.is_castling(*mv) && (mv.to.1 < mv.from.1) == queenside);
+        }
+
+        if let Some((piece, square)) = text.split_once('@') {
+            let piece = match piece {
+                "" => PieceType::Pawn,
+                piece if piece.chars().count() == 1 => {
+                    self.piece_from_char(piece.chars().next()?)?
+                }
+                _ => return None,
+            };
+            let to = parse_square(square, WIDTH, HEIGHT)?;
+            return moves
+                .into_iter()
+                .find(|mv| mv.drop == Some(piece) && mv.to == to);
+        }
+
+        // the piece, then the squares, then the promotion
+        let mut rest: String = text
+            .chars()
+            .filter(|c| !matches!(c, 'x' | '-' | '=' | ':'))
+            .collect();
+        let piece = match rest.chars().next()? {
+            letter if letter.is_ascii_uppercase() || letter == '*' => {
+                rest.remove(0);
+                self.piece_from_char(letter)?
+            }
+            _ => PieceType::Pawn,
+        };
+        let promotion = match rest.chars().last()? {
+            letter if letter.is_ascii_uppercase() => {
+                rest.pop();
+                Some(self.piece_from_char(letter)?)
+            }
+            _ => None,
+        };
+        let split = rest.rfind(|c: char| c.is_ascii_lowercase())?;
+        let to = parse_square(&rest[split..], WIDTH, HEIGHT)?;
+        let from = &rest[..split];
+        let file = from.chars().find(|c| c.is_ascii_lowercase());
+        let rank: String = from.chars().filter(|c| c.is_ascii_digit()).collect();
+
+        let mut candidates = moves.into_iter().filter(|mv| {
+            let moving = self.board[mv.from.0][mv.from.1];
+            mv.drop.is_none()
+                && mv.to == to
+                && !self.is_castling(*mv)
+                && moving.is_some_and(|moving| moving.piece == piece)
+                && mv.promotion == promotion
+                && file.is_none_or(|file| (mv.from.1 as u8 + b'a') as char == file)
+                && (rank.is_empty() || (HEIGHT - mv.from.0).to_string() == rank)
+        });
+        let mv = candidates.next()?;
+        match candidates.next() {
+            Some(_) => None,
+            None => Some(mv),
+        }
+    }
+
+    fn san_without_check(&self, mv: Move) -> String {
+        let to = square_name(mv.to, HEIGHT).to_lowercase();
+        if let Some(piece) = mv.drop {
+            return format!("{}@{}", letter(piece), to);
+        }
+        if self.is_castling(mv) {
+            return if mv.to.1 > mv.from.1 { "O-O" } else { "O-O-O" }.to_string();
+        }
+        let piece = match self.board[mv.from.0][mv.from.1] {
+            Some(piece) => piece,
+            None => return to,
+        };
+
+        let file = |col: usize| ((col as u8 + b'a') as char).to_string();
+        let capture = self.board[mv.to.0][mv.to.1].is_some()
+            || (piece.piece == PieceType::Pawn && mv.from.1 != mv.to.1);
+
+        if piece.piece == PieceType::Pawn {
+            let mut san = if capture {
+                format!("{}x{}", file(mv.from.1), to)
+            } else {
+                to
+            };
+            if let Some(promotion) = mv.promotion {
+                san.push('=');
+                san.push(letter(promotion));
+            }
+            return san;
+        }
+
+        // the other pieces of the same kind that could go to the same square
+        let others: Vec<(usize, usize)> = self
+            .get_legal_moves()
+            .into_iter()
+            .filter(|other| {
+                other.to == mv.to
+                    && other.from != mv.from
+                    && other.drop.is_none()
+                    && self.board[other.from.0][other.from.1] == Some(piece)
+            })
+            .map(|other| other.from)
+            .collect();
+        let from = if others.is_empty() {
+            String::new()
+        } else if others.iter().all(|other| other.1 != mv.from.1) {
+            file(mv.from.1)
+        } else if others.iter().all(|other| other.0 != mv.from.0) {
+            (HEIGHT - mv.from.0).to_string()
+        } else {
+            square_name(mv.from, HEIGHT).to_lowercase()
+        };
+
+        let mut san = format!("{}{}", letter(piece.piece), from);
+        if capture {
+            san.push('x');
+        }
+        san.push_str(&to);
+        if let Some(promotion) = mv.promotion {
+            san.push('=');
+            san.push(letter(promotion));
+        }
+        san
+    }
+}
+
+// the uppercase letter of the piece
+fn letter(piece: PieceType) -> char {
+    piece_to_char(Piece {
+        color: Color::White,
+        piece,
+        untouched: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Game;
+
+    fn san(fen: &str, moves: &[&str]) -> Vec<String> {
+        let game = Game::from_fen(fen).unwrap();
+        moves
+            .iter()
+            .map(|text| game.move_to_san(game.parse_uci_move(text).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn disambiguation() {
+        // two knights on the same rank, two rooks on the same file, and three queens
+        let fen = "4k3/R7/4N1N1/8/1Q1Q4/R7/3Q4/7K w - - 0 1";
+        assert_eq!(
+            san(
+                fen,
+                &["e6f4", "g6f4", "a7a5", "a3a5", "d4c3", "b4c3", "d2c3"]
+            ),
+            vec!["Nef4", "Ngf4", "R7a5", "R3a5", "Qd4c3", "Qbc3", "Q2c3"]
+        );
+    }
+
+    #[test]
+    fn reading() {
+        let game = Game::from_fen("4k3/R7/4N1N1/8/1Q1Q4/R7/3Q4/7K w - - 0 1").unwrap();
+        for (san, uci) in [
+            ("Nef4", "e6f4"),
+            ("Nexf4", "e6f4"),
+            ("R3a5+", "a3a5"),
+            ("Qd4c3", "d4c3"),
+            ("Qd4-c3", "d4c3"),
+            ("Kg1", "h1g1"),
+        ] {
+            assert_eq!(game.parse_san(san), game.parse_uci_move(uci), "{}", san);
+        }
+        // ambiguous, or not a move at all
+        for san in ["Nf4", "N6f4", "Ra5", "Qdc3", "Qh7", "Ke9", "", "x"] {
+            assert_eq!(game.parse_san(san), None, "{}", san);
+        }
+
+        let game = Game::from_fen("r3k3/1P6/8/8/8/8/8/R3K2R w KQq - 0 1").unwrap();
+        for (san, uci) in [
+            ("O-O", "e1g1"),
+            ("0-0-0", "e1c1"),
+            ("bxa8=N", "b7a8n"),
+            ("bxa8Q", "b7a8q"),
+            ("b8=R", "b7b8r"),
+        ] {
+            assert_eq!(game.parse_san(san), game.parse_uci_move(uci), "{}", san);
+        }
+        assert_eq!(game.parse_san("b8"), None);
+
+        // every move written by move_to_san() reads back as the same move
+        let game = Game::new();
+        for mv in game.get_legal_moves() {
+            assert_eq!(game.parse_san(&game.move_to_san(mv)), Some(mv));
+        }
+    }
+
+    #[test]
+    fn pawns_and_checks() {
+        let fen = "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3";
+        assert_eq!(
+            san(fen, &["e5f6", "e5e6", "f1b5"]),
+            vec!["exf6", "e6", "Bb5+"]
+        );
+
+        // fool's mate
+        let fen = "rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2";
+        assert_eq!(san(fen, &["d8h4"]), vec!["Qh4#"]);
+    }
+}
@@ -0,0 +1,1154 @@
+//! Lets the library pick a move by itself.
+//!
+//! The search is an iterative deepening negamax with alpha-beta pruning. Each iteration
+//! searches one ply deeper than the last, and the best line found (the principal variation)
+//! is kept between iterations. At the end of the main search only captures are searched
+//! (quiescence search) so that the evaluation isn't done in the middle of an exchange.
+//!
+//! A search can be stopped after a given depth, a given number of nodes, a given time, or by
+//! setting a shared stop flag from another thread. When playing with a clock a `TimeManager`
+//! can decide how long to search.
+//!
+//! To reach deeper, moves that are unlikely to matter are pruned or searched less deep
+//! (null move pruning, late move reductions, futility pruning), checks are searched one ply
+//! deeper, and each iteration starts with a narrow window around the last score (aspiration
+//! windows). All of these can be turned off with `SearchOptions`, e.g. to compare them.
+//!
+//! The search can use several threads ("Lazy SMP"): helper threads search the same position
+//! at the same time, sharing the transposition table with the main thread, so that the main
+//! thread finds more of its positions already searched. With one thread the search is
+//! deterministic.
+//!
+//! # Examples
+//! ```
+//! use vprytz_chess::search::{search, Limits};
+//! use vprytz_chess::Game;
+//!
+//! let game = Game::new();
+//! let result = search(&game, &Limits { depth: Some(2), ..Limits::default() });
+//! println!("best move: {}", result.best_move.unwrap());
+//! ```
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::eval::{evaluate, piece_value};
+use crate::ordering::{captured_piece, see, MoveOrderer};
+use crate::timeman::TimeManager;
+use crate::tt::{Bound, TranspositionTable};
+use crate::variant::Variant;
+use crate::{Color, Game, Move, PieceType, BOARD_SIZE};
+
+/// Score of being checkmated right now, a mate in n plies is scored as `MATE - n`.
+pub const MATE: i32 = 30_000;
+/// Larger than any score the search can return.
+pub const INFINITY: i32 = 31_000;
+/// The deepest the search will ever go, counting the quiescence search.
+pub const MAX_PLY: usize = 128;
+
+// scores further from zero than this are mate scores
+const MATE_BOUND: i32 = MATE - MAX_PLY as i32;
+
+// how often (in nodes) the shared stop flag is checked
+const STOP_CHECK_INTERVAL: u64 = 1024;
+
+/// Size of the transposition table used by `Searcher::new()`, in megabytes.
+pub const DEFAULT_HASH_MB: usize = 16;
+
+// half the width of the first aspiration window, in centipawns
+const ASPIRATION_WINDOW: i32 = 25;
+
+// how far below alpha the static evaluation has to be to skip quiet moves, indexed by depth
+const FUTILITY_MARGINS: [i32; 3] = [0, 200, 350];
+
+// how far above beta the static evaluation has to be to return early, per ply of depth
+const REVERSE_FUTILITY_MARGIN: i32 = 120;
+
+/// Turns the different prunings and extensions of the search on or off, all are on by default.
+/// # Examples
+/// ```
+/// use vprytz_chess::search::{SearchOptions, Searcher};
+/// let mut searcher = Searcher::new();
+/// searcher.set_options(SearchOptions {
+///     null_move: false,
+///     ..SearchOptions::default()
+/// });
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchOptions {
+    /// Let the opponent move twice, and if we are still doing well enough, skip the position.
+    /// Not used when the player to move only has pawns left, since zugzwang is common there.
+    pub null_move: bool,
+    /// Search quiet moves late in the move ordering less deep, unless they turn out to be good.
+    pub late_move_reductions: bool,
+    /// Skip quiet moves close to the leaves when the position is far below alpha.
+    pub futility_pruning: bool,
+    /// Return early close to the leaves when the position is far above beta.
+    pub reverse_futility_pruning: bool,
+    /// Search moves that give check one ply deeper.
+    pub check_extensions: bool,
+    /// Search each iteration with a narrow window around the last score first.
+    pub aspiration_windows: bool,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            null_move: true,
+            late_move_reductions: true,
+            futility_pruning: true,
+            reverse_futility_pruning: true,
+            check_extensions: true,
+            aspiration_windows: true,
+        }
+    }
+}
+
+/// When to stop searching. Whichever limit is hit first stops the search,
+/// and with no limits at all the search goes on until `MAX_PLY` or the stop flag is set.
+#[derive(Clone, Debug, Default)]
+pub struct Limits {
+    /// Maximum depth (in plies) to search to.
+    pub depth: Option<u32>,
+    /// Maximum number of nodes to visit.
+    pub nodes: Option<u64>,
+    /// Stops the search as soon as it is set to true, e.g. from a UI thread.
+    pub stop: Option<Arc<AtomicBool>>,
+    /// Maximum time to search for.
+    pub time: Option<Duration>,
+    /// Decides how long to search when playing with a clock, see the `timeman` module.
+    /// Its hard limit works like `time`.
+    pub manager: Option<TimeManager>,
+}
+
+/// The outcome of a search (or of one iteration of it).
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchResult {
+    /// The best move found, None if there are no legal moves.
+    pub best_move: Option<Move>,
+    /// Score in centipawns from the point of view of the player to move, see `mate_in()` for mate scores.
+    pub score: i32,
+    /// The principal variation, the line of best play starting with best_move.
+    pub pv: Vec<Move>,
+    /// The last depth that was fully searched.
+    pub depth: u32,
+    /// Number of nodes visited.
+    pub nodes: u64,
+    /// The best lines found, best first, when searching for more than one (see `Searcher::set_multi_pv()`).
+    /// The first line is always the same as `pv` and `score`.
+    pub lines: Vec<PvLine>,
+}
+
+/// One of the lines found by a search, with its score.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PvLine {
+    pub score: i32,
+    pub pv: Vec<Move>,
+}
+
+/// If the score is a mate score, returns the number of moves until mate.
+/// The number is positive if the player to move is giving mate and negative if they are getting mated.
+/// # Examples
+/// ```
+/// use vprytz_chess::search::{mate_in, MATE};
+/// assert_eq!(mate_in(MATE - 1), Some(1));
+/// assert_eq!(mate_in(-MATE + 2), Some(-1));
+/// assert_eq!(mate_in(50), None);
+/// ```
+pub fn mate_in(score: i32) -> Option<i32> {
+    if score > MATE_BOUND {
+        Some((MATE - score + 1) / 2)
+    } else if score < -MATE_BOUND {
+        Some(-(MATE + score) / 2)
+    } else {
+        None
+    }
+}
+
+/// Searches the game with the given limits and returns the best move found.
+/// # Examples
+/// ```
+/// use vprytz_chess::search::{search, Limits};
+/// use vprytz_chess::Game;
+/// let mut game = Game::new();
+/// let result = search(&game, &Limits { depth: Some(1), ..Limits::default() });
+/// game.apply_move(result.best_move.unwrap());
+/// ```
+pub fn search(game: &Game, limits: &Limits) -> SearchResult {
+    Searcher::new().search(game, limits, |_| {})
+}
+
+/// Holds the state of a running search. Most users can call `search()` directly instead.
+/// The transposition table is kept between searches, so reusing a searcher for the
+/// moves of one game makes it faster.
+pub struct Searcher {
+    limits: Limits,
+    nodes: u64,
+    stopped: bool,
+    tt: Arc<TranspositionTable>,
+    orderer: MoveOrderer,
+    options: SearchOptions,
+    threads: usize,
+    multi_pv: usize,
+    // root moves already used by an earlier line of this iteration, when searching several lines
+    excluded: Vec<Move>,
+    start: Instant,
+}
+
+impl Default for Searcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Searcher {
+    /// Creates a new searcher with a transposition table of `DEFAULT_HASH_MB` megabytes.
+    pub fn new() -> Searcher {
+        Searcher::with_table(Arc::new(TranspositionTable::new(DEFAULT_HASH_MB)))
+    }
+
+    /// Creates a new searcher using the given transposition table, which may be shared with other searchers.
+    /// # Examples
+    /// ```
+    /// use std::sync::Arc;
+    /// use vprytz_chess::search::Searcher;
+    /// use vprytz_chess::tt::TranspositionTable;
+    /// let searcher = Searcher::with_table(Arc::new(TranspositionTable::new(64)));
+    /// ```
+    pub fn with_table(tt: Arc<TranspositionTable>) -> Searcher {
+        Searcher {
+            limits: Limits::default(),
+            nodes: 0,
+            stopped: false,
+            tt,
+            orderer: MoveOrderer::new(),
+            options: SearchOptions::default(),
+            threads: 1,
+            multi_pv: 1,
+            excluded: Vec::new(),
+            start: Instant::now(),
+        }
+    }
+
+    /// Sets how many of the best lines to search for (at least one), the default is one.
+    /// Searching for more lines makes the search slower.
+    pub fn set_multi_pv(&mut self, lines: usize) {
+        self.multi_pv = lines.max(1);
+    }
+
+    /// Returns how many lines the search looks for.
+    pub fn multi_pv(&self) -> usize {
+        self.multi_pv
+    }
+
+    /// Replaces the transposition table with a new, empty one of the given size in megabytes.
+    pub fn set_hash_size(&mut self, megabytes: usize) {
+        self.tt = Arc::new(TranspositionTable::new(megabytes));
+    }
+
+    /// Changes which prunings and extensions are used by the search.
+    pub fn set_options(&mut self, options: SearchOptions) {
+        self.options = options;
+    }
+
+    /// Returns the prunings and extensions used by the search.
+    pub fn options(&self) -> &SearchOptions {
+        &self.options
+    }
+
+    /// Sets the number of threads to search with (at least one), the default is one.
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::search::{Limits, Searcher};
+    /// use vprytz_chess::Game;
+    /// let mut searcher = Searcher::new();
+    /// searcher.set_threads(4);
+    /// let limits = Limits { depth: Some(3), ..Limits::default() };
+    /// let result = searcher.search(&Game::new(), &limits, |_| {});
+    /// ```
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    /// Returns the number of threads used by the search.
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Returns the transposition table used by this searcher.
+    pub fn table(&self) -> &Arc<TranspositionTable> {
+        &self.tt
+    }
+
+    /// Runs the search, calling `on_iteration` with the result of each finished iteration
+    /// of the main thread (e.g. to print information about the search while it is running).
+    /// The node limit applies to the main thread, while the returned node count is for all threads.
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::search::{Limits, Searcher};
+    /// use vprytz_chess::Game;
+    /// let game = Game::new();
+    /// let mut searcher = Searcher::new();
+    /// let limits = Limits { depth: Some(3), ..Limits::default() };
+    /// searcher.search(&game, &limits, |result| println!("depth {} score {}", result.depth, result.score));
+    /// ```
+    pub fn search<F: FnMut(&SearchResult)>(
+        &mut self,
+        game: &Game,
+        limits: &Limits,
+        on_iteration: F,
+    ) -> SearchResult {
+        self.tt.new_search();
+        if self.threads == 1 {
+            return self.iterate(game, limits, 1, on_iteration);
+        }
+
+        // the helpers run until the main thread is done
+        let helper_stop = Arc::new(AtomicBool::new(false));
+        let helper_limits = Limits {
+            depth: limits.depth,
+            stop: Some(helper_stop.clone()),
+            ..Limits::default()
+        };
+
+        thread::scope(|scope| {
+            let helpers: Vec<_> = (1..self.threads)
+                .map(|index| {
+                    let mut helper = Searcher::with_table(self.tt.clone());
+                    helper.options = self.options.clone();
+                    let limits = helper_limits.clone();
+
+                    // every other helper starts one ply deeper, so that they don't all search the same thing
+                    let start_depth = 1 + (index % 2) as u32;
+                    scope.spawn(move || helper.iterate(game, &limits, start_depth, |_| {}).nodes)
+                })
+                .collect();
+
+            let mut result = self.iterate(game, limits, 1, on_iteration);
+
+            helper_stop.store(true, Ordering::Relaxed);
+            for helper in helpers {
+                result.nodes += helper.join().unwrap_or(0);
+            }
+            result
+        })
+    }
+
+    // iterative deepening, starting at the given depth
+    fn iterate<F: FnMut(&SearchResult)>(
+        &mut self,
+        game: &Game,
+        limits: &Limits,
+        start_depth: u32,
+        mut on_iteration: F,
+    ) -> SearchResult {
+        self.limits = limits.clone();
+        self.nodes = 0;
+        self.stopped = false;
+        self.start = Instant::now();
+        self.orderer.new_search();
+
+        let mut manager = limits.manager.clone();
+        if let Some(manager) = &manager {
+            let hard = manager.hard_limit();
+            self.limits.time = Some(self.limits.time.map_or(hard, |time| time.min(hard)));
+        }
+
+        let mut game = game.clone();
+        let max_depth = limits
+            .depth
+            .unwrap_or(MAX_PLY as u32)
+            .clamp(1, MAX_PLY as u32);
+
+        let mut result = SearchResult {
+            best_move: None,
+            score: 0,
+            pv: Vec::new(),
+            depth: 0,
+            nodes: 0,
+            lines: Vec::new(),
+        };
+
+        for depth in start_depth..=max_depth {
+            // search each line with the moves of the earlier lines left out at the root
+            let mut lines: Vec<PvLine> = Vec::new();
+            self.excluded.clear();
+            while lines.len() < self.multi_pv {
+                let mut pv = Vec::new();
+                let score = if lines.is_empty() {
+                    self.aspiration(&mut game, depth, result.score, &mut pv)
+                } else {
+                    self.negamax(&mut game, depth, 0, -INFINITY, INFINITY, &mut pv)
+                };
+                if self.stopped || pv.is_empty() {
+                    // the first line is kept even without a move, to tell mate and stalemate apart
+                    if lines.is_empty() && !self.stopped {
+                        lines.push(PvLine { score, pv });
+                    }
+                    break;
+                }
+                self.excluded.push(pv[0]);
+                lines.push(PvLine { score, pv });
+            }
+            self.excluded.clear();
+            // later lines are searched separately and can come out better than earlier ones
+            lines.sort_by_key(|line| -line.score);
+
+            // a stopped iteration can't be trusted, so keep the result of the last finished one
+            if self.stopped {
+                break;
+            }
+
+            let score = lines[0].score;
+            result = SearchResult {
+                best_move: lines[0].pv.first().copied(),
+                score,
+                pv: lines[0].pv.clone(),
+                depth,
+                nodes: self.nodes,
+                lines,
+            };
+            on_iteration(&result);
+
+            // no point in searching deeper once we know how the game ends
+            if result.best_move.is_none() {
+                break;
+            }
+            if let Some(manager) = manager.as_mut() {
+                if !manager.should_continue(&result, self.start.elapsed()) {
+                    break;
+                }
+            }
+            if let Some(moves) = mate_in(score) {
+                if depth as i32 >= moves.abs() * 2 {
+                    break;
+                }
+            }
+        }
+
+        // if we got stopped before the first iteration finished, play any legal move
+        if result.best_move.is_none() && result.depth == 0 {
+            result.best_move = game.get_legal_moves().first().copied();
+            result.pv = result.best_move.into_iter().collect();
+            result.lines = vec![PvLine {
+                score: 0,
+                pv: result.pv.clone(),
+            }];
+        }
+        result.nodes = self.nodes;
+
+        result
+    }
+
+    // search the root with a window around the score of the last iteration, widening it
+    // until the score falls inside
+    fn aspiration(
+        &mut self,
+        game: &mut Game,
+        depth: u32,
+        last_score: i32,
+        pv: &mut Vec<Move>,
+    ) -> i32 {
+        if !self.options.aspiration_windows || depth < 4 || mate_in(last_score).is_some() {
+            return self.negamax(game, depth, 0, -INFINITY, INFINITY, pv);
+        }
+
+        let mut delta = ASPIRATION_WINDOW;
+        let mut alpha = last_score - delta;
+        let mut beta = last_score + delta;
+        loop {
+            let score = self.negamax(game, depth, 0, alpha, beta, pv);
+            if self.stopped {
+                return 0;
+            }
+
+            if score <= alpha {
+                alpha = (score - delta).max(-INFINITY);
+            } else if score >= beta {
+                beta = (score + delta).min(INFINITY);
+            } else {
+                return score;
+            }
+            delta *= 2;
+
+            // give up on the window when it gets this wide
+            if delta > 1000 {
+                alpha = -INFINITY;
+                beta = INFINITY;
+            }
+        }
+    }
+
+    // the main alpha-beta search, the best line found is written to "pv"
+    fn negamax(
+        &mut self,
+        game: &mut Game,
+        depth: u32,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+        pv: &mut Vec<Move>,
+    ) -> i32 {
+        pv.clear();
+        if game.is_duck_to_move() && ply > 0 {
+            return -self.duck_move(game, depth, ply, -beta, -alpha, pv);
+        }
+        if depth == 0 || ply >= MAX_PLY {
+            return self.quiescence(game, ply, alpha, beta);
+        }
+        if self.should_stop() {
+            return 0;
+        }
+        self.nodes += 1;
+
+        // fifty move rule and repetitions
+        if ply > 0 && (game.halfmove_clock >= 100 || game.is_repetition()) {
+            return 0;
+        }
+        if let Some(score) = variant_score(game, ply) {
+            return score;
+        }
+
+        // use the stored result if this position was already searched deep enough
+        let original_alpha = alpha;
+        let hash = game.hash;
+        let mut hash_move = None;
+        if let Some(entry) = self.tt.probe(hash, ply) {
+            hash_move = entry.best_move;
+            if ply > 0 && entry.depth >= depth {
+                let cutoff = match entry.bound {
+                    Bound::Exact => true,
+                    Bound::Lower => entry.score >= beta,
+                    Bound::Upper => entry.score <= alpha,
+                };
+                if cutoff {
+                    if entry.bound == Bound::Exact {
+                        pv.extend(entry.best_move);
+                    }
+                    return entry.score;
+                }
+            }
+        }
+
+        let color = game.active_color;
+        let in_check = game.is_check();
+        let is_pv = beta - alpha > 1;
+        let static_eval = if in_check { -INFINITY } else { evaluate(game) };
+
+        // reverse futility pruning, we are so far above beta that a shallow search won't change it
+        if self.options.reverse_futility_pruning
+            && !is_pv
+            && !in_check
+            && depth <= 3
+            && beta.abs() < MATE_BOUND
+            && static_eval - REVERSE_FUTILITY_MARGIN * depth as i32 >= beta
+        {
+            return static_eval;
+        }
+
+        // null move pruning, if passing still keeps us above beta then a real move will too
+        if self.options.null_move
+            && !is_pv
+            && !in_check
+            && ply > 0
+            && depth >= 3
+            && static_eval >= beta
+            && !game.last_move_was_null()
+            && has_pieces(game, color)
+            // with compulsory captures being forced to move is the normal case
+            && game.get_variant() != Variant::Antichess
+        {
+            let reduction = 2 + depth / 6;
+            let mut null_pv = Vec::new();
+            game.do_null_move();
+            let score = -self.negamax(
+                game,
+                depth.saturating_sub(1 + reduction),
+                ply + 1,
+                -beta,
+                -beta + 1,
+                &mut null_pv,
+            );
+            game.undo();
+
+            if self.stopped {
+                return 0;
+            }
+            if score >= beta {
+                // don't trust mate scores found by passing
+                return if score > MATE_BOUND { beta } else { score };
+            }
+        }
+
+        // futility pruning, quiet moves can't bring us up to alpha this close to the leaves
+        let futile = self.options.futility_pruning
+            && !is_pv
+            && !in_check
+            && (depth as usize) < FUTILITY_MARGINS.len()
+            && alpha.abs() < MATE_BOUND
+            && static_eval + FUTILITY_MARGINS[depth as usize] <= alpha;
+
+        // search the best move from the last time we saw this position first, see the ordering module
+        let mut moves = game.pseudo_legal_moves();
+        self.orderer.order(game, &mut moves, hash_move, ply);
+
+        let mut best = -INFINITY;
+        let mut best_move = None;
+        let mut legal_moves = 0;
+        let mut child_pv = Vec::new();
+
+        for mv in moves {
+            if ply == 0 && self.excluded.contains(&mv) {
+                continue;
+            }
+            let quiet = captured_piece(game, mv).is_none() && mv.promotion.is_none();
+
+            game.do_move(mv);
+            if game.king_attacked(color) {
+                game.undo();
+                continue;
+            }
+            legal_moves += 1;
+            let gives_check = game.is_check();
+
+            if futile && quiet && !gives_check && legal_moves > 1 {
+                game.undo();
+                best = best.max(static_eval + FUTILITY_MARGINS[depth as usize]);
+                continue;
+            }
+
+            let extension = if self.options.check_extensions && gives_check {
+                1
+            } else {
+                0
+            };
+            let new_depth = depth - 1 + extension;
+
+            // late move reductions, quiet moves far down the list are probably bad,
+            // so first check that with a shallower search and a null window
+            let mut score = alpha + 1;
+            if self.options.late_move_reductions
+                && legal_moves > 3
+                && depth >= 3
+                && quiet
+                && !in_check
+                && !gives_check
+            {
+                let reduction = if legal_moves > 6 { 2 } else { 1 };
+                score = -self.negamax(
+                    game,
+                    new_depth.saturating_sub(reduction),
+                    ply + 1,
+                    -alpha - 1,
+                    -alpha,
+                    &mut child_pv,
+                );
+            }
+
+            // search at full depth if not reduced, or if the reduced search surprised us
+            if score > alpha {
+                score = -self.negamax(game, new_depth, ply + 1, -beta, -alpha, &mut child_pv);
+            }
+            game.undo();
+
+            if self.stopped {
+                return 0;
+            }
+
+            if score > best {
+                best = score;
+                best_move = Some(mv);
+                if score > alpha {
+                    alpha = score;
+                    pv.clear();
+                    pv.push(mv);
+                    pv.extend_from_slice(&child_pv);
+                    if alpha >= beta {
+                        if quiet {
+                            self.orderer.record_cutoff(color, mv, depth, ply);
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+
+        // no legal moves means either checkmate or stalemate, or a win in Antichess and Duck chess
+        if legal_moves == 0 {
+            return if matches!(game.get_variant(), Variant::Antichess | Variant::Duck) {
+                MATE - ply as i32
+            } else if in_check {
+                -MATE + ply as i32
+            } else {
+                0
+            };
+        }
+
+        // the root score is wrong for the position when moves were left out
+        if ply > 0 || self.excluded.is_empty() {
+            let bound = if best >= beta {
+                Bound::Lower
+            } else if best > original_alpha {
+                Bound::Exact
+            } else {
+                Bound::Upper
+            };
+            self.tt.store(hash, depth, bound, best, best_move, ply);
+        }
+
+        best
+    }
+
+    // keep searching captures until the position is quiet, so that we don't stop in the middle of an exchange
+    fn quiescence(&mut self, game: &mut Game, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        if game.is_duck_to_move() && ply > 0 {
+            return -self.duck_move(game, 0, ply, -beta, -alpha, &mut Vec::new());
+        }
+        if self.should_stop() {
+            return 0;
+        }
+        self.nodes += 1;
+
+        if let Some(score) = variant_score(game, ply) {
+            return score;
+        }
+
+        // "stand pat", we don't have to capture anything if we are already doing well enough
+        let stand_pat = evaluate(game);
+        if stand_pat >= beta || ply >= MAX_PLY {
+            return stand_pat;
+        }
+        if stand_pat > alpha {
+            alpha = stand_pat;
+        }
+
+        // only captures and promotions that don't lose material
+        let color = game.active_color;
+        let mut moves = game.pseudo_legal_moves();
+        moves.retain(|mv| {
+            (captured_piece(game, *mv).is_some() || mv.promotion.is_some()) && see(game, *mv) >= 0
+        });
+        self.orderer.order(game, &mut moves, None, ply);
+
+        let mut best = stand_pat;
+        for mv in moves {
+            game.do_move(mv);
+            if game.king_attacked(color) {
+                game.undo();
+                continue;
+            }
+            let score = -self.quiescence(game, ply + 1, -beta, -alpha);
+            game.undo();
+
+            if self.stopped {
+                return 0;
+            }
+
+            if score > best {
+                best = score;
+                if score > alpha {
+                    alpha = score;
+                    if alpha >= beta {
+                        break;
+                    }
+                }
+            }
+        }
+
+        best
+    }
+
+    // the second half of a turn in Duck chess, where the player who just moved also moves the
+    // duck. This is searched as part of the move before it, so the score is from the point of
+    // view of the player whose turn it was there (negamax() and quiescence() negate it back),
+    // and no depth is used up for it.
+    fn duck_move(
+        &mut self,
+        game: &mut Game,
+        depth: u32,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+        pv: &mut Vec<Move>,
+    ) -> i32 {
+        let mut best = -INFINITY;
+        let mut child_pv = Vec::new();
+        for mv in game.get_legal_moves() {
+            game.do_move(mv);
+            let score = -self.negamax(game, depth, ply + 1, -beta, -alpha, &mut child_pv);
+            game.undo();
+
+            if self.stopped {
+                return 0;
+            }
+            if score > best {
+                best = score;
+                if score > alpha {
+                    alpha = score;
+                    pv.clear();
+                    pv.push(mv);
+                    pv.extend_from_slice(&child_pv);
+                    if alpha >= beta {
+                        break;
+                    }
+                }
+            }
+        }
+        best
+    }
+
+    // check the limits, remembering if we have been stopped
+    fn should_stop(&mut self) -> bool {
+        if self.stopped {
+            return true;
+        }
+        if let Some(nodes) = self.limits.nodes {
+            if self.nodes >= nodes {
+                self.stopped = true;
+            }
+        }
+        if self.nodes.is_multiple_of(STOP_CHECK_INTERVAL) {
+            if let Some(stop) = &self.limits.stop {
+                if stop.load(Ordering::Relaxed) {
+                    self.stopped = true;
+                }
+            }
+            if let Some(time) = self.limits.time {
+                if self.start.elapsed() >= time {
+                    self.stopped = true;
+                }
+            }
+        }
+        self.stopped
+    }
+}
+
+// the score of a game ended by a rule of the variant, won and lost games are scored like mates
+fn variant_score(game: &Game, ply: usize) -> Option<i32> {
+    let outcome = game.variant_outcome()?;
+    Some(match outcome.winner() {
+        Some(winner) if winner == game.active_color => MATE - ply as i32,
+        Some(_) => -MATE + ply as i32,
+        None => 0,
+    })
+}
+
+// true if the player has anything but pawns and the king, used to avoid null moves in pawn endgames
+fn has_pieces(game: &Game, color: Color) -> bool {
+    (0..BOARD_SIZE).any(|row| {
+        (0..BOARD_SIZE).any(|col| match game.board[row][col] {
+            Some(piece) => {
+                piece.color == color && piece_value(piece.piece) > piece_value(PieceType::Pawn)
+            }
+            None => false,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // play a list of moves given as pairs of squares
+    fn play(moves: &[(&str, &str)]) -> Game {
+        let mut game = Game::new();
+        for (from, to) in moves {
+            assert!(game.make_move(from.to_string(), to.to_string()).is_some());
+        }
+        game
+    }
+
+    #[test]
+    fn finds_mate_in_one() {
+        // scholar's mate is one move away
+        let game = play(&[
+            ("E2", "E4"),
+            ("E7", "E5"),
+            ("F1", "C4"),
+            ("B8", "C6"),
+            ("D1", "H5"),
+            ("G8", "F6"),
+        ]);
+
+        let result = search(
+            &game,
+            &Limits {
+                depth: Some(3),
+                ..Limits::default()
+            },
+        );
+
+        assert_eq!(result.best_move.unwrap().to_string(), "h5f7");
+        assert_eq!(mate_in(result.score), Some(1));
+        assert_eq!(result.pv.len(), 1);
+    }
+
+    #[test]
+    fn takes_hanging_queen() {
+        let game = play(&[("E2", "E4"), ("D7", "D5"), ("D1", "G4")]);
+
+        let result = search(
+            &game,
+            &Limits {
+                depth: Some(2),
+                ..Limits::default()
+            },
+        );
+
+        assert_eq!(result.best_move.unwrap().to_string(), "c8g4");
+        assert!(result.score > 500);
+    }
+
+    #[test]
+    fn respects_node_limit() {
+        let game = Game::new();
+
+        let result = search(
+            &game,
+            &Limits {
+                nodes: Some(2000),
+                ..Limits::default()
+            },
+        );
+
+        assert!(result.nodes <= 2000);
+        assert!(result.best_move.is_some());
+    }
+
+    #[test]
+    fn stop_flag_still_gives_a_move() {
+        let game = Game::new();
+        let stop = Arc::new(AtomicBool::new(true));
+
+        let result = search(
+            &game,
+            &Limits {
+                stop: Some(stop),
+                ..Limits::default()
+            },
+        );
+
+        assert_eq!(result.depth, 0);
+        assert!(game.get_legal_moves().contains(&result.best_move.unwrap()));
+    }
+
+    #[test]
+    fn table_speeds_up_second_search() {
+        let game = play(&[("E2", "E4"), ("E7", "E5"), ("G1", "F3")]);
+        let limits = Limits {
+            depth: Some(4),
+            ..Limits::default()
+        };
+
+        let mut searcher = Searcher::new();
+        let first = searcher.search(&game, &limits, |_| {});
+        let second = searcher.search(&game, &limits, |_| {});
+
+        assert!(second.nodes < first.nodes / 2);
+        assert_eq!(second.best_move, first.best_move);
+    }
+
+    // every option turned off, for comparing against the default
+    fn no_pruning() -> SearchOptions {
+        SearchOptions {
+            null_move: false,
+            late_move_reductions: false,
+            futility_pruning: false,
+            reverse_futility_pruning: false,
+            check_extensions: false,
+            aspiration_windows: false,
+        }
+    }
+
+    #[test]
+    fn pruning_searches_fewer_nodes() {
+        let game = play(&[("E2", "E4"), ("E7", "E5"), ("G1", "F3"), ("B8", "C6")]);
+        let limits = Limits {
+            depth: Some(5),
+            ..Limits::default()
+        };
+
+        let mut plain = Searcher::new();
+        plain.set_options(no_pruning());
+        let plain = plain.search(&game, &limits, |_| {});
+        let pruned = Searcher::new().search(&game, &limits, |_| {});
+
+        assert!(pruned.nodes < plain.nodes);
+    }
+
+    #[test]
+    fn each_option_alone_still_finds_mate() {
+        let game = play(&[
+            ("E2", "E4"),
+            ("E7", "E5"),
+            ("F1", "C4"),
+            ("B8", "C6"),
+            ("D1", "H5"),
+            ("G8", "F6"),
+        ]);
+        let limits = Limits {
+            depth: Some(4),
+            ..Limits::default()
+        };
+
+        let options = [
+            SearchOptions {
+                null_move: true,
+                ..no_pruning()
+            },
+            SearchOptions {
+                late_move_reductions: true,
+                ..no_pruning()
+            },
+            SearchOptions {
+                futility_pruning: true,
+                ..no_pruning()
+            },
+            SearchOptions {
+                reverse_futility_pruning: true,
+                ..no_pruning()
+            },
+            SearchOptions {
+                check_extensions: true,
+                ..no_pruning()
+            },
+            SearchOptions {
+                aspiration_windows: true,
+                ..no_pruning()
+            },
+        ];
+        for options in options {
+            let mut searcher = Searcher::new();
+            searcher.set_options(options);
+            let result = searcher.search(&game, &limits, |_| {});
+            assert_eq!(result.best_move.unwrap().to_string(), "h5f7");
+        }
+    }
+
+    #[test]
+    fn no_null_move_with_only_pawns() {
+        let mut game = Game::new();
+        // remove everything but the kings and pawns
+        for row in [0, 7] {
+            for col in 0..BOARD_SIZE {
+                if col != 4 {
+                    game.board[row][col] = None;
+                }
+            }
+        }
+
+        assert!(!has_pieces(&game, Color::White));
+        assert!(has_pieces(&Game::new(), Color::White));
+    }
+
+    #[test]
+    fn one_thread_is_deterministic() {
+        let game = play(&[("E2", "E4"), ("C7", "C5"), ("G1", "F3")]);
+        let limits = Limits {
+            depth: Some(4),
+            ..Limits::default()
+        };
+
+        let first = Searcher::new().search(&game, &limits, |_| {});
+        let second = Searcher::new().search(&game, &limits, |_| {});
+
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn several_threads_find_mate() {
+        let game = play(&[
+            ("E2", "E4"),
+            ("E7", "E5"),
+            ("F1", "C4"),
+            ("B8", "C6"),
+            ("D1", "H5"),
+            ("G8", "F6"),
+        ]);
+
+        let mut searcher = Searcher::new();
+        searcher.set_threads(4);
+        let result = searcher.search(
+            &game,
+            &Limits {
+                depth: Some(4),
+                ..Limits::default()
+            },
+            |_| {},
+        );
+
+        assert_eq!(result.best_move.unwrap().to_string(), "h5f7");
+        assert_eq!(mate_in(result.score), Some(1));
+    }
+
+    #[test]
+    fn types_can_be_sent_between_threads() {
+        fn assert_send_sync<T: Send + Sync + Clone>() {}
+        fn assert_send<T: Send>() {}
+        assert_send_sync::<Game>();
+        assert_send_sync::<Move>();
+        assert_send_sync::<SearchResult>();
+        assert_send_sync::<Limits>();
+        assert_send::<Searcher>();
+    }
+
+    #[test]
+    fn several_lines() {
+        let game = play(&[("E2", "E4"), ("D7", "D5"), ("D1", "G4")]);
+
+        let mut searcher = Searcher::new();
+        searcher.set_multi_pv(3);
+        let result = searcher.search(
+            &game,
+            &Limits {
+                depth: Some(3),
+                ..Limits::default()
+            },
+            |_| {},
+        );
+
+        assert_eq!(result.lines.len(), 3);
+        assert_eq!(result.lines[0].pv, result.pv);
+        assert_eq!(result.pv[0].to_string(), "c8g4");
+        // the lines are all different and sorted best first
+        assert_ne!(result.lines[1].pv[0], result.lines[2].pv[0]);
+        assert!(result.lines[0].score >= result.lines[1].score);
+        assert!(result.lines[1].score >= result.lines[2].score);
+    }
+
+    #[test]
+    fn respects_time_limit() {
+        let game = Game::new();
+        let start = Instant::now();
+
+        let result = search(
+            &game,
+            &Limits {
+                time: Some(Duration::from_millis(100)),
+                ..Limits::default()
+            },
+        );
+
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(result.best_move.is_some());
+    }
+
+    #[test]
+    fn no_move_when_mated() {
+        // fool's mate
+        let game = play(&[("F2", "F3"), ("E7", "E5"), ("G2", "G4"), ("D8", "H4")]);
+
+        let result = search(
+            &game,
+            &Limits {
+                depth: Some(4),
+                ..Limits::default()
+            },
+        );
+
+        assert_eq!(result.best_move, None);
+        assert_eq!(result.score, -MATE);
+    }
+}
@@ -0,0 +1,553 @@
+//! Drawing positions as SVG images, e.g. for diagrams in documents or on web pages.
+//!
+//! The image is a single self-contained SVG string: the pieces are drawn with shapes of their
+//! own rather than taken from a font or other files. Squares can be highlighted, arrows drawn
+//! between them, and the last move of the game marked. See [`Diagram`] for the options.
+//!
+//! # Examples
+//! ```
+//! use vprytz_chess::svg::{Arrow, Diagram, Highlight};
+//! use vprytz_chess::Game;
+//! let mut game = Game::new();
+//! game.make_move("E2".to_string(), "E4".to_string());
+//! let diagram = Diagram {
+//!     arrows: vec![Arrow::new("g1→f3", "#15781b").unwrap()],
+//!     highlights: vec![Highlight {
+//!         square: "d5".to_string(),
+//!         color: "red".to_string(),
+//!     }],
+//!     last_move: true,
+//!     ..Diagram::default()
+//! };
+//! let svg = game.to_svg(&diagram);
+//! assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+//! ```
+
+use crate::{parse_square, Color, Game, Piece, PieceType};
+
+/// How a position is drawn by `Game::to_svg()`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagram {
+    /// The width and height of a square, in pixels.
+    pub square_size: u32,
+    /// Writes the ranks to the left of the board and the files below it.
+    pub coordinates: bool,
+    /// Shows the board the way Black sees it, with the first rank at the top.
+    pub flipped: bool,
+    /// Squares colored in to draw attention to them.
+    pub highlights: Vec<Highlight>,
+    pub arrows: Vec<Arrow>,
+    /// Marks the squares the last move of the game went from and to.
+    pub last_move: bool,
+}
+
+impl Default for Diagram {
+    /// 45 pixel squares, with coordinates and White at the bottom.
+    fn default() -> Diagram {
+        Diagram {
+            square_size: 45,
+            coordinates: true,
+            flipped: false,
+            highlights: Vec::new(),
+            arrows: Vec::new(),
+            last_move: false,
+        }
+    }
+}
+
+/// A square colored in, e.g. "e4" in "yellow". The color is anything SVG understands, like
+/// "red" or "#ff0000".
+#[derive(Clone, Debug, PartialEq)]
+pub struct Highlight {
+    pub square: String,
+    pub color: String,
+}
+
+/// An arrow from one square to another, or a circle if both are the same square.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Arrow {
+    pub from: String,
+    pub to: String,
+    pub color: String,
+}
+
+impl Arrow {
+    /// Reads an arrow written as two squares, e.g. "e2→e4", "e2-e4" or "e2e4".
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::svg::Arrow;
+    /// let arrow = Arrow::new("e2→e4", "blue").unwrap();
+    /// assert_eq!((arrow.from.as_str(), arrow.to.as_str()), ("e2", "e4"));
+    /// assert!(Arrow::new("e2", "blue").is_none());
+    /// ```
+    pub fn new(text: &str, color: &str) -> Option<Arrow> {
+        let text: String = text
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_lowercase();
+        // the second square starts at the second letter
+        let split = text
+            .char_indices()
+            .skip(1)
+            .find(|(_, c)| c.is_ascii_alphabetic())?
+            .0;
+        let (from, to) = text.split_at(split);
+        let is_square = |square: &str| {
+            square.len() >= 2
+                && square.starts_with(|c: char| c.is_ascii_alphabetic())
+                && square[1..].chars().all(|c| c.is_ascii_digit())
+        };
+        if !is_square(from) || !is_square(to) {
+            return None;
+        }
+        Some(Arrow {
+            from: from.to_string(),
+            to: to.to_string(),
+            color: color.to_string(),
+        })
+    }
+}
+
+/// A part of the drawing of a piece, in a 45 by 45 box with y going down.
+pub(crate) enum Shape {
+    Polygon(&'static [(f32, f32)]),
+    Circle(f32, f32, f32),
+}
+
+// the drawings are about the shape of the usual figurines, with a base to stand on
+const PAWN: &[Shape] = &[
+    Shape::Circle(22.5, 15.0, 5.5),
+    Shape::Polygon(&[
+        (19.0, 20.0),
+        (26.0, 20.0),
+        (29.0, 30.0),
+        (33.0, 38.0),
+        (12.0, 38.0),
+        (16.0, 30.0),
+    ]),
+    Shape::Polygon(&[(10.0, 37.0), (35.0, 37.0), (35.0, 41.0), (10.0, 41.0)]),
+];
+const KNIGHT: &[Shape] = &[
+    Shape::Polygon(&[
+        (21.0, 9.0),
+        (31.0, 12.0),
+        (36.0, 22.0),
+        (35.0, 38.0),
+        (15.0, 38.0),
+        (16.0, 33.0),
+        (23.0, 27.0),
+        (21.0, 23.0),
+        (15.0, 27.0),
+        (11.0, 30.0),
+        (8.0, 27.0),
+        (9.0, 22.0),
+        (16.0, 15.0),
+        (17.0, 10.0),
+    ]),
+    Shape::Polygon(&[(12.0, 37.0), (37.0, 37.0), (37.0, 41.0), (12.0, 41.0)]),
+];
+const BISHOP: &[Shape] = &[
+    Shape::Circle(22.5, 9.0, 2.5),
+    Shape::Polygon(&[
+        (22.5, 12.0),
+        (28.0, 17.0),
+        (30.0, 24.0),
+        (27.0, 30.0),
+        (28.0, 35.0),
+        (17.0, 35.0),
+        (18.0, 30.0),
+        (15.0, 24.0),
+        (17.0, 17.0),
+    ]),
+    Shape::Polygon(&[(11.0, 35.0), (34.0, 35.0), (34.0, 40.0), (11.0, 40.0)]),
+];
+const ROOK: &[Shape] = &[
+    Shape::Polygon(&[
+        (11.0, 9.0),
+        (15.0, 9.0),
+        (15.0, 12.0),
+        (20.0, 12.0),
+        (20.0, 9.0),
+        (25.0, 9.0),
+        (25.0, 12.0),
+        (30.0, 12.0),
+        (30.0, 9.0),
+        (34.0, 9.0),
+        (34.0, 15.0),
+        (30.0, 18.0),
+        (30.0, 32.0),
+        (33.0, 36.0),
+        (12.0, 36.0),
+        (15.0, 32.0),
+        (15.0, 18.0),
+        (11.0, 15.0),
+    ]),
+    Shape::Polygon(&[(9.0, 36.0), (36.0, 36.0), (36.0, 40.0), (9.0, 40.0)]),
+];
+const QUEEN: &[Shape] = &[
+    Shape::Circle(8.0, 12.0, 2.5),
+    Shape::Circle(15.5, 9.5, 2.5),
+    Shape::Circle(22.5, 8.5, 2.5),
+    Shape::Circle(29.5, 9.5, 2.5),
+    Shape::Circle(37.0, 12.0, 2.5),
+    Shape::Polygon(&[
+        (8.0, 14.0),
+        (14.0, 26.0),
+        (15.5, 12.0),
+        (20.0, 25.0),
+        (22.5, 11.0),
+        (25.0, 25.0),
+        (29.5, 12.0),
+        (31.0, 26.0),
+        (37.0, 14.0),
+        (33.0, 35.0),
+        (12.0, 35.0),
+    ]),
+    Shape::Polygon(&[(11.0, 35.0), (34.0, 35.0), (34.0, 40.0), (11.0, 40.0)]),
+];
+const KING: &[Shape] = &[
+    Shape::Polygon(&[
+        (21.0, 4.0),
+        (24.0, 4.0),
+        (24.0, 7.0),
+        (27.0, 7.0),
+        (27.0, 10.0),
+        (24.0, 10.0),
+        (24.0, 15.0),
+        (21.0, 15.0),
+        (21.0, 10.0),
+        (18.0, 10.0),
+        (18.0, 7.0),
+        (21.0, 7.0),
+    ]),
+    Shape::Polygon(&[
+        (9.0, 22.0),
+        (15.0, 16.0),
+        (22.5, 19.0),
+        (30.0, 16.0),
+        (36.0, 22.0),
+        (32.0, 35.0),
+        (13.0, 35.0),
+    ]),
+    Shape::Polygon(&[(11.0, 35.0), (34.0, 35.0), (34.0, 40.0), (11.0, 40.0)]),
+];
+// pieces without a drawing of their own are a disc with their letter on it
+const DISC: &[Shape] = &[Shape::Circle(22.5, 22.5, 15.0)];
+
+pub(crate) const LIGHT: &str = "#f0d9b5";
+pub(crate) const DARK: &str = "#b58863";
+pub(crate) const LAST_MOVE: &str = "#9bc700";
+pub(crate) const DUCK: &str = "#f5c518";
+
+// the drawing of a piece, and the letter written on it for the pieces drawn as discs
+pub(crate) fn piece_shapes(piece: PieceType) -> (&'static [Shape], Option<char>) {
+    match piece {
+        PieceType::Pawn => (PAWN, None),
+        PieceType::Knight => (KNIGHT, None),
+        PieceType::Bishop => (BISHOP, None),
+        PieceType::Rook => (ROOK, None),
+        PieceType::Queen => (QUEEN, None),
+        PieceType::King => (KING, None),
+        PieceType::Duck => (DISC, None),
+        piece => (
+            DISC,
+            Some(crate::fen::piece_to_char(Piece {
+                color: Color::White,
+                piece,
+                untouched: false,
+            })),
+        ),
+    }
+}
+
+// the outline of an arrow from one point to another, with its head at the second point
+pub(crate) fn arrow_polygon(from: (f32, f32), to: (f32, f32), size: f32) -> Vec<(f32, f32)> {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length = (dx * dx + dy * dy).sqrt();
+    let (dx, dy) = (dx / length, dy / length);
+    let (nx, ny) = (-dy, dx);
+    // the tip stops a little before the middle of the square
+    let tip = (to.0 - dx * size * 0.1, to.1 - dy * size * 0.1);
+    let head = (tip.0 - dx * size * 0.4, tip.1 - dy * size * 0.4);
+    let (shaft, wings) = (size * 0.08, size * 0.22);
+    let side = |point: (f32, f32), width: f32| (point.0 + nx * width, point.1 + ny * width);
+    vec![
+        side(from, shaft),
+        side(head, shaft),
+        side(head, wings),
+        tip,
+        side(head, -wings),
+        side(head, -shaft),
+        side(from, -shaft),
+    ]
+}
+
+impl<const WIDTH: usize, const HEIGHT: usize> Game<WIDTH, HEIGHT> {
+    /// Draws the position as an SVG image.
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::svg::Diagram;
+    /// use vprytz_chess::Game;
+    /// let svg = Game::new().to_svg(&Diagram::default());
+    /// assert!(svg.contains("viewBox=\"0 0 382.5 382.5\""));
+    /// ```
+    pub fn to_svg(&self, diagram: &Diagram) -> String {
+        let size = diagram.square_size as f32;
+        let margin = if diagram.coordinates { size / 2.0 } else { 0.0 };
+        let width = margin + size * WIDTH as f32;
+        let height = margin + size * HEIGHT as f32;
+        // the top left corner of a square
+        let corner = |(row, col): (usize, usize)| {
+            let (x, y) = match diagram.flipped {
+                false => (col, row),
+                true => (WIDTH - 1 - col, HEIGHT - 1 - row),
+            };
+            (margin + x as f32 * size, y as f32 * size)
+        };
+        let center = |square: (usize, usize)| {
+            let (x, y) = corner(square);
+            (x + size / 2.0, y + size / 2.0)
+        };
+        let square = |name: &str| parse_square(name, WIDTH, HEIGHT);
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n",
+            w = number(width),
+            h = number(height)
+        );
+
+        for row in 0..HEIGHT {
+            for col in 0..WIDTH {
+                let (x, y) = corner((row, col));
+                let color = if (row + col) % 2 == 0 { LIGHT } else { DARK };
+                svg.push_str(&rect(x, y, size, color, None));
+            }
+        }
+
+        if diagram.last_move {
+            if let Some((mv, _)) = self.history.last() {
+                let squares = match mv.drop {
+                    Some(_) => vec![mv.to],
+                    None => vec![mv.from, mv.to],
+                };
+                for square in squares {
+                    let (x, y) = corner(square);
+                    svg.push_str(&rect(x, y, size, LAST_MOVE, Some(0.41)));
+                }
+            }
+        }
+        for highlight in &diagram.highlights {
+            if let Some(square) = square(&highlight.square) {
+                let (x, y) = corner(square);
+                svg.push_str(&rect(x, y, size, &highlight.color, Some(0.5)));
+            }
+        }
+
+        if diagram.coordinates {
+            let font = size * 0.3;
+            for row in 0..HEIGHT {
+                let (_, y) = center((row, 0));
+                svg.push_str(&text(
+                    margin / 2.0,
+                    y + font / 3.0,
+                    font,
+                    &(HEIGHT - row).to_string(),
+                ));
+            }
+            for col in 0..WIDTH {
+                let (x, _) = center((0, col));
+                let file = ((b'a' + col as u8) as char).to_string();
+                svg.push_str(&text(x, height - margin / 2.0 + font / 3.0, font, &file));
+            }
+        }
+
+        for row in 0..HEIGHT {
+            for col in 0..WIDTH {
+                if let Some(piece) = self.board[row][col] {
+                    let (x, y) = corner((row, col));
+                    svg.push_str(&piece_svg(piece, x, y, size));
+                }
+            }
+        }
+
+        for arrow in &diagram.arrows {
+            let (Some(from), Some(to)) = (square(&arrow.from), square(&arrow.to)) else {
+                continue;
+            };
+            let color = escape(&arrow.color);
+            if from == to {
+                let (x, y) = center(from);
+                svg.push_str(&format!(
+                    "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\" opacity=\"0.8\"/>\n",
+                    number(x),
+                    number(y),
+                    number(size * 0.45),
+                    color,
+                    number(size * 0.08)
+                ));
+                continue;
+            }
+            svg.push_str(&format!(
+                "<polygon points=\"{}\" fill=\"{}\" opacity=\"0.8\"/>\n",
+                points(&arrow_polygon(center(from), center(to), size)),
+                color
+            ));
+        }
+
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+fn rect(x: f32, y: f32, size: f32, color: &str, opacity: Option<f32>) -> String {
+    let opacity = opacity.map_or(String::new(), |opacity| format!(" opacity=\"{}\"", opacity));
+    format!(
+        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"{}/>\n",
+        number(x),
+        number(y),
+        number(size),
+        number(size),
+        escape(color),
+        opacity
+    )
+}
+
+fn text(x: f32, y: f32, size: f32, text: &str) -> String {
+    format!(
+        "<text x=\"{}\" y=\"{}\" font-size=\"{}\" font-family=\"sans-serif\" text-anchor=\"middle\" fill=\"#555555\">{}</text>\n",
+        number(x),
+        number(y),
+        number(size),
+        text
+    )
+}
+
+fn points(points: &[(f32, f32)]) -> String {
+    let points: Vec<String> = points
+        .iter()
+        .map(|(x, y)| format!("{},{}", number(*x), number(*y)))
+        .collect();
+    points.join(" ")
+}
+
+// rounded to two decimals, which is plenty for pixels
+fn number(value: f32) -> String {
+    let text = format!("{:.2}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    match text {
+        "-0" => "0".to_string(),
+        text => text.to_string(),
+    }
+}
+
+// a piece drawn in the square with its top left corner at (x, y)
+fn piece_svg(piece: Piece, x: f32, y: f32, size: f32) -> String {
+    let (fill, stroke, letter) = match (piece.piece, piece.color) {
+        (PieceType::Duck, _) => (DUCK, "#000000", "#000000"),
+        (_, Color::White) => ("#ffffff", "#000000", "#000000"),
+        (_, Color::Black) => ("#000000", "#000000", "#ffffff"),
+    };
+    let (shapes, text) = piece_shapes(piece.piece);
+    let mut svg = format!(
+        "<g transform=\"translate({} {}) scale({})\" fill=\"{}\" stroke=\"{}\" stroke-width=\"1.5\" stroke-linejoin=\"round\">",
+        number(x),
+        number(y),
+        number(size / 45.0),
+        fill,
+        stroke
+    );
+    for shape in shapes {
+        svg.push_str(&match shape {
+            Shape::Polygon(corners) => format!("<polygon points=\"{}\"/>", points(corners)),
+            Shape::Circle(cx, cy, r) => {
+                format!("<circle cx=\"{}\" cy=\"{}\" r=\"{}\"/>", cx, cy, r)
+            }
+        });
+    }
+    if let Some(text) = text {
+        svg.push_str(&format!(
+            "<text x=\"22.5\" y=\"29\" font-size=\"20\" font-family=\"sans-serif\" font-weight=\"bold\" text-anchor=\"middle\" fill=\"{}\" stroke=\"none\">{}</text>",
+            letter, text
+        ));
+    }
+    svg.push_str("</g>\n");
+    svg
+}
+
+// makes text safe to put in an attribute
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Arrow, Diagram, Highlight};
+    use crate::Game;
+
+    #[test]
+    fn pieces_and_coordinates() {
+        let svg = Game::new().to_svg(&Diagram::default());
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<g ").count(), 32);
+        assert_eq!(svg.matches("<rect ").count(), 64);
+        // no files or fonts are needed other than a sans-serif one for the coordinates
+        assert!(!svg.contains("href"));
+        // the a1 rook in the bottom left corner, next to the coordinates
+        assert!(svg.contains("<g transform=\"translate(22.5 315) scale(1)\" fill=\"#ffffff\""));
+        assert!(svg.contains(">8</text>") && svg.contains(">h</text>"));
+
+        let diagram = Diagram {
+            square_size: 90,
+            coordinates: false,
+            flipped: true,
+            ..Diagram::default()
+        };
+        let svg = Game::new().to_svg(&diagram);
+        assert!(svg.contains("viewBox=\"0 0 720 720\""));
+        assert!(!svg.contains("<text"));
+        // now the a1 rook is in the top right corner
+        assert!(svg.contains("<g transform=\"translate(630 0) scale(2)\" fill=\"#ffffff\""));
+    }
+
+    #[test]
+    fn highlights_and_arrows() {
+        let mut game = Game::new();
+        game.make_move("E2".to_string(), "E4".to_string());
+        let diagram = Diagram {
+            coordinates: false,
+            highlights: vec![Highlight {
+                square: "a8".to_string(),
+                color: "\"red\"".to_string(),
+            }],
+            arrows: vec![
+                Arrow::new("e7-e5", "blue").unwrap(),
+                Arrow::new("d4d4", "green").unwrap(),
+                Arrow::new("z9z1", "green").unwrap(),
+            ],
+            last_move: true,
+            ..Diagram::default()
+        };
+        let svg = game.to_svg(&diagram);
+        assert!(svg.contains("<rect x=\"0\" y=\"0\" width=\"45\" height=\"45\" fill=\"&quot;red&quot;\" opacity=\"0.5\"/>"));
+        // e2 and e4
+        assert!(svg.contains("<rect x=\"180\" y=\"270\" width=\"45\" height=\"45\" fill=\"#9bc700\" opacity=\"0.41\"/>"));
+        assert!(svg.contains("<rect x=\"180\" y=\"180\" width=\"45\" height=\"45\" fill=\"#9bc700\" opacity=\"0.41\"/>"));
+        // the arrow goes straight down from e7, the circle is on d4, and z9 isn't on the board
+        assert!(svg.contains("<polygon points=\"198.9,67.5 198.9,135 192.6,135 202.5,153 212.4,135 206.1,135 206.1,67.5\" fill=\"blue\""));
+        assert!(svg.contains(
+            "<circle cx=\"157.5\" cy=\"202.5\" r=\"20.25\" fill=\"none\" stroke=\"green\""
+        ));
+        assert_eq!(svg.matches("stroke=\"green\"").count(), 1);
+    }
+
+    #[test]
+    fn fairy_pieces() {
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/A3K2c w - - 0 1").unwrap();
+        let svg = game.to_svg(&Diagram::default());
+        assert!(svg.contains(">A</text>") && svg.contains(">C</text>"));
+    }
+}
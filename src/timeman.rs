@@ -0,0 +1,245 @@
+//! Deciding how long to think when playing with a clock.
+//!
+//! The time manager works with two limits. The soft limit is the time we would like to
+//! spend: no new iteration of the search is started after it. The hard limit is the most we
+//! are ever willing to spend, and stops the search in the middle of an iteration.
+//!
+//! The soft limit is stretched (up to the hard limit) while the search keeps changing its
+//! mind about the best move, or when the score drops, since both mean that the position is
+//! harder than it looked. With a single legal move there is nothing to think about and the
+//! search stops after the first iteration.
+//!
+//! # Examples
+//! ```
+//! use std::time::Duration;
+//! use vprytz_chess::search::{search, Limits};
+//! use vprytz_chess::timeman::TimeManager;
+//! use vprytz_chess::Game;
+//!
+//! let game = Game::new();
+//! let manager = TimeManager::new(&game, Duration::from_secs(3), Duration::ZERO, None);
+//! assert!(manager.soft_limit() < manager.hard_limit());
+//!
+//! let result = search(&game, &Limits { manager: Some(manager), ..Limits::default() });
+//! assert!(result.best_move.is_some());
+//! ```
+
+use std::time::Duration;
+
+use crate::clock::Clock;
+use crate::eval::{phase, MAX_PHASE};
+use crate::search::SearchResult;
+use crate::{Game, Move};
+
+/// Kept back from the clock for the time it takes to send the move.
+pub const MOVE_OVERHEAD: Duration = Duration::from_millis(50);
+
+// moves we expect to still have to play when the time control doesn't say,
+// more with all pieces on the board than in the endgame
+const MIN_MOVES_LEFT: u32 = 20;
+const MAX_MOVES_LEFT: u32 = 40;
+
+// the hard limit is this many times the soft limit, and at most this part of the time left
+const HARD_LIMIT_FACTOR: u32 = 4;
+const HARD_LIMIT_SHARE: f64 = 0.5;
+
+// a score dropping by more than this (in centipawns) since the last iteration is a bad sign
+const SCORE_DROP: i32 = 30;
+
+/// Soft and hard time limits for one move, see the module documentation.
+#[derive(Clone, Debug)]
+pub struct TimeManager {
+    soft: Duration,
+    hard: Duration,
+    single_move: bool,
+    // how often the best move changed lately, decaying with every iteration
+    instability: f64,
+    last_best: Option<Move>,
+    last_score: Option<i32>,
+    score_dropped: bool,
+}
+
+impl TimeManager {
+    /// Computes the limits for the player to move, given the time they have left, their
+    /// increment and the number of moves until the next time control (None if the rest of
+    /// the game has to be played in this time).
+    pub fn new(
+        game: &Game,
+        remaining: Duration,
+        increment: Duration,
+        moves_to_go: Option<u32>,
+    ) -> TimeManager {
+        let available = remaining.saturating_sub(MOVE_OVERHEAD);
+        let moves = match moves_to_go {
+            Some(moves) => moves.max(1),
+            None => {
+                MIN_MOVES_LEFT
+                    + (MAX_MOVES_LEFT - MIN_MOVES_LEFT) * phase(game) as u32 / MAX_PHASE as u32
+            }
+        };
+
+        // the increment comes back after the move, so most of it can be used, but neither
+        // limit goes past the share of the time left
+        let most = available.mul_f64(HARD_LIMIT_SHARE);
+        let soft = (available / moves + increment * 3 / 4).min(most);
+        let hard = (soft * HARD_LIMIT_FACTOR).min(most);
+
+        TimeManager {
+            soft,
+            hard,
+            single_move: game.get_legal_moves().len() == 1,
+            instability: 0.0,
+            last_best: None,
+            last_score: None,
+            score_dropped: false,
+        }
+    }
+
+    /// Computes the limits for the player to move from the clock of the game.
+    pub fn from_clock(game: &Game, clock: &Clock) -> TimeManager {
+        let color = game.get_active_color();
+        TimeManager::new(
+            game,
+            clock.remaining(color),
+            clock.increment(color),
+            clock.moves_to_go(color),
+        )
+    }
+
+    /// The time we would like to spend, before any extensions.
+    pub fn soft_limit(&self) -> Duration {
+        self.soft
+    }
+
+    /// The time the search must never go past.
+    pub fn hard_limit(&self) -> Duration {
+        self.hard
+    }
+
+    /// The soft limit stretched by how unsure the search currently is, at most the hard limit.
+    pub fn extended_limit(&self) -> Duration {
+        let mut factor = 1.0 + self.instability * 0.5;
+        if self.score_dropped {
+            factor *= 1.5;
+        }
+        self.soft.mul_f64(factor).min(self.hard)
+    }
+
+    /// Called after each finished iteration of the search, returns true if there is time for another one.
+    pub fn should_continue(&mut self, result: &SearchResult, elapsed: Duration) -> bool {
+        if self.single_move {
+            return false;
+        }
+
+        self.instability *= 0.5;
+        if self.last_best.is_some() && self.last_best != result.best_move {
+            self.instability += 1.0;
+        }
+        self.score_dropped =
+            matches!(self.last_score, Some(last) if last - result.score > SCORE_DROP);
+        self.last_best = result.best_move;
+        self.last_score = Some(result.score);
+
+        elapsed < self.extended_limit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::{search, Limits};
+
+    fn result(best: &str, score: i32) -> SearchResult {
+        let game = Game::new();
+        SearchResult {
+            best_move: game.parse_uci_move(best),
+            score,
+            pv: Vec::new(),
+            depth: 1,
+            nodes: 0,
+            lines: Vec::new(),
+        }
+    }
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn limits() {
+        let opening = Game::new();
+        let endgame = Game::from_fen("4k3/pppp4/8/8/8/8/PPPP4/4K3 w - - 0 1").unwrap();
+
+        // 40 moves left with all pieces on the board, 20 in a pawn ending
+        let manager = TimeManager::new(&opening, ms(4050), ms(0), None);
+        assert_eq!(manager.soft_limit(), ms(100));
+        assert_eq!(manager.hard_limit(), ms(400));
+        assert_eq!(
+            TimeManager::new(&endgame, ms(4050), ms(0), None).soft_limit(),
+            ms(200)
+        );
+
+        // the increment, and never more than half of the time left for the hard limit
+        let manager = TimeManager::new(&opening, ms(4050), ms(200), Some(4));
+        assert_eq!(manager.soft_limit(), ms(1000 + 150));
+        assert_eq!(manager.hard_limit(), ms(2000));
+        let manager = TimeManager::new(&opening, ms(4050), ms(1000), Some(2));
+        assert_eq!(manager.soft_limit(), ms(2000));
+        assert_eq!(manager.hard_limit(), ms(2000));
+        // little time left but a large increment, which only comes after the move
+        let manager = TimeManager::new(&opening, ms(1000), ms(2000), None);
+        assert_eq!(manager.soft_limit(), ms(475));
+        assert_eq!(manager.hard_limit(), ms(475));
+        assert_eq!(
+            TimeManager::new(&opening, ms(30), ms(0), None).hard_limit(),
+            ms(0)
+        );
+    }
+
+    #[test]
+    fn extends_when_unsure() {
+        let game = Game::new();
+        let mut manager = TimeManager::new(&game, ms(4050), ms(0), None);
+
+        assert!(manager.should_continue(&result("e2e4", 20), ms(90)));
+        assert!(!manager.should_continue(&result("e2e4", 20), ms(110)));
+
+        // the best move changes
+        let mut manager = TimeManager::new(&game, ms(4050), ms(0), None);
+        manager.should_continue(&result("e2e4", 20), ms(10));
+        assert!(manager.should_continue(&result("d2d4", 20), ms(140)));
+        assert_eq!(manager.extended_limit(), ms(150));
+
+        // the score drops
+        let mut manager = TimeManager::new(&game, ms(4050), ms(0), None);
+        manager.should_continue(&result("e2e4", 20), ms(10));
+        assert!(manager.should_continue(&result("e2e4", -50), ms(140)));
+        assert!(!manager.should_continue(&result("e2e4", -50), ms(140)));
+    }
+
+    #[test]
+    fn single_legal_move() {
+        // the king is in check and has only one square to go to
+        let game = Game::from_fen("k7/8/1K6/8/8/8/8/7Q b - - 0 1").unwrap();
+        assert_eq!(game.get_legal_moves().len(), 1);
+
+        let manager = TimeManager::new(&game, Duration::from_secs(600), ms(0), None);
+        let result = search(
+            &game,
+            &Limits {
+                manager: Some(manager),
+                ..Limits::default()
+            },
+        );
+        assert_eq!(result.depth, 1);
+        assert_eq!(
+            result.best_move,
+            Some(Move {
+                from: (0, 0),
+                to: (0, 1),
+                promotion: None,
+                drop: None,
+            })
+        );
+    }
+}
@@ -0,0 +1,425 @@
+//! Transposition table, a cache of already searched positions.
+//!
+//! The same position can often be reached through different move orders, and without a
+//! cache the search would search it again every time. The table is a fixed size array
+//! indexed by the Zobrist hash of the position (see `Game::get_zobrist_hash()`), storing
+//! the depth, score, bound type and best move found for each position.
+//!
+//! Entries are packed into two 64 bit atomics and the key is stored xored with the data,
+//! so that several threads can share one table without locking. A torn write (one thread
+//! writing while another reads) simply looks like a missing entry.
+//!
+//! # Examples
+//! ```
+//! use vprytz_chess::tt::{Bound, TranspositionTable};
+//! use vprytz_chess::Game;
+//!
+//! let game = Game::new();
+//! let table = TranspositionTable::new(1);
+//! table.store(game.get_zobrist_hash(), 4, Bound::Exact, 25, None, 0);
+//! assert_eq!(table.probe(game.get_zobrist_hash(), 0).unwrap().score, 25);
+//! ```
+
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+use crate::search::{MATE, MAX_PLY};
+use crate::{Move, PieceType, BOARD_SIZE};
+
+// scores further from zero than this are mate scores (same as in the search)
+const MATE_BOUND: i32 = MATE - MAX_PLY as i32;
+
+// each bucket holds this many entries, to choose between when replacing
+const BUCKET_SIZE: usize = 2;
+
+// generations are stored in 6 bits
+const GENERATION_MASK: u8 = 0x3f;
+
+/// What the stored score means, since alpha-beta often only proves a bound on the real score.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Bound {
+    /// The score is exact.
+    Exact,
+    /// The real score is at least this high (the search failed high).
+    Lower,
+    /// The real score is at most this high (the search failed low).
+    Upper,
+}
+
+/// A position stored in the table.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub best_move: Option<Move>,
+    pub score: i32,
+    pub depth: u32,
+    pub bound: Bound,
+}
+
+// one entry, the key is stored xored with the data to detect torn writes
+#[derive(Default)]
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+/// A fixed size transposition table that can be shared between threads.
+pub struct TranspositionTable {
+    slots: Vec<Slot>,
+    generation: AtomicU8,
+}
+
+impl TranspositionTable {
+    /// Creates a table using about the given number of megabytes (at least one bucket is always allocated).
+    pub fn new(megabytes: usize) -> TranspositionTable {
+        let mut table = TranspositionTable {
+            slots: Vec::new(),
+            generation: AtomicU8::new(0),
+        };
+        table.resize(megabytes);
+        table
+    }
+
+    /// Changes the size of the table, clearing it.
+    pub fn resize(&mut self, megabytes: usize) {
+        let buckets =
+            (megabytes * 1024 * 1024 / (std::mem::size_of::<Slot>() * BUCKET_SIZE)).max(1);
+        self.slots = (0..buckets * BUCKET_SIZE)
+            .map(|_| Slot::default())
+            .collect();
+    }
+
+    /// Removes every entry from the table.
+    pub fn clear(&self) {
+        for slot in self.slots.iter() {
+            slot.key.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Marks the start of a new search, so that entries from older searches get replaced first.
+    pub fn new_search(&self) {
+        let generation = self.generation.load(Ordering::Relaxed);
+        self.generation.store(
+            generation.wrapping_add(1) & GENERATION_MASK,
+            Ordering::Relaxed,
+        );
+    }
+
+    /// Looks up a position, returning the stored entry if there is one.
+    /// `ply` is the distance from the root of the search, used to turn stored mate scores
+    /// back into "mate in n from the root".
+    pub fn probe(&self, hash: u64, ply: usize) -> Option<Entry> {
+        for slot in self.bucket(hash) {
+            let data = slot.data.load(Ordering::Relaxed);
+            if data != 0 && slot.key.load(Ordering::Relaxed) ^ data == hash {
+                let mut entry = unpack(data);
+                entry.score = score_from_table(entry.score, ply);
+                return Some(entry);
+            }
+        }
+        None
+    }
+
+    /// Stores a position. An entry for the same position is always replaced, otherwise
+    /// entries from older searches are replaced first and then the least deep one.
+    pub fn store(
+        &self,
+        hash: u64,
+        depth: u32,
+        bound: Bound,
+        score: i32,
+        best_move: Option<Move>,
+        ply: usize,
+    ) {
+        let generation = self.generation.load(Ordering::Relaxed);
+        let bucket = self.bucket(hash);
+
+        // pick the slot to write to, lower worth is replaced first
+        let mut target = &bucket[0];
+        let mut target_worth = i32::MAX;
+        for slot in bucket {
+            let data = slot.data.load(Ordering::Relaxed);
+            if data == 0 || slot.key.load(Ordering::Relaxed) ^ data == hash {
+                target = slot;
+                break;
+            }
+            let old = unpack(data);
+            let mut worth = old.depth as i32;
+            if data_generation(data) == generation {
+                worth += 256;
+            }
+            if worth < target_worth {
+                target = slot;
+                target_worth = worth;
+            }
+        }
+
+        // keep the old best move if we don't have one, it is still the best guess
+        let old_data = target.data.load(Ordering::Relaxed);
+        let best_move = match best_move {
+            Some(mv) => Some(mv),
+            None if target.key.load(Ordering::Relaxed) ^ old_data == hash && old_data != 0 => {
+                unpack(old_data).best_move
+            }
+            None => None,
+        };
+
+        let data = pack(
+            Entry {
+                best_move,
+                score: score_to_table(score, ply),
+                depth,
+                bound,
+            },
+            generation,
+        );
+        target.key.store(hash ^ data, Ordering::Relaxed);
+        target.data.store(data, Ordering::Relaxed);
+    }
+
+    /// How full the table is in permille, counting only entries from the current search
+    /// (in the first thousand slots, like UCI engines report it).
+    pub fn hashfull(&self) -> usize {
+        let generation = self.generation.load(Ordering::Relaxed);
+        let sample = self.slots.len().min(1000);
+        let used = self.slots[..sample]
+            .iter()
+            .filter(|slot| {
+                let data = slot.data.load(Ordering::Relaxed);
+                data != 0 && data_generation(data) == generation
+            })
+            .count();
+        used * 1000 / sample
+    }
+
+    fn bucket(&self, hash: u64) -> &[Slot] {
+        let buckets = self.slots.len() / BUCKET_SIZE;
+        let index = (hash % buckets as u64) as usize * BUCKET_SIZE;
+        &self.slots[index..index + BUCKET_SIZE]
+    }
+}
+
+// mate scores are stored as distance from the stored position instead of from the root,
+// so that they are still right when the position is found at another ply
+fn score_to_table(score: i32, ply: usize) -> i32 {
+    if score > MATE_BOUND {
+        score + ply as i32
+    } else if score < -MATE_BOUND {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_table(score: i32, ply: usize) -> i32 {
+    if score > MATE_BOUND {
+        score - ply as i32
+    } else if score < -MATE_BOUND {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+// pieces a move can promote to or drop, stored as their place in the list plus one, custom
+// fairy pieces are stored as CUSTOM_CODE plus their letter (a to z as 1 to 26)
+const MOVE_PIECES: [PieceType; 12] = [
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+    PieceType::Pawn,
+    PieceType::King,
+    PieceType::Archbishop,
+    PieceType::Chancellor,
+    PieceType::Amazon,
+    PieceType::Camel,
+    PieceType::Grasshopper,
+    PieceType::Duck,
+];
+const CUSTOM_CODE: u64 = 16;
+
+fn piece_code(piece: PieceType) -> u64 {
+    match piece {
+        PieceType::Custom(letter) => CUSTOM_CODE + (letter as u64 & 0x1f),
+        piece => {
+            let index = MOVE_PIECES.iter().position(|other| *other == piece);
+            index.expect("all other pieces are in the list") as u64 + 1
+        }
+    }
+}
+
+fn code_piece(code: u64) -> Option<PieceType> {
+    match code {
+        0 => None,
+        code if code > CUSTOM_CODE => Some(PieceType::Custom(
+            (0x60 | (code - CUSTOM_CODE)) as u8 as char,
+        )),
+        code => MOVE_PIECES.get(code as usize - 1).copied(),
+    }
+}
+
+// layout of the data word:
+// bits 0-7 from square, 8-15 to square, 20 has move, 21-22 bound, 23-30 depth, 31 drop,
+// 32-47 score, 48-53 generation, 54-59 promotion or dropped piece (see MOVE_PIECES),
+// 63 always set (so data is never 0)
+fn pack(entry: Entry, generation: u8) -> u64 {
+    let mut data: u64 = 1 << 63;
+
+    if let Some(mv) = entry.best_move {
+        let from = (mv.from.0 * BOARD_SIZE + mv.from.1) as u64;
+        let to = (mv.to.0 * BOARD_SIZE + mv.to.1) as u64;
+        let piece = mv.promotion.or(mv.drop).map_or(0, piece_code);
+        let drop = mv.drop.is_some() as u64;
+        data |= from | (to << 8) | (1 << 20) | (drop << 31) | (piece << 54);
+    }
+
+    let bound = match entry.bound {
+        Bound::Exact => 0,
+        Bound::Lower => 1,
+        Bound::Upper => 2,
+    };
+    data |= bound << 21;
+    data |= (entry.depth.min(255) as u64) << 23;
+    data |= (entry.score as i16 as u16 as u64) << 32;
+    data |= ((generation & GENERATION_MASK) as u64) << 48;
+
+    data
+}
+
+fn unpack(data: u64) -> Entry {
+    let best_move = if data & (1 << 20) != 0 {
+        let from = (data & 0xff) as usize;
+        let to = ((data >> 8) & 0xff) as usize;
+        let piece = code_piece((data >> 54) & 0x3f);
+        let (promotion, drop) = if data & (1 << 31) != 0 {
+            (None, piece)
+        } else {
+            (piece, None)
+        };
+        Some(Move {
+            from: (from / BOARD_SIZE, from % BOARD_SIZE),
+            to: (to / BOARD_SIZE, to % BOARD_SIZE),
+            promotion,
+            drop,
+        })
+    } else {
+        None
+    };
+
+    let bound = match (data >> 21) & 0x3 {
+        0 => Bound::Exact,
+        1 => Bound::Lower,
+        _ => Bound::Upper,
+    };
+
+    Entry {
+        best_move,
+        score: ((data >> 32) & 0xffff) as u16 as i16 as i32,
+        depth: ((data >> 23) & 0xff) as u32,
+        bound,
+    }
+}
+
+fn data_generation(data: u64) -> u8 {
+    ((data >> 48) as u8) & GENERATION_MASK
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn store_and_probe() {
+        let table = TranspositionTable::new(1);
+        let mv = Move {
+            from: (1, 4),
+            to: (0, 4),
+            promotion: Some(PieceType::Knight),
+            drop: None,
+        };
+
+        table.store(12345, 7, Bound::Lower, -150, Some(mv), 3);
+
+        assert_eq!(
+            table.probe(12345, 3),
+            Some(Entry {
+                best_move: Some(mv),
+                score: -150,
+                depth: 7,
+                bound: Bound::Lower,
+            })
+        );
+        assert_eq!(table.probe(54321, 3), None);
+
+        // drops are kept apart from promotions
+        let drop = Move {
+            from: (5, 5),
+            to: (5, 5),
+            promotion: None,
+            drop: Some(PieceType::Pawn),
+        };
+        table.store(777, 1, Bound::Exact, 0, Some(drop), 0);
+        assert_eq!(table.probe(777, 0).unwrap().best_move, Some(drop));
+
+        let fairy = Move {
+            promotion: Some(PieceType::Chancellor),
+            drop: None,
+            ..drop
+        };
+        table.store(778, 1, Bound::Exact, 0, Some(fairy), 0);
+        assert_eq!(table.probe(778, 0).unwrap().best_move, Some(fairy));
+        // custom fairy pieces are stored by their letter
+        for letter in ['d', 'z'] {
+            let custom = Move {
+                promotion: Some(PieceType::Custom(letter)),
+                ..fairy
+            };
+            table.store(779, 1, Bound::Exact, 0, Some(custom), 0);
+            assert_eq!(table.probe(779, 0).unwrap().best_move, Some(custom));
+        }
+
+        table.clear();
+        assert_eq!(table.probe(12345, 3), None);
+    }
+
+    #[test]
+    fn mate_scores_are_relative_to_ply() {
+        let table = TranspositionTable::new(1);
+
+        // mate in 5 plies from the root, found 2 plies into the search
+        table.store(1, 3, Bound::Exact, MATE - 5, None, 2);
+
+        // found again 4 plies into the search, the mate is now 7 plies away from the root
+        assert_eq!(table.probe(1, 4).unwrap().score, MATE - 7);
+    }
+
+    #[test]
+    fn keeps_deep_entries_of_the_current_search() {
+        // a table with a single bucket, so that every position fights for the same slots
+        let table = TranspositionTable::new(0);
+
+        table.store(1, 10, Bound::Exact, 0, None, 0);
+        table.store(2, 1, Bound::Exact, 0, None, 0);
+        table.store(3, 2, Bound::Exact, 0, None, 0);
+
+        // the shallow entry is replaced, the deep one is kept
+        assert!(table.probe(1, 0).is_some());
+        assert!(table.probe(2, 0).is_none());
+        assert!(table.probe(3, 0).is_some());
+
+        // in the next search even the deep entry goes before an entry of the new search
+        table.new_search();
+        table.store(4, 1, Bound::Exact, 0, None, 0);
+        table.store(5, 1, Bound::Exact, 0, None, 0);
+        assert!(table.probe(1, 0).is_none());
+        assert!(table.probe(3, 0).is_none());
+        assert!(table.probe(4, 0).is_some());
+        assert!(table.probe(5, 0).is_some());
+    }
+
+    #[test]
+    fn can_be_shared_between_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<TranspositionTable>();
+    }
+}
@@ -0,0 +1,713 @@
+//! A full-screen terminal interface for two players sharing a keyboard, used by the `tui`
+//! binary.
+//!
+//! The board is drawn as a colored checkerboard with Unicode pieces. A cursor is moved with the
+//! arrow keys (or `h`, `j`, `k`, `l`), and Enter or space picks up the piece under it, showing
+//! where it can go (see [`Game::get_possible_moves`]), and then puts it down. The last move and
+//! a king in check are highlighted, and the moves played so far (in SAN) and the clocks are
+//! shown next to the board. The other keys are:
+//!
+//! | **Key** | **Description**                                    |
+//! | ------- | -------------------------------------------------- |
+//! | `Esc`   | Puts the picked up piece back.                     |
+//! | `u`     | Takes back the last move.                          |
+//! | `f`     | Turns the board around.                            |
+//! | `p`     | Changes the piece pawns are promoted to.           |
+//! | `n`     | Starts a new game.                                 |
+//! | `q`     | Leaves.                                            |
+//!
+//! Everything is drawn into a [`Buffer`] of cells first, which is then copied to the terminal
+//! by [`run`] (which needs the `tui` feature). Tests can draw into a buffer and look at it
+//! instead.
+//!
+//! # Examples
+//! ```
+//! use vprytz_chess::tui::{App, Buffer, Key};
+//! use vprytz_chess::Game;
+//! let mut app = App::new(Game::new(), None);
+//! // the cursor starts on e2, pick up the pawn and move it two squares up
+//! for key in [Key::Select, Key::Up, Key::Up, Key::Select] {
+//!     app.key(key);
+//! }
+//! let mut buffer = Buffer::new(60, 12);
+//! app.draw(&mut buffer);
+//! assert!(buffer.text(2).ends_with("1. e4"));
+//! ```
+
+use std::time::Duration;
+
+use crate::cli::{describe, name};
+use crate::clock::{Clock, TimeControl};
+use crate::pgn::result_token;
+use crate::render::{symbol, Pieces};
+use crate::{square_name, Color, Game, Piece, PieceType, BOARD_SIZE};
+
+/// A color given as red, green and blue.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+/// One character of the screen and its colors, None being the terminal's own colors.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Cell {
+    pub symbol: char,
+    pub fg: Option<Rgb>,
+    pub bg: Option<Rgb>,
+}
+
+const BLANK: Cell = Cell {
+    symbol: ' ',
+    fg: None,
+    bg: None,
+};
+
+/// The screen, a grid of cells.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Buffer {
+    width: usize,
+    height: usize,
+    cells: Vec<Cell>,
+}
+
+impl Buffer {
+    /// Creates an empty buffer of the given size.
+    pub fn new(width: usize, height: usize) -> Buffer {
+        Buffer {
+            width,
+            height,
+            cells: vec![BLANK; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The cell in column `x` of line `y`.
+    /// # Panics
+    /// Panics if the cell is outside the buffer.
+    pub fn cell(&self, x: usize, y: usize) -> Cell {
+        assert!(x < self.width && y < self.height);
+        self.cells[y * self.width + x]
+    }
+
+    /// The characters of line `y`, without the spaces at the end.
+    pub fn text(&self, y: usize) -> String {
+        let line: String = self.cells[y * self.width..(y + 1) * self.width]
+            .iter()
+            .map(|cell| cell.symbol)
+            .collect();
+        line.trim_end().to_string()
+    }
+
+    // writes text starting at (x, y), cutting off what doesn't fit
+    fn put(&mut self, x: usize, y: usize, text: &str, fg: Option<Rgb>, bg: Option<Rgb>) {
+        if y >= self.height {
+            return;
+        }
+        for (i, symbol) in text.chars().enumerate() {
+            if x + i >= self.width {
+                break;
+            }
+            self.cells[y * self.width + x + i] = Cell { symbol, fg, bg };
+        }
+    }
+}
+
+/// The keys the interface reacts to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    /// Enter or space, picks up or puts down a piece.
+    Select,
+    /// Escape, puts the piece back.
+    Cancel,
+    Char(char),
+}
+
+const LIGHT: Rgb = Rgb(240, 217, 181);
+const DARK: Rgb = Rgb(181, 136, 99);
+const LAST_MOVE_LIGHT: Rgb = Rgb(205, 210, 106);
+const LAST_MOVE_DARK: Rgb = Rgb(170, 162, 58);
+const DESTINATION_LIGHT: Rgb = Rgb(150, 200, 130);
+const DESTINATION_DARK: Rgb = Rgb(110, 160, 90);
+const SELECTED: Rgb = Rgb(100, 140, 200);
+const CURSOR: Rgb = Rgb(70, 110, 220);
+const CHECK: Rgb = Rgb(220, 60, 60);
+const WHITE_PIECE: Rgb = Rgb(255, 255, 255);
+const BLACK_PIECE: Rgb = Rgb(0, 0, 0);
+// the running clock
+const RUNNING: Rgb = Rgb(120, 220, 120);
+
+// the left edge of the moves and clocks, and how many lines of moves are shown
+const PANE: usize = 28;
+const MOVE_LINES: usize = 5;
+
+const HELP: &str =
+    "arrows move  enter pick/put  esc cancel  u undo  f flip  p promotion  n new  q quit";
+
+const PROMOTIONS: [PieceType; 4] = [
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+];
+
+/// The state of the interface: the game, the cursor and the piece picked up.
+pub struct App {
+    game: Game,
+    time_control: Option<TimeControl>,
+    // the clock, stopped, once the game is over
+    stopped_clock: Option<Clock>,
+    cursor: (usize, usize),
+    selected: Option<(usize, usize)>,
+    flipped: bool,
+    promotion: PieceType,
+    message: String,
+    quit: bool,
+}
+
+impl App {
+    /// Starts with the given game, and a clock if a time control is given.
+    pub fn new(game: Game, time_control: Option<TimeControl>) -> App {
+        let mut app = App {
+            game: Game::new(),
+            time_control,
+            stopped_clock: None,
+            cursor: (0, 0),
+            selected: None,
+            flipped: false,
+            promotion: PieceType::Queen,
+            message: String::new(),
+            quit: false,
+        };
+        app.start(game);
+        app
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /// True once `q` has been pressed.
+    pub fn is_done(&self) -> bool {
+        self.quit
+    }
+
+    fn start(&mut self, mut game: Game) {
+        if let Some(control) = &self.time_control {
+            game.set_clock(Clock::new(control.clone()));
+        }
+        game.set_promotion(name_of(self.promotion).to_string());
+        self.cursor = match game.get_active_color() {
+            Color::White => (6, 4),
+            Color::Black => (1, 4),
+        };
+        self.game = game;
+        self.stopped_clock = None;
+        self.selected = None;
+        self.message.clear();
+    }
+
+    /// Handles a key press.
+    pub fn key(&mut self, key: Key) {
+        self.message.clear();
+        let (row, col) = self.cursor;
+        // the cursor moves the way the arrow points on the screen
+        let (up, left) = match self.flipped {
+            false => (row.saturating_sub(1), col.saturating_sub(1)),
+            true => ((row + 1).min(BOARD_SIZE - 1), (col + 1).min(BOARD_SIZE - 1)),
+        };
+        let (down, right) = match self.flipped {
+            false => ((row + 1).min(BOARD_SIZE - 1), (col + 1).min(BOARD_SIZE - 1)),
+            true => (row.saturating_sub(1), col.saturating_sub(1)),
+        };
+
+        match key {
+            Key::Up | Key::Char('k') => self.cursor = (up, col),
+            Key::Down | Key::Char('j') => self.cursor = (down, col),
+            Key::Left | Key::Char('h') => self.cursor = (row, left),
+            Key::Right | Key::Char('l') => self.cursor = (row, right),
+            Key::Select | Key::Char(' ') => self.select(),
+            Key::Cancel => self.selected = None,
+            Key::Char('u') => {
+                // the clock of a finished game goes back in, to be set back with the move
+                if let Some(clock) = self.stopped_clock.take() {
+                    self.game.set_clock(clock);
+                }
+                if self.game.undo_move().is_none() {
+                    self.message = "There are no moves to take back".to_string();
+                }
+                self.stop_clock_when_over();
+                self.selected = None;
+            }
+            Key::Char('f') => self.flipped = !self.flipped,
+            Key::Char('p') => {
+                let next = PROMOTIONS
+                    .iter()
+                    .position(|piece| *piece == self.promotion)
+                    .map_or(0, |index| (index + 1) % PROMOTIONS.len());
+                self.promotion = PROMOTIONS[next];
+                self.game.set_promotion(name_of(self.promotion).to_string());
+                self.message = format!("Pawns are promoted to a {}", name_of(self.promotion));
+            }
+            Key::Char('n') => self.start(Game::new()),
+            Key::Char('q') => self.quit = true,
+            Key::Char(_) => {}
+        }
+    }
+
+    // picks up the piece under the cursor, or puts down the one picked up
+    fn select(&mut self) {
+        if self.game.get_outcome().is_some() {
+            self.message = "The game is over, press n for a new one".to_string();
+            return;
+        }
+        let cursor = self.cursor;
+        if let Some(from) = self.selected {
+            if from == cursor {
+                self.selected = None;
+                return;
+            }
+            if self.destinations(from).contains(&cursor) {
+                self.selected = None;
+                self.play(from, cursor);
+                return;
+            }
+        }
+
+        match self.game.board[cursor.0][cursor.1] {
+            Some(piece) if self.is_movable(piece) => {
+                if self.destinations(cursor).is_empty() {
+                    self.message = "That piece can't move".to_string();
+                } else {
+                    self.selected = Some(cursor);
+                }
+            }
+            // the duck is placed on the board for the first time
+            None if self.game.is_duck_to_move() && self.selected.is_none() => {
+                self.play(cursor, cursor)
+            }
+            _ if self.selected.is_some() => self.message = "It can't go there".to_string(),
+            _ => {
+                let color = name(self.game.get_active_color());
+                self.message = format!("Pick one of {}'s pieces", color.to_lowercase());
+            }
+        }
+    }
+
+    fn is_movable(&self, piece: Piece) -> bool {
+        match self.game.is_duck_to_move() {
+            true => piece.piece == PieceType::Duck,
+            false => piece.color == self.game.get_active_color() && piece.piece != PieceType::Duck,
+        }
+    }
+
+    fn destinations(&self, from: (usize, usize)) -> Vec<(usize, usize)> {
+        self.game
+            .get_possible_moves(square_name(from, BOARD_SIZE))
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|square| crate::parse_square(square, BOARD_SIZE, BOARD_SIZE))
+            .collect()
+    }
+
+    fn play(&mut self, from: (usize, usize), to: (usize, usize)) {
+        let from = square_name(from, BOARD_SIZE);
+        let to = square_name(to, BOARD_SIZE);
+        if self.game.make_move(from, to).is_none() {
+            self.message = "That move isn't allowed".to_string();
+        }
+        self.stop_clock_when_over();
+    }
+
+    // keeps the clock out of a finished game, stopped, so it is still shown
+    fn stop_clock_when_over(&mut self) {
+        if self.game.get_outcome().is_some() {
+            if let Some(mut clock) = self.game.take_clock() {
+                clock.stop();
+                self.stopped_clock = Some(clock);
+            }
+        }
+    }
+
+    /// Draws the board, the moves, the clocks and the status line into the buffer.
+    pub fn draw(&self, buffer: &mut Buffer) {
+        *buffer = Buffer::new(buffer.width(), buffer.height());
+        self.draw_board(buffer);
+        self.draw_moves(buffer);
+        self.draw_clocks(buffer);
+
+        let status = match self.game.get_outcome() {
+            Some(outcome) => format!("{} {}", result_token(Some(outcome)), describe(outcome)),
+            None => {
+                let color = name(self.game.get_active_color());
+                if self.game.is_duck_to_move() {
+                    format!("{} to move the duck", color)
+                } else if self.game.is_check() {
+                    format!("{} to move, check!", color)
+                } else {
+                    format!("{} to move", color)
+                }
+            }
+        };
+        buffer.put(0, BOARD_SIZE + 2, &status, None, None);
+        buffer.put(PANE, BOARD_SIZE + 2, &self.message, None, None);
+        buffer.put(0, BOARD_SIZE + 3, HELP, None, None);
+    }
+
+    fn draw_board(&self, buffer: &mut Buffer) {
+        let last = self.game.history.last().map(|(mv, _)| *mv);
+        let destinations = self
+            .selected
+            .map_or(Vec::new(), |from| self.destinations(from));
+        let in_check = self.game.is_check().then(|| self.game.get_active_color());
+
+        for y in 0..BOARD_SIZE {
+            let row = if self.flipped { BOARD_SIZE - 1 - y } else { y };
+            buffer.put(0, y, &(BOARD_SIZE - row).to_string(), None, None);
+            for x in 0..BOARD_SIZE {
+                let col = if self.flipped { BOARD_SIZE - 1 - x } else { x };
+                let square = (row, col);
+                let light = (row + col) % 2 == 0;
+                let piece = self.game.board[row][col];
+
+                let mut bg = if light { LIGHT } else { DARK };
+                if last
+                    .is_some_and(|mv| mv.to == square || (mv.drop.is_none() && mv.from == square))
+                {
+                    bg = if light {
+                        LAST_MOVE_LIGHT
+                    } else {
+                        LAST_MOVE_DARK
+                    };
+                }
+                if destinations.contains(&square) {
+                    bg = if light {
+                        DESTINATION_LIGHT
+                    } else {
+                        DESTINATION_DARK
+                    };
+                }
+                if piece.is_some_and(|piece| {
+                    piece.piece == PieceType::King && Some(piece.color) == in_check
+                }) {
+                    bg = CHECK;
+                }
+                if self.selected == Some(square) {
+                    bg = SELECTED;
+                }
+                if self.cursor == square {
+                    bg = CURSOR;
+                }
+
+                let (symbol, fg) = match piece {
+                    Some(piece) => (
+                        symbol(piece, Pieces::Unicode, true),
+                        match piece.color {
+                            Color::White => WHITE_PIECE,
+                            Color::Black => BLACK_PIECE,
+                        },
+                    ),
+                    None if destinations.contains(&square) => ('•', BLACK_PIECE),
+                    None => (' ', BLACK_PIECE),
+                };
+                buffer.put(2 + x * 3, y, &format!(" {} ", symbol), Some(fg), Some(bg));
+            }
+        }
+        for x in 0..BOARD_SIZE {
+            let col = if self.flipped { BOARD_SIZE - 1 - x } else { x };
+            let file = ((b'a' + col as u8) as char).to_string();
+            buffer.put(3 + x * 3, BOARD_SIZE, &file, None, None);
+        }
+    }
+
+    // the last few lines of moves, between the clocks
+    fn draw_moves(&self, buffer: &mut Buffer) {
+        let mut game = self.game.get_starting_position();
+        let mut lines: Vec<String> = Vec::new();
+        for mv in self.game.get_move_history() {
+            let san = game.move_to_san(mv);
+            let color = game.get_active_color();
+            match lines.last_mut() {
+                // a Duck chess turn or black's move goes on the same line
+                Some(line) if color == Color::Black || game.is_duck_to_move() => {
+                    line.push(' ');
+                    line.push_str(&san);
+                }
+                _ if color == Color::Black => {
+                    lines.push(format!("{}... {}", game.fullmove_number, san))
+                }
+                _ => lines.push(format!("{}. {}", game.fullmove_number, san)),
+            }
+            game.do_move(mv);
+        }
+        let skip = lines.len().saturating_sub(MOVE_LINES);
+        for (y, line) in lines.iter().skip(skip).enumerate() {
+            buffer.put(PANE, 2 + y, line, None, None);
+        }
+    }
+
+    // the clock of the player at the top of the board on the first line, the other on the last
+    fn draw_clocks(&self, buffer: &mut Buffer) {
+        let clock = self.game.get_clock().or(self.stopped_clock.as_ref());
+        let (top, bottom) = match self.flipped {
+            false => (Color::Black, Color::White),
+            true => (Color::White, Color::Black),
+        };
+        for (y, color) in [(0, top), (BOARD_SIZE - 1, bottom)] {
+            let mut text = format!("{:<7}", name(color));
+            let mut fg = None;
+            if let Some(clock) = clock {
+                text.push_str(&clock_text(clock.remaining(color)));
+                if clock.running() == Some(color) {
+                    fg = Some(RUNNING);
+                }
+            }
+            buffer.put(PANE, y, &text, fg, None);
+        }
+    }
+}
+
+fn name_of(piece: PieceType) -> &'static str {
+    match piece {
+        PieceType::Rook => "rook",
+        PieceType::Bishop => "bishop",
+        PieceType::Knight => "knight",
+        _ => "queen",
+    }
+}
+
+// minutes and seconds, with tenths in the last 20 seconds
+fn clock_text(time: Duration) -> String {
+    let seconds = time.as_secs();
+    if time < Duration::from_secs(20) {
+        format!("0:{:02}.{}", seconds, time.subsec_millis() / 100)
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+/// Takes over the terminal and lets two players play the game, until `q` is pressed.
+#[cfg(feature = "tui")]
+pub fn run(game: Game, time_control: Option<TimeControl>) -> std::io::Result<()> {
+    use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+    use crossterm::{cursor, execute, terminal};
+
+    // puts the terminal back the way it was, also when something goes wrong
+    struct Restore;
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let _ = execute!(
+                std::io::stdout(),
+                cursor::Show,
+                terminal::LeaveAlternateScreen
+            );
+            let _ = terminal::disable_raw_mode();
+        }
+    }
+
+    terminal::enable_raw_mode()?;
+    let _restore = Restore;
+    let mut stdout = std::io::stdout();
+    execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
+
+    let mut app = App::new(game, time_control);
+    while !app.is_done() {
+        let (width, height) = terminal::size()?;
+        let mut buffer = Buffer::new(width as usize, height as usize);
+        app.draw(&mut buffer);
+        show(&mut stdout, &buffer)?;
+
+        // redrawn every now and then for the clocks
+        if !event::poll(Duration::from_millis(100))? {
+            continue;
+        }
+        let key = match event::read()? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => key,
+            _ => continue,
+        };
+        let key = match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => Key::Char('q'),
+            KeyCode::Up => Key::Up,
+            KeyCode::Down => Key::Down,
+            KeyCode::Left => Key::Left,
+            KeyCode::Right => Key::Right,
+            KeyCode::Enter => Key::Select,
+            KeyCode::Esc => Key::Cancel,
+            KeyCode::Char(c) => Key::Char(c),
+            _ => continue,
+        };
+        app.key(key);
+    }
+    Ok(())
+}
+
+// copies the buffer to the terminal
+#[cfg(feature = "tui")]
+fn show<W: std::io::Write>(output: &mut W, buffer: &Buffer) -> std::io::Result<()> {
+    use crossterm::style::{self, Print, SetBackgroundColor, SetForegroundColor};
+    use crossterm::{cursor, queue};
+
+    let color = |rgb: Option<Rgb>| match rgb {
+        Some(Rgb(r, g, b)) => style::Color::Rgb { r, g, b },
+        None => style::Color::Reset,
+    };
+    // the colors are only sent when they change
+    let mut colors = None;
+    for y in 0..buffer.height() {
+        queue!(output, cursor::MoveTo(0, y as u16))?;
+        for x in 0..buffer.width() {
+            let cell = buffer.cell(x, y);
+            if colors != Some((cell.fg, cell.bg)) {
+                colors = Some((cell.fg, cell.bg));
+                queue!(
+                    output,
+                    SetForegroundColor(color(cell.fg)),
+                    SetBackgroundColor(color(cell.bg))
+                )?;
+            }
+            queue!(output, Print(cell.symbol))?;
+        }
+    }
+    queue!(output, style::ResetColor)?;
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use crate::clock::ManualTime;
+
+    fn press(app: &mut App, keys: &[Key]) {
+        for key in keys {
+            app.key(*key);
+        }
+    }
+
+    fn drawn(app: &App) -> Buffer {
+        let mut buffer = Buffer::new(90, 12);
+        app.draw(&mut buffer);
+        buffer
+    }
+
+    #[test]
+    fn board_and_highlights() {
+        let mut app = App::new(Game::new(), None);
+        let buffer = drawn(&app);
+        assert_eq!(buffer.text(0), "8  ♜  ♞  ♝  ♛  ♚  ♝  ♞  ♜   Black");
+        assert_eq!(buffer.text(8), "   a  b  c  d  e  f  g  h");
+        assert_eq!(buffer.text(10), "White to move");
+        // pieces are told apart by their color, and the cursor is on e2
+        assert_eq!(buffer.cell(15, 0).fg, Some(BLACK_PIECE));
+        assert_eq!(buffer.cell(15, 7).fg, Some(WHITE_PIECE));
+        assert_eq!(buffer.cell(15, 6).bg, Some(CURSOR));
+        assert_eq!(buffer.cell(3, 7).bg, Some(DARK));
+
+        // the knight on g1 is picked up, its two squares are shown
+        press(&mut app, &[Key::Down, Key::Right, Key::Right, Key::Select]);
+        let buffer = drawn(&app);
+        assert_eq!(buffer.cell(21, 7).bg, Some(CURSOR));
+        assert_eq!(buffer.cell(18, 5).bg, Some(DESTINATION_LIGHT));
+        assert_eq!(buffer.cell(24, 5).bg, Some(DESTINATION_LIGHT));
+        assert_eq!(buffer.cell(24, 5).symbol, '•');
+
+        press(&mut app, &[Key::Up, Key::Up, Key::Left, Key::Select]);
+        let buffer = drawn(&app);
+        assert_eq!(buffer.text(2), format!("{:<28}1. Nf3", "6"));
+        assert_eq!(buffer.cell(21, 7).bg, Some(LAST_MOVE_DARK));
+        assert_eq!(buffer.cell(18, 5).bg, Some(CURSOR));
+        assert_eq!(buffer.text(10), "Black to move");
+
+        // the other player's pieces can't be picked up
+        press(&mut app, &[Key::Select]);
+        assert!(drawn(&app).text(10).ends_with("Pick one of black's pieces"));
+
+        // flipped, black is at the bottom
+        press(&mut app, &[Key::Char('f')]);
+        let buffer = drawn(&app);
+        assert_eq!(buffer.text(7), "8  ♜  ♞  ♝  ♚  ♛  ♝  ♞  ♜   Black");
+        assert_eq!(buffer.text(8), "   h  g  f  e  d  c  b  a");
+    }
+
+    #[test]
+    fn check_and_mate() {
+        let mut app = App::new(
+            Game::from_fen("rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2")
+                .unwrap(),
+            None,
+        );
+        // up to the queen on d8, then down to h4
+        press(&mut app, &[Key::Up, Key::Left, Key::Select]);
+        press(&mut app, &[Key::Down; 4]);
+        press(&mut app, &[Key::Right; 4]);
+        press(&mut app, &[Key::Select]);
+        let buffer = drawn(&app);
+        assert_eq!(buffer.text(2), format!("{:<28}2... Qh4#", "6"));
+        assert_eq!(buffer.cell(15, 7).bg, Some(CHECK));
+        assert_eq!(buffer.text(10), "0-1 Black wins by checkmate");
+
+        press(&mut app, &[Key::Char('u')]);
+        assert_eq!(drawn(&app).text(10), "Black to move");
+    }
+
+    #[test]
+    fn clocks() {
+        let time = Arc::new(ManualTime::default());
+        let control = TimeControl::fischer(Duration::from_secs(300), Duration::from_secs(2));
+        let mut app = App::new(Game::new(), Some(control.clone()));
+        app.game
+            .set_clock(Clock::with_time_source(control.clone(), time.clone()));
+
+        time.advance(Duration::from_secs(15));
+        press(&mut app, &[Key::Select, Key::Up, Key::Up, Key::Select]);
+        time.advance(Duration::from_millis(290_500));
+        let buffer = drawn(&app);
+        assert_eq!(
+            buffer.text(0),
+            format!("{:<28}Black  0:09.5", "8  ♜  ♞  ♝  ♛  ♚  ♝  ♞  ♜")
+        );
+        assert_eq!(buffer.cell(PANE, 0).fg, Some(RUNNING));
+        assert!(buffer.text(7).ends_with("White  4:47"));
+        assert_eq!(buffer.cell(PANE, 7).fg, None);
+
+        // taking the move back gives white the clock again, without the increment
+        press(&mut app, &[Key::Char('u')]);
+        let clock = app.game.get_clock().unwrap();
+        assert_eq!(clock.running(), Some(Color::White));
+        assert_eq!(clock.remaining(Color::White), Duration::from_secs(285));
+        assert_eq!(clock.remaining(Color::Black), Duration::from_secs(300));
+        let buffer = drawn(&app);
+        assert!(buffer.text(0).ends_with("Black  5:00"));
+        assert_eq!(buffer.cell(PANE, 7).fg, Some(RUNNING));
+
+        // the clock stops at checkmate and runs again once the mate is taken back
+        let fen = "rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2";
+        let mut app = App::new(Game::from_fen(fen).unwrap(), Some(control.clone()));
+        app.game
+            .set_clock(Clock::with_time_source(control, time.clone()));
+        press(&mut app, &[Key::Up, Key::Left, Key::Select]);
+        press(&mut app, &[Key::Down; 4]);
+        press(&mut app, &[Key::Right; 4]);
+        press(&mut app, &[Key::Select]);
+        assert!(app.game.get_clock().is_none());
+        assert_eq!(drawn(&app).cell(PANE, 0).fg, None);
+
+        time.advance(Duration::from_secs(60));
+        press(&mut app, &[Key::Char('u')]);
+        let clock = app.game.get_clock().unwrap();
+        assert_eq!(clock.running(), Some(Color::Black));
+        assert_eq!(clock.remaining(Color::Black), Duration::from_secs(300));
+        assert_eq!(drawn(&app).cell(PANE, 0).fg, Some(RUNNING));
+        time.advance(Duration::from_secs(5));
+        assert!(drawn(&app).text(0).ends_with("Black  4:55"));
+    }
+}
@@ -0,0 +1,607 @@
+//! The engine side of the Universal Chess Interface (UCI), so that the library can be used
+//! from chess GUIs and tournament managers.
+//!
+//! The GUI sends one command per line and the engine answers with lines of its own, see
+//! the [protocol description](https://www.shredderchess.com/chess-features/uci-universal-chess-interface.html).
+//! Searches run on their own thread, so that `stop`, `ponderhit` and `isready` are answered
+//! while the engine is thinking.
+//!
+//! The `uci` binary runs this on stdin and stdout, so a session can be scripted by piping
+//! commands into it:
+//!
+//! ```bash
+//! printf 'position startpos moves e2e4\ngo depth 4\n' | cargo run --bin uci
+//! ```
+//!
+//! When the input ends, a search that was started with a limit is finished first, while
+//! `go infinite` and `go ponder` searches are stopped.
+//!
+//! # Examples
+//! ```
+//! let input = "uci\nposition startpos moves e2e4 e7e5\ngo depth 2\n";
+//! let output = vprytz_chess::uci::run(input.as_bytes(), Vec::new());
+//! let output = String::from_utf8(output).unwrap();
+//! assert!(output.contains("uciok"));
+//! assert!(output.lines().last().unwrap().starts_with("bestmove"));
+//! ```
+
+use std::io::{BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::search::{mate_in, Limits, SearchResult, Searcher, DEFAULT_HASH_MB};
+use crate::timeman::TimeManager;
+use crate::variant::Variant;
+use crate::{Color, Game, Move};
+
+/// Name of the engine, as sent in reply to `uci`.
+pub const ENGINE_NAME: &str = "vprytz-chess";
+
+/// Author of the engine, as sent in reply to `uci`.
+pub const ENGINE_AUTHOR: &str = "Vilhelm Prytz";
+
+const MAX_HASH_MB: usize = 4096;
+const MAX_THREADS: usize = 256;
+const MAX_MULTI_PV: usize = 256;
+
+/// Reads UCI commands from `input` and writes the answers to `output`, until the input ends
+/// or `quit` is received. Returns the output, which is handy when writing to a buffer.
+pub fn run<R: BufRead, W: Write + Send + 'static>(input: R, output: W) -> W {
+    let mut engine = Engine::new(output);
+
+    for line in input.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        if !engine.command(&line) {
+            engine.stop();
+            break;
+        }
+    }
+
+    // searches that would never end by themselves are stopped, the others may finish
+    engine.wait();
+
+    match Arc::try_unwrap(engine.output) {
+        Ok(output) => output
+            .into_inner()
+            .unwrap_or_else(|error| error.into_inner()),
+        Err(_) => unreachable!("the search thread has been joined"),
+    }
+}
+
+// a search running on its own thread
+struct Running {
+    handle: JoinHandle<Searcher>,
+    stop: Arc<AtomicBool>,
+    // set while the best move must be held back (go infinite and go ponder)
+    hold: Arc<AtomicBool>,
+    // time to search for once a ponder search turns into a real one
+    ponder_time: Option<Duration>,
+}
+
+struct Engine<W> {
+    output: Arc<Mutex<W>>,
+    game: Game,
+    // None while a search has it
+    searcher: Option<Searcher>,
+    running: Option<Running>,
+    // castling is sent and received as king takes rook
+    chess960: bool,
+    variant: Variant,
+}
+
+impl<W: Write + Send + 'static> Engine<W> {
+    fn new(output: W) -> Engine<W> {
+        Engine {
+            output: Arc::new(Mutex::new(output)),
+            game: Game::new(),
+            searcher: Some(Searcher::new()),
+            running: None,
+            chess960: false,
+            variant: Variant::Standard,
+        }
+    }
+
+    // handles one line of input, returning false on quit
+    fn command(&mut self, line: &str) -> bool {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let (command, args) = match tokens.split_first() {
+            Some((command, args)) => (*command, args),
+            None => return true,
+        };
+
+        match command {
+            "uci" => {
+                send(&self.output, &format!("id name {}", ENGINE_NAME));
+                send(&self.output, &format!("id author {}", ENGINE_AUTHOR));
+                send(
+                    &self.output,
+                    &format!(
+                        "option name Hash type spin default {} min 1 max {}",
+                        DEFAULT_HASH_MB, MAX_HASH_MB
+                    ),
+                );
+                send(
+                    &self.output,
+                    &format!(
+                        "option name Threads type spin default 1 min 1 max {}",
+                        MAX_THREADS
+                    ),
+                );
+                send(
+                    &self.output,
+                    &format!(
+                        "option name MultiPV type spin default 1 min 1 max {}",
+                        MAX_MULTI_PV
+                    ),
+                );
+                send(&self.output, "option name Ponder type check default false");
+                send(
+                    &self.output,
+                    "option name UCI_Chess960 type check default false",
+                );
+                let variants: Vec<String> = Variant::ALL
+                    .iter()
+                    .map(|variant| format!("var {}", variant.name()))
+                    .collect();
+                send(
+                    &self.output,
+                    &format!(
+                        "option name UCI_Variant type combo default {} {}",
+                        Variant::Standard.name(),
+                        variants.join(" ")
+                    ),
+                );
+                send(&self.output, "uciok");
+            }
+            "isready" => send(&self.output, "readyok"),
+            "ucinewgame" => {
+                self.wait();
+                self.searcher().table().clear();
+                self.game = Game::new_variant(self.variant);
+            }
+            "setoption" => {
+                self.wait();
+                self.set_option(args);
+            }
+            "position" => {
+                self.wait();
+                self.position(args);
+            }
+            "go" => {
+                self.wait();
+                self.go(args);
+            }
+            "stop" => self.stop(),
+            "ponderhit" => {
+                if let Some(running) = &self.running {
+                    running.hold.store(false, Ordering::Relaxed);
+                    if let Some(time) = running.ponder_time {
+                        let stop = running.stop.clone();
+                        thread::spawn(move || {
+                            thread::sleep(time);
+                            stop.store(true, Ordering::Relaxed);
+                        });
+                    }
+                }
+            }
+            "quit" => return false,
+            // debug and register don't change anything, unknown commands are ignored
+            _ => {}
+        }
+        true
+    }
+
+    fn searcher(&mut self) -> &mut Searcher {
+        self.searcher.as_mut().expect("no search is running")
+    }
+
+    // setoption name <name> [value <value>], names can contain spaces
+    fn set_option(&mut self, args: &[&str]) {
+        let value_at = args.iter().position(|token| *token == "value");
+        let name = args[..value_at.unwrap_or(args.len())]
+            .iter()
+            .skip_while(|token| **token == "name")
+            .copied()
+            .collect::<Vec<&str>>()
+            .join(" ");
+        let text = value_at.and_then(|at| args.get(at + 1)).copied();
+        let value = text.and_then(|value| value.parse::<usize>().ok());
+
+        match (name.to_lowercase().as_str(), value) {
+            ("hash", Some(megabytes)) => self
+                .searcher()
+                .set_hash_size(megabytes.clamp(1, MAX_HASH_MB)),
+            ("threads", Some(threads)) => {
+                self.searcher().set_threads(threads.clamp(1, MAX_THREADS))
+            }
+            ("multipv", Some(lines)) => self.searcher().set_multi_pv(lines.clamp(1, MAX_MULTI_PV)),
+            // the GUI tells us about pondering, but there is nothing to set up for it
+            ("ponder", _) => {}
+            ("uci_chess960", _) => {
+                self.chess960 = text == Some("true");
+                self.game.set_chess960(self.chess960);
+            }
+            ("uci_variant", _) => match text.and_then(Variant::from_name) {
+                Some(variant) => {
+                    self.variant = variant;
+                    self.game = Game::new_variant(variant);
+                }
+                None => send(
+                    &self.output,
+                    &format!("info string unknown variant {}", text.unwrap_or("")),
+                ),
+            },
+            _ => send(
+                &self.output,
+                &format!("info string unknown option {}", name),
+            ),
+        }
+    }
+
+    // position startpos|fen <fen> [moves <move>...]
+    fn position(&mut self, args: &[&str]) {
+        let moves_at = args
+            .iter()
+            .position(|token| *token == "moves")
+            .unwrap_or(args.len());
+
+        let game = match args.first() {
+            Some(&"startpos") => Some(Game::new_variant(self.variant)),
+            Some(&"fen") => Game::from_fen_variant(&args[1..moves_at].join(" "), self.variant),
+            _ => None,
+        };
+        let mut game = match game {
+            Some(mut game) => {
+                game.set_chess960(self.chess960);
+                game
+            }
+            None => {
+                send(
+                    &self.output,
+                    &format!("info string invalid position {}", args.join(" ")),
+                );
+                return;
+            }
+        };
+
+        for text in args.iter().skip(moves_at + 1) {
+            match game.parse_uci_move(text) {
+                Some(mv) => {
+                    game.apply_move(mv);
+                }
+                None => {
+                    send(&self.output, &format!("info string illegal move {}", text));
+                    break;
+                }
+            }
+        }
+        self.game = game;
+    }
+
+    fn go(&mut self, args: &[&str]) {
+        let mut limits = Limits::default();
+        let mut clock = Clock::default();
+        let mut hold = false;
+        let mut ponder = false;
+
+        let mut tokens = args.iter();
+        while let Some(token) = tokens.next() {
+            let mut number = || tokens.next().and_then(|value| value.parse::<u64>().ok());
+            match *token {
+                "depth" => limits.depth = number().map(|depth| depth as u32),
+                "nodes" => limits.nodes = number(),
+                "movetime" => limits.time = number().map(Duration::from_millis),
+                "wtime" => clock.white_time = number(),
+                "btime" => clock.black_time = number(),
+                "winc" => clock.white_increment = number().unwrap_or(0),
+                "binc" => clock.black_increment = number().unwrap_or(0),
+                "movestogo" => clock.moves_to_go = number(),
+                "infinite" => hold = true,
+                "ponder" => {
+                    hold = true;
+                    ponder = true;
+                }
+                _ => {}
+            }
+        }
+
+        if limits.time.is_none() {
+            limits.manager = clock.time_manager(&self.game);
+        }
+        // a ponder search goes on until ponderhit, and only then starts to use its time
+        let ponder_time = if ponder {
+            let manager = limits.manager.take();
+            limits
+                .time
+                .take()
+                .or(manager.map(|manager| manager.soft_limit()))
+        } else {
+            None
+        };
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let hold = Arc::new(AtomicBool::new(hold));
+        limits.stop = Some(stop.clone());
+
+        let mut searcher = self.searcher.take().expect("no search is running");
+        let game = self.game.clone();
+        let output = self.output.clone();
+        let thread_stop = stop.clone();
+        let thread_hold = hold.clone();
+
+        let handle = thread::spawn(move || {
+            let start = Instant::now();
+            let table = searcher.table().clone();
+            let result = searcher.search(&game, &limits, |result| {
+                for line in info_lines(&game, result, start.elapsed(), table.hashfull()) {
+                    send(&output, &line);
+                }
+            });
+
+            // the best move may only be sent once the GUI says so
+            while thread_hold.load(Ordering::Relaxed) && !thread_stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(1));
+            }
+            send(&output, &best_move_line(&game, &result));
+            searcher
+        });
+
+        self.running = Some(Running {
+            handle,
+            stop,
+            hold,
+            ponder_time,
+        });
+    }
+
+    // stops the running search, if any, and waits for it to send its best move
+    fn stop(&mut self) {
+        if let Some(running) = &self.running {
+            running.stop.store(true, Ordering::Relaxed);
+        }
+        self.wait();
+    }
+
+    // waits for the running search, if any, to finish by itself, a search that waits for
+    // stop or ponderhit (go infinite or go ponder) is stopped as it would never finish
+    fn wait(&mut self) {
+        if let Some(running) = self.running.take() {
+            if running.hold.load(Ordering::Relaxed) {
+                running.stop.store(true, Ordering::Relaxed);
+            }
+            match running.handle.join() {
+                Ok(searcher) => self.searcher = Some(searcher),
+                // the search thread panicked, start over with a fresh searcher
+                Err(_) => self.searcher = Some(Searcher::new()),
+            }
+        }
+    }
+}
+
+// the time control part of a go command, times in milliseconds
+#[derive(Default)]
+struct Clock {
+    white_time: Option<u64>,
+    black_time: Option<u64>,
+    white_increment: u64,
+    black_increment: u64,
+    moves_to_go: Option<u64>,
+}
+
+impl Clock {
+    fn time_manager(&self, game: &Game) -> Option<TimeManager> {
+        let (time, increment) = match game.get_active_color() {
+            Color::White => (self.white_time?, self.white_increment),
+            Color::Black => (self.black_time?, self.black_increment),
+        };
+        Some(TimeManager::new(
+            game,
+            Duration::from_millis(time),
+            Duration::from_millis(increment),
+            self.moves_to_go.map(|moves| moves as u32),
+        ))
+    }
+}
+
+fn send<W: Write>(output: &Mutex<W>, line: &str) {
+    let mut output = output.lock().unwrap_or_else(|error| error.into_inner());
+    // there is nobody to tell if the GUI went away
+    let _ = writeln!(output, "{}", line);
+    let _ = output.flush();
+}
+
+// one info line per searched line (more than one with MultiPV)
+fn info_lines(
+    game: &Game,
+    result: &SearchResult,
+    elapsed: Duration,
+    hashfull: usize,
+) -> Vec<String> {
+    let millis = elapsed.as_millis() as u64;
+    let nps = result.nodes * 1000 / millis.max(1);
+
+    result
+        .lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !line.pv.is_empty())
+        .map(|(index, line)| {
+            let score = match mate_in(line.score) {
+                Some(moves) => format!("mate {}", moves),
+                None => format!("cp {}", line.score),
+            };
+            format!(
+                "info depth {} multipv {} score {} nodes {} nps {} hashfull {} time {} pv {}",
+                result.depth,
+                index + 1,
+                score,
+                result.nodes,
+                nps,
+                hashfull,
+                millis,
+                moves_to_uci(game, &line.pv).join(" ")
+            )
+        })
+        .collect()
+}
+
+fn best_move_line(game: &Game, result: &SearchResult) -> String {
+    let moves = moves_to_uci(game, &result.pv);
+    match moves.as_slice() {
+        [] => "bestmove 0000".to_string(),
+        [best] => format!("bestmove {}", best),
+        [best, ponder, ..] => format!("bestmove {} ponder {}", best, ponder),
+    }
+}
+
+// writes a line of moves, playing them on a copy of the game so that castling comes out right
+fn moves_to_uci(game: &Game, moves: &[Move]) -> Vec<String> {
+    let mut game = game.clone();
+    moves
+        .iter()
+        .map(|mv| {
+            let text = game.move_to_uci(*mv);
+            game.do_move(*mv);
+            text
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(input: &str) -> Vec<String> {
+        let output = run(input.as_bytes(), Vec::new());
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| line.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn handshake() {
+        let output = session("uci\nisready\n");
+
+        assert_eq!(output[0], format!("id name {}", ENGINE_NAME));
+        assert!(output
+            .iter()
+            .any(|line| line.starts_with("option name Hash type spin")));
+        assert!(output
+            .iter()
+            .any(|line| line.starts_with("option name MultiPV type spin")));
+        assert_eq!(output[output.len() - 2], "uciok");
+        assert_eq!(output[output.len() - 1], "readyok");
+    }
+
+    #[test]
+    fn finds_mate_from_fen_and_moves() {
+        // after f2f3 e7e5 g2g4 black mates with Qh4
+        let output = session("position fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 moves f2f3 e7e5 g2g4\ngo depth 3\n");
+
+        assert!(output
+            .iter()
+            .any(|line| line.starts_with("info depth 1 multipv 1 score mate 1")));
+        assert_eq!(output.last().unwrap(), "bestmove d8h4");
+    }
+
+    #[test]
+    fn castling_is_sent_as_king_move() {
+        // castling is mate, the rooks on e8 and g8 block their own king
+        let output = session("position fen 4rkr1/4p1p1/8/8/8/8/8/4K2R w K - 0 1\ngo depth 2\n");
+
+        assert!(output
+            .iter()
+            .any(|line| line.contains("score mate 1 ") && line.ends_with("pv e1g1")));
+        assert_eq!(output.last().unwrap(), "bestmove e1g1");
+
+        // with UCI_Chess960 the king takes its own rook
+        let output = session("setoption name UCI_Chess960 value true\nposition fen 4rkr1/4p1p1/8/8/8/8/8/4K2R w K - 0 1\ngo depth 2\n");
+        assert_eq!(output.last().unwrap(), "bestmove e1h1");
+    }
+
+    #[test]
+    fn variant_option() {
+        let output = session("uci\nsetoption name UCI_Variant value 3check\nposition fen rnbqkbnr/ppp2ppp/8/3pp3/4P3/5Q2/PPPP1PPP/RNB1KBNR w KQkq - 1+3 0 3\ngo depth 2\n");
+
+        assert!(output.iter().any(|line| line
+            .starts_with("option name UCI_Variant type combo default chess var chess ")
+            && line.contains(" var 3check")));
+        // the third check wins like a mate
+        assert!(output.iter().any(|line| line.contains("score mate 1 ")));
+    }
+
+    #[test]
+    fn multi_pv_and_options() {
+        let output = session("setoption name MultiPV value 3\nsetoption name Hash value 2\nsetoption name Threads value 2\nsetoption name Foo value 1\nposition startpos\ngo depth 2\n");
+
+        assert!(output
+            .iter()
+            .any(|line| line == "info string unknown option Foo"));
+        assert!(output
+            .iter()
+            .any(|line| line.starts_with("info depth 2 multipv 3 ")));
+        assert!(output.last().unwrap().starts_with("bestmove"));
+    }
+
+    #[test]
+    fn infinite_search_waits_for_stop() {
+        let output = session("position startpos\ngo infinite\nisready\nstop\nisready\n");
+
+        // readyok is sent while searching, the best move only after stop
+        let ready = output.iter().position(|line| line == "readyok").unwrap();
+        let best = output
+            .iter()
+            .position(|line| line.starts_with("bestmove"))
+            .unwrap();
+        assert!(ready < best);
+        assert_eq!(output.last().unwrap(), "readyok");
+    }
+
+    #[test]
+    fn ponderhit_starts_the_clock() {
+        let start = Instant::now();
+        let output =
+            session("position startpos moves e2e4\ngo ponder wtime 3000 btime 3000\nponderhit\n");
+
+        assert!(output.last().unwrap().starts_with("bestmove"));
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn clock_of_the_side_to_move() {
+        let clock = Clock {
+            white_time: Some(60_000),
+            black_time: None,
+            white_increment: 1000,
+            black_increment: 0,
+            moves_to_go: Some(20),
+        };
+
+        let game = Game::new();
+        let manager = clock.time_manager(&game).unwrap();
+        let expected = TimeManager::new(
+            &game,
+            Duration::from_secs(60),
+            Duration::from_secs(1),
+            Some(20),
+        );
+        assert_eq!(manager.soft_limit(), expected.soft_limit());
+
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/4K3 b - - 0 1").unwrap();
+        assert!(clock.time_manager(&game).is_none());
+    }
+
+    #[test]
+    fn mated_side_has_no_move() {
+        let output = session("position startpos moves f2f3 e7e5 g2g4 d8h4\ngo depth 2\n");
+
+        assert_eq!(output.last().unwrap(), "bestmove 0000");
+    }
+}
@@ -0,0 +1,114 @@
+// Antichess (also called losing chess or giveaway): the first player to lose all their pieces
+// or to have no legal moves wins. Capturing is compulsory (any capture may be chosen when
+// there are several), the king is just another piece that can be captured and can't castle,
+// and pawns may also promote to kings.
+
+use crate::{Color, Game, Move, PieceType};
+
+impl<const WIDTH: usize, const HEIGHT: usize> Game<WIDTH, HEIGHT> {
+    // adds the promotions to king and leaves only the captures if there are any
+    pub(crate) fn antichess_moves(&self, moves: &mut Vec<Move>) {
+        let kings: Vec<Move> = moves
+            .iter()
+            .filter(|mv| mv.promotion == Some(PieceType::Queen))
+            .map(|mv| Move {
+                promotion: Some(PieceType::King),
+                ..*mv
+            })
+            .collect();
+        moves.extend(kings);
+
+        if moves.iter().any(|mv| self.is_capture(*mv)) {
+            moves.retain(|mv| self.is_capture(*mv));
+        }
+    }
+
+    // true if the player has a capture and so isn't allowed to make any other move
+    pub(crate) fn must_capture(&self, color: Color) -> bool {
+        let mut game = self.position();
+        game.active_color = color;
+        game.pseudo_legal_moves()
+            .iter()
+            .any(|mv| game.is_capture(*mv))
+    }
+
+    // there is no castling in Antichess, so anything on the target square is an enemy piece
+    pub(crate) fn is_capture(&self, mv: Move) -> bool {
+        let pawn = matches!(self.board[mv.from.0][mv.from.1], Some(piece) if piece.piece == PieceType::Pawn);
+        self.board[mv.to.0][mv.to.1].is_some() || (pawn && mv.from.1 != mv.to.1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::variant::{check_perft, Variant};
+    use crate::{Color, Game, GameState, Outcome};
+
+    #[test]
+    fn captures_are_compulsory() {
+        let mut game = Game::new_variant(Variant::Antichess);
+        assert_eq!(
+            game.to_fen(),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1"
+        );
+        game.make_move("E2".to_string(), "E4".to_string());
+        game.make_move("D7".to_string(), "D5".to_string());
+
+        // exd5 is the only legal move, also for get_possible_moves()
+        assert_eq!(game.get_legal_moves().len(), 1);
+        assert_eq!(
+            game.get_possible_moves("E4".to_string()),
+            Some(vec!["D5".to_string()])
+        );
+        assert_eq!(game.get_possible_moves("G1".to_string()), Some(vec![]));
+        assert_eq!(game.make_move("G1".to_string(), "F3".to_string()), None);
+    }
+
+    #[test]
+    fn kings_are_ordinary_pieces() {
+        // the king can be left attacked, captured, and pawns may promote to it
+        let game =
+            Game::from_fen_variant("8/P7/8/8/8/8/8/K1k5 w - - 0 1", Variant::Antichess).unwrap();
+        assert_eq!(game.parse_uci_move("a7a8k").unwrap().to_string(), "a7a8k");
+
+        let mut game =
+            Game::from_fen_variant("8/P7/8/8/8/8/1k6/K7 w - - 0 1", Variant::Antichess).unwrap();
+        assert!(!game.is_check());
+        assert_eq!(game.get_game_state(), GameState::InProgress);
+        assert!(game.parse_uci_move("a7a8k").is_none());
+
+        game.apply_move(game.parse_uci_move("a1b2").unwrap());
+        assert_eq!(game.get_game_state(), GameState::GameOver);
+        assert_eq!(
+            game.get_outcome(),
+            Some(Outcome::VariantWin {
+                winner: Color::Black
+            })
+        );
+    }
+
+    #[test]
+    fn stalemate_wins() {
+        // black's pawn is blocked, so black has no moves and wins
+        let game =
+            Game::from_fen_variant("8/8/8/8/8/p7/P7/8 b - - 0 1", Variant::Antichess).unwrap();
+        assert_eq!(
+            game.get_outcome(),
+            Some(Outcome::VariantWin {
+                winner: Color::Black
+            })
+        );
+    }
+
+    #[test]
+    fn perft_reference_positions() {
+        // from the python-chess test suite
+        check_perft(
+            Variant::Antichess,
+            &[
+                (Variant::Antichess.starting_fen(), &[20, 400, 8067]),
+                ("8/1p6/8/8/8/8/P7/8 w - - 0 1", &[2, 4, 4, 3, 1, 0]),
+            ],
+        );
+    }
+}
@@ -0,0 +1,147 @@
+// Atomic chess: every capture is an explosion that removes the capturing piece, the captured
+// piece and every piece but pawns on the eight squares around the capture. Kings can't
+// capture (they would blow themselves up), and kings standing next to each other can't be
+// checked, since taking one would also explode the other. Exploding the enemy king wins,
+// even if the own king is in check at the time, while exploding the own king is illegal.
+
+use crate::{offset, Outcome};
+use crate::{Color, Game, PieceType, KING_OFFSETS};
+
+impl<const WIDTH: usize, const HEIGHT: usize> Game<WIDTH, HEIGHT> {
+    // removes the capturing piece and everything but pawns around the square of the capture
+    pub(crate) fn explode(&mut self, pos: (usize, usize)) {
+        self.board[pos.0][pos.1] = None;
+        for step in KING_OFFSETS {
+            if let Some(target) = offset(pos, step, WIDTH, HEIGHT) {
+                if let Some(piece) = self.board[target.0][target.1] {
+                    if piece.piece != PieceType::Pawn {
+                        self.board[target.0][target.1] = None;
+                    }
+                }
+            }
+        }
+    }
+
+    // king_attacked() for Atomic, where a missing king has exploded
+    pub(crate) fn atomic_king_attacked(&self, color: Color) -> bool {
+        match (self.find_king(color), self.find_king(color.opposite())) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(king), Some(_)) => {
+                !self.next_to_king(king, color.opposite())
+                    && self.is_attacked(king, color.opposite())
+            }
+        }
+    }
+
+    // true if the king of the given color stands on one of the squares around pos
+    pub(crate) fn next_to_king(&self, pos: (usize, usize), color: Color) -> bool {
+        KING_OFFSETS
+            .iter()
+            .any(|step| match offset(pos, *step, WIDTH, HEIGHT) {
+                Some(target) => self.is_piece(target.0, target.1, color, PieceType::King),
+                None => false,
+            })
+    }
+
+    pub(crate) fn atomic_outcome(&self) -> Option<Outcome> {
+        match (self.find_king(Color::White), self.find_king(Color::Black)) {
+            (Some(_), None) => Some(Outcome::VariantWin {
+                winner: Color::White,
+            }),
+            (None, Some(_)) => Some(Outcome::VariantWin {
+                winner: Color::Black,
+            }),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn find_king(&self, color: Color) -> Option<(usize, usize)> {
+        (0..HEIGHT)
+            .flat_map(|row| (0..WIDTH).map(move |col| (row, col)))
+            .find(|(row, col)| self.is_piece(*row, *col, color, PieceType::King))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::variant::{check_perft, Variant};
+    use crate::{Color, Game, GameState, Outcome};
+
+    #[test]
+    fn explosions() {
+        // Nxd7 takes out the knight, the pawn on d7 and the bishop, queen and king next to it,
+        // but not the pawns on c7 and e7
+        let mut game = Game::from_fen_variant(
+            "rnbqkbnr/pppppppp/8/2N5/8/8/PPPPPPPP/R1BQKBNR w KQkq - 0 1",
+            Variant::Atomic,
+        )
+        .unwrap();
+        game.apply_move(game.parse_uci_move("c5d7").unwrap());
+        assert_eq!(
+            game.to_fen(),
+            "rn3bnr/ppp1pppp/8/8/8/8/PPPPPPPP/R1BQKBNR b KQ - 0 1"
+        );
+        assert_eq!(game.get_game_state(), GameState::GameOver);
+        assert_eq!(
+            game.get_outcome(),
+            Some(Outcome::VariantWin {
+                winner: Color::White
+            })
+        );
+    }
+
+    #[test]
+    fn king_rules() {
+        // the king can't take the queen, and the queen next to it can't give check
+        let game =
+            Game::from_fen_variant("8/8/8/8/8/8/3qk3/3K4 w - - 0 1", Variant::Atomic).unwrap();
+        assert!(!game.is_check());
+        assert!(game.parse_uci_move("d1d2").is_none());
+
+        // with the kings apart the same queen gives check, and a capture next to the
+        // own king is illegal since it would blow it up
+        let game =
+            Game::from_fen_variant("8/8/8/3k4/8/8/3q4/3KB3 w - - 0 1", Variant::Atomic).unwrap();
+        assert!(game.is_check());
+        assert!(game.parse_uci_move("e1d2").is_none());
+
+        // exploding the enemy king is allowed even when in check
+        let mut game =
+            Game::from_fen_variant("8/8/8/8/8/3N4/1k6/2r1K2q w - - 0 1", Variant::Atomic).unwrap();
+        assert!(game.is_check());
+        assert!(game.parse_uci_move("d3b4").is_none());
+        game.apply_move(game.parse_uci_move("d3c1").unwrap());
+        assert_eq!(
+            game.get_outcome(),
+            Some(Outcome::VariantWin {
+                winner: Color::White
+            })
+        );
+    }
+
+    #[test]
+    fn perft_reference_positions() {
+        // from the python-chess test suite
+        check_perft(
+            Variant::Atomic,
+            &[
+                (
+                    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                    &[20, 400, 8902][..],
+                ),
+                (
+                    "rn2kb1r/1pp1p2p/p2q1pp1/3P4/2P3b1/4PN2/PP3PPP/R2QKB1R b KQkq - 0 1",
+                    &[40, 1238, 45237],
+                ),
+                (
+                    "rn1qkb1r/p5pp/2p5/3p4/N3P3/5P2/PPP4P/R1BQK3 w Qkq - 0 1",
+                    &[28, 833, 23353],
+                ),
+                ("8/8/8/8/8/8/2k5/rR4KR w KQ - 0 1", &[18, 180, 4364]),
+                ("r3k1rR/5K2/8/8/8/8/8/8 b kq - 0 1", &[25, 282, 6753]),
+                ("Rr2k1rR/3K4/3p4/8/8/8/7P/8 w kq - 0 1", &[21, 465, 10631]),
+            ],
+        );
+    }
+}
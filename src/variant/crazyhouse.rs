@@ -0,0 +1,276 @@
+// Crazyhouse: captured pieces go into the pocket of the player who took them, who may later
+// drop them on any empty square instead of moving (pawns not on the first or last rank).
+// Promoted pieces turn back into pawns when they are captured, so the board keeps track of
+// which pieces were promoted.
+
+use super::color_index;
+use crate::fen::piece_from_char;
+use crate::{Color, Game, Move, Piece, PieceType, BOARD_SIZE};
+
+// the squares holding promoted pieces
+pub(crate) type Promoted<const WIDTH: usize = BOARD_SIZE, const HEIGHT: usize = BOARD_SIZE> =
+    [[bool; WIDTH]; HEIGHT];
+
+/// The pieces a player holds in Crazyhouse, captured from the other player and ready to be dropped.
+/// # Examples
+/// ```
+/// use vprytz_chess::variant::Pocket;
+/// use vprytz_chess::PieceType;
+/// let mut pocket = Pocket::default();
+/// pocket.add(PieceType::Knight);
+/// assert_eq!(pocket.count(PieceType::Knight), 1);
+/// assert!(pocket.remove(PieceType::Knight));
+/// assert!(pocket.is_empty());
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Pocket {
+    counts: [u32; 5],
+}
+
+impl Pocket {
+    /// The piece types that can be in a pocket, in the order they are written to FEN.
+    pub const PIECES: [PieceType; 5] = [
+        PieceType::Queen,
+        PieceType::Rook,
+        PieceType::Bishop,
+        PieceType::Knight,
+        PieceType::Pawn,
+    ];
+
+    /// Returns how many pieces of the type are in the pocket.
+    pub fn count(&self, piece: PieceType) -> u32 {
+        index(piece).map_or(0, |index| self.counts[index])
+    }
+
+    /// Puts a piece in the pocket, kings are ignored since they can't be captured.
+    pub fn add(&mut self, piece: PieceType) {
+        if let Some(index) = index(piece) {
+            self.counts[index] += 1;
+        }
+    }
+
+    /// Takes a piece out of the pocket, returning false if there was none.
+    pub fn remove(&mut self, piece: PieceType) -> bool {
+        match index(piece) {
+            Some(index) if self.counts[index] > 0 => {
+                self.counts[index] -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns true if there is nothing to drop.
+    pub fn is_empty(&self) -> bool {
+        self.counts.iter().all(|count| *count == 0)
+    }
+}
+
+fn index(piece: PieceType) -> Option<usize> {
+    Pocket::PIECES.iter().position(|other| *other == piece)
+}
+
+// the pieces of a Crazyhouse FEN split into the normal FEN pieces, the pockets (either in
+// brackets or as a ninth rank) and the promoted pieces (marked with a ~)
+pub(crate) fn parse_board(text: &str) -> Option<(String, [Pocket; 2], Promoted)> {
+    let (board, pocket) = match text.split_once('[') {
+        Some((board, pocket)) => (board, pocket.strip_suffix(']')?),
+        None => match text.splitn(BOARD_SIZE + 1, '/').nth(BOARD_SIZE) {
+            Some(pocket) => (&text[..text.len() - pocket.len() - 1], pocket),
+            None => (text, ""),
+        },
+    };
+
+    let mut pockets = [Pocket::default(); 2];
+    for c in pocket.chars() {
+        let color = if c.is_ascii_uppercase() {
+            Color::White
+        } else {
+            Color::Black
+        };
+        let piece = piece_from_char(c).filter(|piece| *piece != PieceType::King)?;
+        pockets[color_index(color)].add(piece);
+    }
+
+    let mut promoted = [[false; BOARD_SIZE]; BOARD_SIZE];
+    for (row, rank) in board.split('/').enumerate().take(BOARD_SIZE) {
+        let mut col = 0;
+        for c in rank.chars() {
+            match c {
+                '~' if col > 0 && col <= BOARD_SIZE => promoted[row][col - 1] = true,
+                '~' => return None,
+                _ => col += c.to_digit(10).unwrap_or(1) as usize,
+            }
+        }
+    }
+
+    Some((board.replace('~', ""), pockets, promoted))
+}
+
+impl<const WIDTH: usize, const HEIGHT: usize> Game<WIDTH, HEIGHT> {
+    /// Returns the pieces the player has in hand (only used in Crazyhouse).
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::variant::Variant;
+    /// use vprytz_chess::{Color, Game, PieceType};
+    /// let fen = "rnbqkb1r/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[n] w KQkq - 0 1";
+    /// let game = Game::from_fen_variant(fen, Variant::Crazyhouse).unwrap();
+    /// assert_eq!(game.get_pocket(Color::Black).count(PieceType::Knight), 1);
+    /// ```
+    pub fn get_pocket(&self, color: Color) -> Pocket {
+        self.pockets[color_index(color)]
+    }
+
+    /// Returns true if the piece on the square (e.g. "E4") was a pawn once and becomes one
+    /// again when captured in Crazyhouse.
+    pub fn is_promoted(&self, square: String) -> bool {
+        let pos = self.pos_to_index(square);
+        self.promoted[pos.0][pos.1]
+    }
+
+    // every drop of a piece in the pocket of the player to move onto an empty square
+    pub(crate) fn drop_moves(&self, moves: &mut Vec<Move>) {
+        let pocket = self.get_pocket(self.active_color);
+        for piece in Pocket::PIECES {
+            if pocket.count(piece) == 0 {
+                continue;
+            }
+            for row in 0..HEIGHT {
+                // pawns can't be dropped where they would promote or never have been
+                if piece == PieceType::Pawn && (row == 0 || row == HEIGHT - 1) {
+                    continue;
+                }
+                for col in 0..WIDTH {
+                    if self.board[row][col].is_none() {
+                        moves.push(Move {
+                            from: (row, col),
+                            to: (row, col),
+                            promotion: None,
+                            drop: Some(piece),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    // moves pieces in and out of the pockets and keeps track of promoted pieces,
+    // called by do_move() before the board is changed
+    pub(crate) fn update_pockets(&mut self, mv: Move) {
+        if let Some(piece) = mv.drop {
+            self.pockets[color_index(self.active_color)].remove(piece);
+            self.promoted[mv.to.0][mv.to.1] = false;
+            return;
+        }
+        let mover = match self.board[mv.from.0][mv.from.1] {
+            Some(piece) => piece,
+            None => return,
+        };
+        if self.is_castling(mv) {
+            return;
+        }
+
+        let captured = match self.board[mv.to.0][mv.to.1] {
+            Some(_) if self.promoted[mv.to.0][mv.to.1] => Some(PieceType::Pawn),
+            Some(piece) => Some(piece.piece),
+            // en passant
+            None if mover.piece == PieceType::Pawn && mv.from.1 != mv.to.1 => Some(PieceType::Pawn),
+            None => None,
+        };
+        if let Some(piece) = captured {
+            self.pockets[color_index(mover.color)].add(piece);
+        }
+
+        self.promoted[mv.to.0][mv.to.1] =
+            self.promoted[mv.from.0][mv.from.1] || mv.promotion.is_some();
+        self.promoted[mv.from.0][mv.from.1] = false;
+    }
+
+    // the piece a drop puts on the board, a pawn dropped on its starting rank may still
+    // move two squares
+    pub(crate) fn dropped_piece(&self, mv: Move, piece: PieceType) -> Piece {
+        Piece {
+            color: self.active_color,
+            piece,
+            untouched: piece == PieceType::Pawn
+                && Self::pawn_row(self.active_color) == Some(mv.to.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::variant::{check_perft, play, Variant};
+    use crate::{Color, Game, PieceType};
+
+    #[test]
+    fn captures_and_drops() {
+        let mut game = Game::new_variant(Variant::Crazyhouse);
+        assert_eq!(
+            game.to_fen(),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[] w KQkq - 0 1"
+        );
+
+        play(&mut game, &["e2e4", "d7d5", "e4d5", "d8d5", "b1c3", "d5a5"]);
+        assert_eq!(game.get_pocket(Color::White).count(PieceType::Pawn), 1);
+        assert_eq!(game.get_pocket(Color::Black).count(PieceType::Pawn), 1);
+
+        let drop = game.parse_uci_move("P@e6").unwrap();
+        assert_eq!(drop.to_string(), "P@e6");
+        game.apply_move(drop);
+        assert!(game.get_pocket(Color::White).is_empty());
+        assert_eq!(
+            game.to_fen(),
+            "rnb1kbnr/ppp1pppp/4P3/q7/8/2N5/PPPP1PPP/R1BQKBNR[p] b KQkq - 3 4"
+        );
+
+        // no pawns on the first or last rank
+        assert!(game.parse_uci_move("P@a1").is_none());
+        assert!(game.parse_uci_move("P@h3").is_some());
+        assert!(game.parse_uci_move("N@h3").is_none());
+
+        game.undo_move();
+        assert_eq!(game.get_pocket(Color::White).count(PieceType::Pawn), 1);
+    }
+
+    #[test]
+    fn promoted_pieces_become_pawns() {
+        let mut game =
+            Game::from_fen_variant("3rk3/2P5/8/8/8/8/8/4K3 w - - 0 1", Variant::Crazyhouse)
+                .unwrap();
+        play(&mut game, &["c7c8q"]);
+        assert!(game.is_promoted("C8".to_string()));
+        assert_eq!(game.to_fen(), "2Q~rk3/8/8/8/8/8/8/4K3[] b - - 0 1");
+
+        play(&mut game, &["d8c8"]);
+        assert_eq!(game.get_pocket(Color::Black).count(PieceType::Pawn), 1);
+        assert_eq!(game.get_pocket(Color::Black).count(PieceType::Queen), 0);
+
+        // read back from FEN, with the pocket as a ninth rank as well
+        let game =
+            Game::from_fen_variant("2Q~rk3/8/8/8/8/8/8/4K3/Nn b - - 0 1", Variant::Crazyhouse)
+                .unwrap();
+        assert!(game.is_promoted("C8".to_string()));
+        assert_eq!(game.to_fen(), "2Q~rk3/8/8/8/8/8/8/4K3[Nn] b - - 0 1");
+    }
+
+    #[test]
+    fn perft_reference_positions() {
+        // from the python-chess test suite
+        check_perft(
+            Variant::Crazyhouse,
+            &[
+                (
+                    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[] w KQkq - 0 1",
+                    &[20, 400, 8902][..],
+                ),
+                ("2k5/8/8/8/8/8/8/4K3[QRBNPqrbnp] w - - 0 1", &[301, 75353]),
+                (
+                    "r1bqk2r/pppp1ppp/2n1p3/4P3/1b1Pn3/2NB1N2/PPP2PPP/R1BQK2R[] b KQkq - 0 1",
+                    &[42, 1347, 58057],
+                ),
+                ("4k3/1Q~6/8/8/4b3/8/Kpp5/8/ b - - 0 1", &[20, 360, 5445]),
+            ],
+        );
+    }
+}
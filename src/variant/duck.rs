@@ -0,0 +1,175 @@
+// Duck chess: after every move the player also moves the duck, a neutral piece that blocks
+// the way of both players and can't be captured, to any other empty square. Both halves of a
+// turn are moves of their own, the duck move being a drop of the duck (written "*@e5") made by
+// the same player. There is no check, so kings may be left attacked, and capturing the king
+// wins. A player without any legal moves also wins.
+
+use crate::{Color, Game, Move, Piece, PieceType};
+
+// the duck belongs to nobody, its color doesn't mean anything
+const DUCK: Piece = Piece {
+    color: Color::White,
+    piece: PieceType::Duck,
+    untouched: false,
+};
+
+impl<const WIDTH: usize, const HEIGHT: usize> Game<WIDTH, HEIGHT> {
+    /// Returns true if the player to move has made their move and still has to move the duck
+    /// (only in Duck chess).
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::variant::Variant;
+    /// use vprytz_chess::{Color, Game};
+    /// let mut game = Game::new_variant(Variant::Duck);
+    /// game.make_move("E2".to_string(), "E4".to_string());
+    /// assert!(game.is_duck_to_move());
+    /// assert_eq!(game.get_active_color(), Color::White);
+    /// game.make_move("E5".to_string(), "E5".to_string()); // the duck is placed for the first time
+    /// assert!(!game.is_duck_to_move());
+    /// assert_eq!(game.get_active_color(), Color::Black);
+    /// ```
+    pub fn is_duck_to_move(&self) -> bool {
+        self.duck_to_move
+    }
+
+    // the duck may go to any empty square, it comes from the square it stands on (or from
+    // the target square the first time it is placed)
+    pub(crate) fn duck_moves(&self, moves: &mut Vec<Move>) {
+        let duck = self.find_duck();
+        for row in 0..HEIGHT {
+            for col in 0..WIDTH {
+                if self.board[row][col].is_none() {
+                    moves.push(Move {
+                        from: duck.unwrap_or((row, col)),
+                        to: (row, col),
+                        promotion: None,
+                        drop: Some(PieceType::Duck),
+                    });
+                }
+            }
+        }
+    }
+
+    // the second half of the turn, called by do_move() for duck moves
+    pub(crate) fn move_duck(&mut self, mv: Move) {
+        if let Some((row, col)) = self.find_duck() {
+            self.board[row][col] = None;
+        }
+        self.board[mv.to.0][mv.to.1] = Some(DUCK);
+        self.duck_to_move = false;
+        self.active_color = self.active_color.opposite();
+    }
+
+    fn find_duck(&self) -> Option<(usize, usize)> {
+        (0..HEIGHT)
+            .flat_map(|row| (0..WIDTH).map(move |col| (row, col)))
+            .find(|(row, col)| {
+                matches!(self.board[*row][*col], Some(piece) if piece.piece == PieceType::Duck)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::search::{search, Limits, MATE};
+    use crate::variant::{play, Variant};
+    use crate::{Color, Game, GameState, Outcome, PieceType};
+
+    fn duck(fen: &str) -> Game {
+        Game::from_fen_variant(fen, Variant::Duck).unwrap()
+    }
+
+    #[test]
+    fn two_moves_a_turn() {
+        let mut game = Game::new_variant(Variant::Duck);
+        assert_eq!(game.get_legal_moves().len(), 20);
+        play(&mut game, &["e2e4"]);
+        assert_eq!(game.get_active_color(), Color::White);
+        // the duck may go to any of the 32 empty squares
+        let moves = game.get_legal_moves();
+        assert_eq!(moves.len(), 32);
+        assert!(moves.iter().all(|mv| mv.drop == Some(PieceType::Duck)));
+
+        play(&mut game, &["*@e5"]);
+        assert_eq!(
+            game.to_fen(),
+            "rnbqkbnr/pppppppp/8/4*3/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1"
+        );
+        // the duck blocks the e-pawn, and has to leave its square
+        assert_eq!(
+            game.get_possible_moves("E7".to_string()),
+            Some(vec!["E6".to_string()])
+        );
+        play(&mut game, &["d7d5"]);
+        assert!(game.parse_uci_move("*@e5").is_none());
+        assert_eq!(game.get_possible_moves("E5".to_string()).unwrap().len(), 31);
+        play(&mut game, &["*@f5"]);
+        assert_eq!(game.get_game_state(), GameState::InProgress);
+
+        // and can't be captured
+        assert!(game.parse_uci_move("e4d5").is_some());
+        assert!(game.parse_uci_move("e4f5").is_none());
+
+        game.undo_move();
+        game.undo_move();
+        assert!(!game.is_duck_to_move());
+        assert!(game.parse_uci_move("d7d5").is_some());
+    }
+
+    #[test]
+    fn capturing_the_king_wins() {
+        // there is no check, the king may stay next to the queen
+        let mut game = duck("4k3/8/8/8/8/8/3q4/4K3 w - - 0 1");
+        assert!(!game.is_check());
+        assert!(game.parse_uci_move("e1e2").is_some());
+
+        play(&mut game, &["e1f1", "*@a1", "d2f2"]);
+        assert_eq!(game.get_outcome(), None);
+        play(&mut game, &["*@a2", "f1f2"]);
+        assert_eq!(game.get_outcome(), None);
+
+        let mut game = duck("4k3/8/8/8/8/8/3q4/4K3 b - - 0 1");
+        play(&mut game, &["d2e1"]);
+        assert!(!game.is_duck_to_move());
+        assert_eq!(
+            game.get_outcome(),
+            Some(Outcome::VariantWin {
+                winner: Color::Black
+            })
+        );
+    }
+
+    #[test]
+    fn stalemate_wins() {
+        // nothing white has can move past the duck and the black pawns
+        let game = duck("k7/8/8/8/8/p1p5/P*P5/KB6 w - - 0 1");
+        assert_eq!(game.get_game_state(), GameState::GameOver);
+        assert_eq!(
+            game.get_outcome(),
+            Some(Outcome::VariantWin {
+                winner: Color::White
+            })
+        );
+    }
+
+    #[test]
+    fn search_takes_the_king_and_places_the_duck() {
+        let limits = Limits {
+            depth: Some(2),
+            ..Limits::default()
+        };
+        let game = duck("4k3/8/8/8/8/8/3q4/4K3 b - - 0 1");
+        let result = search(&game, &limits);
+        assert_eq!(result.score, MATE - 1);
+        assert_eq!(result.best_move.map(|mv| mv.to), Some((7, 4)));
+
+        // the duck has to go between the queen and the king
+        let mut game = duck("k7/8/8/8/8/8/P7/K6q w - - 0 1");
+        play(&mut game, &["a2a3"]);
+        let result = search(&game, &limits);
+        let duck = result.best_move.unwrap();
+        assert_eq!(duck.drop, Some(PieceType::Duck));
+        assert!(duck.to.0 == 7 && (1..7).contains(&duck.to.1));
+        assert!(result.score > -MATE / 2);
+    }
+}
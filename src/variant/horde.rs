@@ -0,0 +1,101 @@
+// Horde: white has 36 pawns and no king against the normal black army. White wins by
+// mating the black king and black by capturing every white piece. The white pawns on the
+// first rank may move two squares, as may the ones that reach the second rank later, but
+// a double step from the first rank can't be taken en passant.
+
+use super::Variant;
+use crate::{Color, Game, Outcome, Piece, PieceType};
+
+impl<const WIDTH: usize, const HEIGHT: usize> Game<WIDTH, HEIGHT> {
+    // true for white pawns on the first or second rank, which may move two squares in Horde
+    // even when they have moved before
+    pub(crate) fn horde_double_step(&self, pos: (usize, usize), piece: Piece) -> bool {
+        self.variant == Variant::Horde
+            && piece.piece == PieceType::Pawn
+            && piece.color == Color::White
+            && pos.0 >= HEIGHT - 2
+    }
+
+    pub(crate) fn horde_outcome(&self) -> Option<Outcome> {
+        let white_left = self
+            .board
+            .iter()
+            .flatten()
+            .any(|square| matches!(square, Some(piece) if piece.color == Color::White));
+        if white_left {
+            None
+        } else {
+            Some(Outcome::VariantWin {
+                winner: Color::Black,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::variant::{check_perft, Variant};
+    use crate::{Color, Game, GameState, Outcome};
+
+    #[test]
+    fn first_rank_pawns() {
+        let mut game =
+            Game::from_fen_variant("4k3/8/8/8/8/8/8/P7 w - - 0 1", Variant::Horde).unwrap();
+        assert!(!game.is_check());
+        assert!(game.parse_uci_move("a1a3").is_some());
+
+        // still two squares from the second rank after the first step
+        game.apply_move(game.parse_uci_move("a1a2").unwrap());
+        game.apply_move(game.parse_uci_move("e8d8").unwrap());
+        assert!(game.parse_uci_move("a2a4").is_some());
+
+        // no en passant after a double step from the first rank
+        let mut game =
+            Game::from_fen_variant("4k3/8/8/8/8/1p6/8/P7 w - - 0 1", Variant::Horde).unwrap();
+        game.apply_move(game.parse_uci_move("a1a3").unwrap());
+        assert!(game.parse_uci_move("b3a2").is_none());
+    }
+
+    #[test]
+    fn capturing_everything_wins() {
+        let mut game =
+            Game::from_fen_variant("4k3/8/8/8/8/8/1q6/P7 b - - 0 1", Variant::Horde).unwrap();
+        game.apply_move(game.parse_uci_move("b2a1").unwrap());
+        assert_eq!(game.get_game_state(), GameState::GameOver);
+        assert_eq!(
+            game.get_outcome(),
+            Some(Outcome::VariantWin {
+                winner: Color::Black
+            })
+        );
+
+        // white can still mate the black king
+        let game =
+            Game::from_fen_variant("k7/PP6/1PP5/8/8/8/8/8 b - - 0 1", Variant::Horde).unwrap();
+        assert_eq!(
+            game.get_outcome(),
+            Some(Outcome::Checkmate {
+                winner: Color::White
+            })
+        );
+    }
+
+    #[test]
+    fn perft_reference_positions() {
+        // from the python-chess test suite
+        check_perft(
+            Variant::Horde,
+            &[
+                (Variant::Horde.starting_fen(), &[8, 128, 1274]),
+                (
+                    "4k3/pp4q1/3P2p1/8/P3PP2/PPP2r2/PPP5/PPPP4 b - - 0 1",
+                    &[30, 241, 6633],
+                ),
+                (
+                    "k7/5p2/4p2P/3p2P1/2p2P2/1p2P2P/p2P2P1/2P2P2 w - - 0 1",
+                    &[13, 172, 2205],
+                ),
+            ],
+        );
+    }
+}
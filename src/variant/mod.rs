@@ -0,0 +1,410 @@
+//! Chess variants, games played on the same board with different rules.
+//!
+//! A [`Game`] is always playing one [`Variant`], standard chess unless it was created with
+//! [`Game::new_variant`] or [`Game::from_fen_variant`]. The variant decides the starting
+//! position and can add ways for the game to end, which the rest of the library (including
+//! the search) then follows.
+//!
+//! * King of the Hill: a player also wins by moving their king to one of the four center
+//!   squares (d4, e4, d5 or e5).
+//! * Three-Check: a player also wins by giving check for the third time. The number of
+//!   checks each player has left to give is written to FEN as an extra field after the
+//!   en passant square, e.g. `2+3` once white has given one check.
+//! * Atomic: captures explode, removing the capturing piece and every piece but pawns next
+//!   to the captured one. Kings can't capture and can't be checked while standing next to
+//!   each other. Exploding the enemy king wins.
+//! * Crazyhouse: captured pieces go into the capturer's [`Pocket`] and can be dropped back on
+//!   the board as a move of their own, written like "N@f3". Promoted pieces become pawns
+//!   again when captured. In FEN the pockets are written in brackets after the board, e.g.
+//!   `RNBQKBNR[Qp]`, and promoted pieces are followed by a `~`.
+//! * Antichess: the first player to lose all their pieces, or to have no legal moves, wins.
+//!   Capturing is compulsory, the king can be captured like any other piece (so there is no
+//!   check, nor castling) and pawns may promote to kings.
+//! * Horde: white has 36 pawns and no king. White wins by mating the black king, black by
+//!   capturing all the white pieces. White pawns on the first rank may move two squares.
+//! * Racing Kings: the first king to reach the eighth rank wins, and no move may give check.
+//!   When the white king gets there first black may draw by reaching it on the next move.
+//! * Duck chess: after each move the player also has to move the duck, a neutral piece that
+//!   blocks both players and can't be captured, to another empty square. This is a move of
+//!   its own, a drop of the duck written like "*@e5" (see [`Game::is_duck_to_move`]), and the
+//!   turn passes once it has been made. There is no check, capturing the king wins and so does
+//!   having no legal moves. The duck is written as `*` in FEN, which doesn't tell if the duck
+//!   still has to be moved, so positions are read as being at the start of a turn.
+//!
+//! # Examples
+//! ```
+//! use vprytz_chess::variant::Variant;
+//! use vprytz_chess::{Color, Game, Outcome};
+//!
+//! let mut game = Game::new_variant(Variant::KingOfTheHill);
+//! for (from, to) in [("E2", "E4"), ("A7", "A6"), ("E1", "E2"), ("A6", "A5"), ("E2", "D3"), ("A5", "A4")] {
+//!     game.make_move(from.to_string(), to.to_string());
+//! }
+//! game.make_move("D3".to_string(), "D4".to_string());
+//! assert_eq!(game.get_outcome(), Some(Outcome::VariantWin { winner: Color::White }));
+//! ```
+
+use crate::fen::STARTING_FEN;
+use crate::{zobrist, Color, Game, Move, Outcome, PieceType, BOARD_SIZE};
+
+mod antichess;
+mod atomic;
+mod crazyhouse;
+mod duck;
+mod horde;
+mod racing_kings;
+
+pub use crazyhouse::Pocket;
+pub(crate) use crazyhouse::Promoted;
+
+// the squares a king has to reach in King of the Hill, d4, e4, d5 and e5
+const HILL: [(usize, usize); 4] = [(3, 3), (3, 4), (4, 3), (4, 4)];
+
+/// Checks a player has to give to win Three-Check.
+pub const CHECKS_TO_WIN: u32 = 3;
+
+/// The rule sets a game can be played with, see the module documentation.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Variant {
+    /// Normal chess.
+    #[default]
+    Standard,
+    /// Bringing the king to the center of the board wins.
+    KingOfTheHill,
+    /// Giving check three times wins.
+    ThreeCheck,
+    /// Captures explode everything around them.
+    Atomic,
+    /// Captured pieces can be dropped back on the board.
+    Crazyhouse,
+    /// Losing all pieces wins.
+    Antichess,
+    /// White's pawns against black's pieces.
+    Horde,
+    /// Kings race to the eighth rank.
+    RacingKings,
+    /// A duck that blocks both players is moved after every move.
+    Duck,
+}
+
+impl Variant {
+    /// All variants, standard chess first.
+    pub const ALL: [Variant; 9] = [
+        Variant::Standard,
+        Variant::KingOfTheHill,
+        Variant::ThreeCheck,
+        Variant::Atomic,
+        Variant::Crazyhouse,
+        Variant::Antichess,
+        Variant::Horde,
+        Variant::RacingKings,
+        Variant::Duck,
+    ];
+
+    /// Returns the name used for the variant by UCI GUIs, e.g. "kingofthehill".
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::variant::Variant;
+    /// assert_eq!(Variant::ThreeCheck.name(), "3check");
+    /// ```
+    pub fn name(self) -> &'static str {
+        match self {
+            Variant::Standard => "chess",
+            Variant::KingOfTheHill => "kingofthehill",
+            Variant::ThreeCheck => "3check",
+            Variant::Atomic => "atomic",
+            Variant::Crazyhouse => "crazyhouse",
+            Variant::Antichess => "antichess",
+            Variant::Horde => "horde",
+            Variant::RacingKings => "racingkings",
+            Variant::Duck => "duck",
+        }
+    }
+
+    /// Returns the variant with the given name, ignoring case, spaces, dashes and underscores
+    /// so that both "King of the Hill" and "kingofthehill" work. Standard chess can also be
+    /// called "standard" or "normal".
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::variant::Variant;
+    /// assert_eq!(Variant::from_name("Three-Check"), Some(Variant::ThreeCheck));
+    /// assert_eq!(Variant::from_name("normal"), Some(Variant::Standard));
+    /// ```
+    pub fn from_name(name: &str) -> Option<Variant> {
+        let name: String = name
+            .chars()
+            .filter(|c| !matches!(c, ' ' | '-' | '_'))
+            .collect::<String>()
+            .to_lowercase();
+        match name.as_str() {
+            "chess" | "standard" | "normal" => Some(Variant::Standard),
+            "kingofthehill" | "koth" => Some(Variant::KingOfTheHill),
+            "3check" | "threecheck" => Some(Variant::ThreeCheck),
+            "atomic" => Some(Variant::Atomic),
+            "crazyhouse" | "zh" => Some(Variant::Crazyhouse),
+            "antichess" | "losingchess" | "giveaway" => Some(Variant::Antichess),
+            "horde" => Some(Variant::Horde),
+            "racingkings" | "racing" => Some(Variant::RacingKings),
+            "duck" | "duckchess" => Some(Variant::Duck),
+            _ => None,
+        }
+    }
+
+    /// Returns the FEN of the position games of this variant start from.
+    pub fn starting_fen(self) -> &'static str {
+        match self {
+            Variant::Standard | Variant::KingOfTheHill | Variant::Atomic | Variant::Duck => {
+                STARTING_FEN
+            }
+            Variant::ThreeCheck => "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 3+3 0 1",
+            Variant::Crazyhouse => "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[] w KQkq - 0 1",
+            Variant::Antichess => "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1",
+            Variant::Horde => {
+                "rnbqkbnr/pppppppp/8/1PP2PP1/PPPPPPPP/PPPPPPPP/PPPPPPPP/PPPPPPPP w kq - 0 1"
+            }
+            Variant::RacingKings => "8/8/8/8/8/8/krbnNBRK/qrbnNBRQ w - - 0 1",
+        }
+    }
+}
+
+impl Game {
+    /// Creates a game of the given variant from its starting position.
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::variant::Variant;
+    /// use vprytz_chess::Game;
+    /// let game = Game::new_variant(Variant::ThreeCheck);
+    /// assert_eq!(game.get_variant(), Variant::ThreeCheck);
+    /// ```
+    pub fn new_variant(variant: Variant) -> Game {
+        Game::from_fen_variant(variant.starting_fen(), variant)
+            .expect("starting positions are valid")
+    }
+
+    /// Creates a game of the given variant from a FEN string, returning None if it can't be read.
+    /// Three-Check positions may have the checks left to give after the en passant square,
+    /// without it no checks have been given yet. Crazyhouse positions may have pockets, see
+    /// the module documentation.
+    /// # Examples
+    /// ```
+    /// use vprytz_chess::variant::Variant;
+    /// use vprytz_chess::{Color, Game};
+    /// let fen = "rnbqkbnr/ppp1pppp/8/1B1p4/4P3/8/PPPP1PPP/RNBQK1NR b KQkq - 2+3 1 2";
+    /// let game = Game::from_fen_variant(fen, Variant::ThreeCheck).unwrap();
+    /// assert_eq!(game.get_checks_given(Color::White), 1);
+    /// assert_eq!(game.to_fen(), fen);
+    /// ```
+    pub fn from_fen_variant(fen: &str, variant: Variant) -> Option<Game> {
+        let mut fields: Vec<&str> = fen.split_whitespace().collect();
+
+        let mut checks = [0, 0];
+        if variant == Variant::ThreeCheck && fields.get(4).is_some_and(|f| f.contains('+')) {
+            let (white, black) = fields.remove(4).split_once('+')?;
+            for (given, left) in checks.iter_mut().zip([white, black]) {
+                *given = CHECKS_TO_WIN.checked_sub(left.parse().ok()?)?;
+            }
+        }
+
+        let mut pockets = [Pocket::default(); 2];
+        let mut promoted = [[false; BOARD_SIZE]; BOARD_SIZE];
+        let board;
+        if variant == Variant::Crazyhouse && !fields.is_empty() {
+            (board, pockets, promoted) = crazyhouse::parse_board(fields[0])?;
+            fields[0] = &board;
+        }
+
+        let mut game = Game::from_fen(&fields.join(" "))?;
+        game.variant = variant;
+        game.checks = checks;
+        game.pockets = pockets;
+        game.promoted = promoted;
+        game.hash = zobrist::hash(&game);
+        game.update_state();
+        Some(game)
+    }
+}
+
+// variants are only played on the standard board, but the rules are part of every Game
+impl<const WIDTH: usize, const HEIGHT: usize> Game<WIDTH, HEIGHT> {
+    /// Returns the variant the game is played with.
+    pub fn get_variant(&self) -> Variant {
+        self.variant
+    }
+
+    /// Returns how many times the player has given check, only counted in Three-Check.
+    pub fn get_checks_given(&self, color: Color) -> u32 {
+        self.checks[color_index(color)]
+    }
+
+    // the game ending by a rule of the variant, checked before looking for mate or stalemate
+    pub(crate) fn variant_outcome(&self) -> Option<Outcome> {
+        match self.variant {
+            Variant::Standard => None,
+            Variant::KingOfTheHill => HILL.iter().find_map(|(row, col)| {
+                self.board[*row][*col]
+                    .filter(|piece| piece.piece == PieceType::King)
+                    .map(|king| Outcome::VariantWin { winner: king.color })
+            }),
+            Variant::ThreeCheck => [Color::White, Color::Black]
+                .into_iter()
+                .find(|color| self.get_checks_given(*color) >= CHECKS_TO_WIN)
+                .map(|winner| Outcome::VariantWin { winner }),
+            // capturing the king wins, like exploding it does in Atomic
+            Variant::Atomic | Variant::Duck => self.atomic_outcome(),
+            Variant::Horde => self.horde_outcome(),
+            Variant::RacingKings => self.racing_kings_outcome(),
+            // having no legal moves wins, which is found like mate and stalemate
+            Variant::Crazyhouse | Variant::Antichess => None,
+        }
+    }
+
+    // called at the end of do_move(), after the turn has passed to the other player
+    pub(crate) fn variant_after_move(&mut self, mv: Move, capture: bool) {
+        match self.variant {
+            Variant::ThreeCheck if self.king_attacked(self.active_color) => {
+                self.checks[color_index(self.active_color.opposite())] += 1;
+            }
+            Variant::Atomic if capture => self.explode(mv.to),
+            // the turn only passes once the duck has been moved as well
+            Variant::Duck if self.variant_outcome().is_none() => {
+                self.active_color = self.active_color.opposite();
+                self.duck_to_move = true;
+            }
+            _ => {}
+        }
+    }
+}
+
+pub(crate) fn color_index(color: Color) -> usize {
+    match color {
+        Color::White => 0,
+        Color::Black => 1,
+    }
+}
+
+// checks the perft numbers of positions of a variant, given from depth 1 up
+#[cfg(test)]
+pub(crate) fn check_perft(variant: Variant, positions: &[(&str, &[u64])]) {
+    for (fen, expected) in positions {
+        let mut game = Game::from_fen_variant(fen, variant).unwrap();
+        for (depth, nodes) in expected.iter().enumerate() {
+            assert_eq!(crate::perft(&mut game, depth as u32 + 1), *nodes, "{}", fen);
+        }
+    }
+}
+
+// plays moves written in UCI notation, which must be legal
+#[cfg(test)]
+pub(crate) fn play(game: &mut Game, moves: &[&str]) {
+    for text in moves {
+        let mv = game.parse_uci_move(text).unwrap();
+        game.apply_move(mv);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::{search, Limits, MATE};
+    use crate::GameState;
+
+    #[test]
+    fn names() {
+        for variant in Variant::ALL {
+            assert_eq!(Variant::from_name(variant.name()), Some(variant));
+        }
+        assert_eq!(
+            Variant::from_name("King of the Hill"),
+            Some(Variant::KingOfTheHill)
+        );
+        assert_eq!(Variant::from_name("shogi"), None);
+        assert_eq!(Game::new().get_variant(), Variant::Standard);
+    }
+
+    #[test]
+    fn king_of_the_hill() {
+        let mut game = Game::new_variant(Variant::KingOfTheHill);
+        assert_eq!(game.to_fen(), STARTING_FEN);
+        play(&mut game, &["e2e4", "a7a6", "e1e2", "a6a5", "e2d3", "a5a4"]);
+        assert_eq!(game.get_outcome(), None);
+
+        play(&mut game, &["d3d4"]);
+        assert_eq!(game.get_game_state(), GameState::GameOver);
+        assert_eq!(
+            game.get_outcome(),
+            Some(Outcome::VariantWin {
+                winner: Color::White
+            })
+        );
+        assert!(game.get_legal_moves().is_empty());
+
+        // the same moves are just moves in normal chess
+        let mut game = Game::new();
+        play(
+            &mut game,
+            &["e2e4", "a7a6", "e1e2", "a6a5", "e2d3", "a5a4", "d3d4"],
+        );
+        assert_eq!(game.get_outcome(), None);
+    }
+
+    #[test]
+    fn three_check() {
+        let mut game = Game::new_variant(Variant::ThreeCheck);
+        play(&mut game, &["e2e4", "d7d5", "f1b5"]);
+        assert_eq!(game.get_checks_given(Color::White), 1);
+        assert_eq!(
+            game.to_fen(),
+            "rnbqkbnr/ppp1pppp/8/1B1p4/4P3/8/PPPP1PPP/RNBQK1NR b KQkq - 2+3 1 2"
+        );
+
+        // the checks are part of the position, for repetitions and the transposition table
+        let other = Game::from_fen_variant(
+            "rnbqkbnr/ppp1pppp/8/1B1p4/4P3/8/PPPP1PPP/RNBQK1NR b KQkq - 0 1",
+            Variant::ThreeCheck,
+        )
+        .unwrap();
+        assert_ne!(other.get_zobrist_hash(), game.get_zobrist_hash());
+
+        play(&mut game, &["c7c6", "b5c6", "b7c6"]);
+        game.undo_move();
+        assert_eq!(game.get_checks_given(Color::White), 2);
+        game.undo_move();
+        assert_eq!(game.get_checks_given(Color::White), 1);
+
+        let mut game = Game::from_fen_variant(
+            "rnbqkbnr/ppp2ppp/8/3pp3/4P3/5Q2/PPPP1PPP/RNB1KBNR w KQkq - 1+3 0 3",
+            Variant::ThreeCheck,
+        )
+        .unwrap();
+        play(&mut game, &["f3f7"]);
+        assert_eq!(
+            game.get_outcome(),
+            Some(Outcome::VariantWin {
+                winner: Color::White
+            })
+        );
+    }
+
+    #[test]
+    fn search_plays_for_the_variant_win() {
+        // the king can step onto the hill, in normal chess nothing is going on
+        let fen = "7k/8/8/8/8/2K5/8/8 w - - 0 1";
+        let game = Game::from_fen_variant(fen, Variant::KingOfTheHill).unwrap();
+        let limits = Limits {
+            depth: Some(3),
+            ..Limits::default()
+        };
+        let result = search(&game, &limits);
+        assert_eq!(result.score, MATE - 1);
+        assert_eq!(result.best_move.map(|mv| mv.to), Some((4, 3)));
+
+        // and a check that wins Three-Check is found like a mate
+        let game = Game::from_fen_variant(
+            "rnbqkbnr/ppp2ppp/8/3pp3/4P3/5Q2/PPPP1PPP/RNB1KBNR w KQkq - 1+3 0 3",
+            Variant::ThreeCheck,
+        )
+        .unwrap();
+        let result = search(&game, &limits);
+        assert_eq!(result.score, MATE - 1);
+    }
+}
@@ -0,0 +1,94 @@
+// Racing Kings: both kings start on the first rank and the first to reach the eighth rank
+// wins. No move may give check, so kings are never in check. If the white king gets there
+// first, black has one more move to reach the eighth rank as well and draw, to make up for
+// white moving first.
+
+use crate::{Color, Game, Outcome, PieceType};
+
+impl<const WIDTH: usize, const HEIGHT: usize> Game<WIDTH, HEIGHT> {
+    pub(crate) fn racing_kings_outcome(&self) -> Option<Outcome> {
+        let at_goal = |color| self.find_king(color).is_some_and(|king| king.0 == 0);
+        match (at_goal(Color::White), at_goal(Color::Black)) {
+            (true, true) => Some(Outcome::VariantDraw),
+            (false, true) => Some(Outcome::VariantWin {
+                winner: Color::Black,
+            }),
+            (true, false) if self.active_color == Color::Black && self.black_can_catch_up() => None,
+            (true, false) => Some(Outcome::VariantWin {
+                winner: Color::White,
+            }),
+            (false, false) => None,
+        }
+    }
+
+    // true if the black king has a legal move to the eighth rank
+    fn black_can_catch_up(&self) -> bool {
+        self.pseudo_legal_moves().into_iter().any(|mv| {
+            mv.to.0 == 0
+                && self.is_piece(mv.from.0, mv.from.1, Color::Black, PieceType::King)
+                && self.is_legal(mv, Color::Black)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::variant::{check_perft, Variant};
+    use crate::{Color, Game, GameState, Outcome};
+
+    #[test]
+    fn no_checks() {
+        // the rook could give check along the rank or the file, neither is allowed
+        let game =
+            Game::from_fen_variant("8/8/8/8/8/3k4/8/R6K w - - 0 1", Variant::RacingKings).unwrap();
+        assert!(game.parse_uci_move("a1d1").is_none());
+        assert!(game.parse_uci_move("a1a3").is_none());
+        assert!(game.parse_uci_move("a1a2").is_some());
+    }
+
+    #[test]
+    fn black_may_equalise() {
+        let mut game =
+            Game::from_fen_variant("8/5k1K/8/8/8/8/8/8 w - - 0 1", Variant::RacingKings).unwrap();
+        game.apply_move(game.parse_uci_move("h7h8").unwrap());
+        assert_eq!(game.get_outcome(), None);
+
+        let mut draw = game.clone();
+        draw.apply_move(draw.parse_uci_move("f7f8").unwrap());
+        assert_eq!(draw.get_outcome(), Some(Outcome::VariantDraw));
+
+        game.apply_move(game.parse_uci_move("f7e7").unwrap());
+        assert_eq!(game.get_game_state(), GameState::GameOver);
+        assert_eq!(
+            game.get_outcome(),
+            Some(Outcome::VariantWin {
+                winner: Color::White
+            })
+        );
+
+        // the white king wins at once when black can't reach the eighth rank
+        let mut game =
+            Game::from_fen_variant("8/7K/8/8/3k4/8/8/8 w - - 0 1", Variant::RacingKings).unwrap();
+        game.apply_move(game.parse_uci_move("h7h8").unwrap());
+        assert_eq!(
+            game.get_outcome(),
+            Some(Outcome::VariantWin {
+                winner: Color::White
+            })
+        );
+    }
+
+    #[test]
+    fn perft_reference_positions() {
+        // from the python-chess test suite
+        let game = Game::new_variant(Variant::RacingKings);
+        assert_eq!(game.to_fen(), "8/8/8/8/8/8/krbnNBRK/qrbnNBRQ w - - 0 1");
+        check_perft(
+            Variant::RacingKings,
+            &[
+                ("8/8/8/8/8/8/krbnNBRK/qrbnNBRQ w - - 0 1", &[21, 421, 11264]),
+                ("4brn1/2K2k2/8/8/8/8/8/8 w - - 0 1", &[6, 33, 178, 3151]),
+            ],
+        );
+    }
+}
@@ -0,0 +1,485 @@
+//! The engine side of the Chess Engine Communication Protocol (CECP, also called the xboard
+//! protocol), version 2, for tools that don't speak UCI.
+//!
+//! Unlike UCI the engine keeps track of the game itself: the GUI sends the moves one at a
+//! time and the engine answers with its own moves when it is its turn. Moves are written in
+//! coordinate notation ("e2e4", "e7e8q"), the same as for UCI. See the
+//! [protocol description](https://www.gnu.org/software/xboard/engine-intf.html).
+//!
+//! The engine thinks on the thread reading the input, so commands sent while it is thinking
+//! (such as `?`) are only read once it has moved.
+//!
+//! # Examples
+//! ```
+//! let input = "xboard\nprotover 2\nnew\nsd 2\nusermove e2e4\n";
+//! let output = vprytz_chess::xboard::run(input.as_bytes(), Vec::new());
+//! let output = String::from_utf8(output).unwrap();
+//! assert!(output.contains("feature done=1"));
+//! assert!(output.lines().last().unwrap().starts_with("move "));
+//! ```
+
+use std::io::{BufRead, Write};
+use std::time::{Duration, Instant};
+
+use crate::search::{mate_in, Limits, SearchResult, Searcher};
+use crate::timeman::TimeManager;
+use crate::uci::ENGINE_NAME;
+use crate::variant::Variant;
+use crate::{parse_square, Color, Game, Outcome, BOARD_SIZE};
+
+// used when the GUI never said how long to think
+const DEFAULT_MOVE_TIME: Duration = Duration::from_secs(5);
+
+// mate scores are sent as this plus the number of moves to mate
+const MATE_SCORE: i32 = 100_000;
+
+/// Reads CECP commands from `input` and writes the answers to `output`, until the input ends
+/// or `quit` is received. Returns the output, which is handy when writing to a buffer.
+pub fn run<R: BufRead, W: Write>(input: R, output: W) -> W {
+    let mut engine = Engine::new(output);
+
+    for line in input.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        if !engine.command(&line) {
+            break;
+        }
+    }
+
+    engine.output
+}
+
+struct Engine<W> {
+    output: W,
+    game: Game,
+    // from the variant command, standard chess after new
+    variant: Variant,
+    searcher: Searcher,
+    // the color the engine plays, None in force mode
+    engine_color: Option<Color>,
+    // send thinking output while searching
+    post: bool,
+    depth: Option<u32>,
+    // fixed time per move, from st
+    move_time: Option<Duration>,
+    // moves per time control (0 for the whole game) and increment, from level
+    moves_per_session: u32,
+    increment: Duration,
+    // the engine's clock, from time
+    time_left: Option<Duration>,
+}
+
+impl<W: Write> Engine<W> {
+    fn new(output: W) -> Engine<W> {
+        Engine {
+            output,
+            game: Game::new(),
+            variant: Variant::Standard,
+            searcher: Searcher::new(),
+            engine_color: Some(Color::Black),
+            post: false,
+            depth: None,
+            move_time: None,
+            moves_per_session: 0,
+            increment: Duration::ZERO,
+            time_left: None,
+        }
+    }
+
+    fn send(&mut self, line: &str) {
+        send_line(&mut self.output, line);
+    }
+
+    // handles one line of input, returning false on quit
+    fn command(&mut self, line: &str) -> bool {
+        let line = line.trim();
+        let (command, args) = match line.split_once(' ') {
+            Some((command, args)) => (command, args.trim()),
+            None => (line, ""),
+        };
+
+        match command {
+            "" | "xboard" | "accepted" | "rejected" | "random" | "hard" | "easy" | "computer"
+            | "name" | "otim" | "?" => {}
+            "protover" => {
+                self.send("feature done=0");
+                self.send(&format!(
+                    "feature myname=\"{}\" ping=1 setboard=1 usermove=1 playother=1 san=0 time=1 draw=0 sigint=0 sigterm=0 reuse=1 analyze=0 colors=0 memory=1 smp=1 variants=\"{}\"",
+                    ENGINE_NAME,
+                    Variant::ALL.map(variant_name).join(",")
+                ));
+                self.send("feature done=1");
+            }
+            "new" => {
+                self.variant = Variant::Standard;
+                self.game = Game::new();
+                self.searcher.table().clear();
+                self.engine_color = Some(Color::Black);
+                self.depth = None;
+            }
+            "force" => self.engine_color = None,
+            "go" => {
+                self.engine_color = Some(self.game.get_active_color());
+                self.think();
+            }
+            "playother" => self.engine_color = Some(self.game.get_active_color().opposite()),
+            "usermove" => self.user_move(args),
+            "variant" => match parse_variant(args) {
+                Some(variant) => {
+                    self.variant = variant;
+                    self.game = Game::new_variant(variant);
+                }
+                None => self.send(&format!("Error (unsupported variant): {}", args)),
+            },
+            "setboard" => match Game::from_fen_variant(args, self.variant) {
+                Some(game) => self.game = game,
+                None => self.send("tellusererror Illegal position"),
+            },
+            "undo" => {
+                self.game.undo_move();
+            }
+            "remove" => {
+                self.game.undo_move();
+                self.game.undo_move();
+            }
+            "level" => match parse_level(args) {
+                Some((moves, base, increment)) => {
+                    self.moves_per_session = moves;
+                    self.time_left = Some(base);
+                    self.increment = increment;
+                    self.move_time = None;
+                }
+                None => self.send(&format!("Error (bad level): {}", args)),
+            },
+            "st" => match args.parse::<f64>() {
+                Ok(seconds) if seconds > 0.0 => {
+                    self.move_time = Some(Duration::from_secs_f64(seconds))
+                }
+                _ => self.send(&format!("Error (bad time): {}", args)),
+            },
+            "sd" => match args.parse::<u32>() {
+                Ok(depth) => self.depth = Some(depth.max(1)),
+                Err(_) => self.send(&format!("Error (bad depth): {}", args)),
+            },
+            "time" => match args.parse::<u64>() {
+                // centiseconds
+                Ok(time) => self.time_left = Some(Duration::from_millis(time * 10)),
+                Err(_) => self.send(&format!("Error (bad time): {}", args)),
+            },
+            "memory" => {
+                if let Ok(megabytes) = args.parse::<usize>() {
+                    self.searcher.set_hash_size(megabytes.max(1));
+                }
+            }
+            "cores" => {
+                if let Ok(threads) = args.parse::<usize>() {
+                    self.searcher.set_threads(threads.max(1));
+                }
+            }
+            "post" => self.post = true,
+            "nopost" => self.post = false,
+            "ping" => self.send(&format!("pong {}", args)),
+            "result" => self.engine_color = None,
+            "quit" => return false,
+            // without usermove=1 the GUI sends moves on their own
+            _ if looks_like_move(command) => self.user_move(command),
+            _ => self.send(&format!("Error (unknown command): {}", command)),
+        }
+        true
+    }
+
+    fn user_move(&mut self, text: &str) {
+        let mv = match self.game.parse_uci_move(text) {
+            Some(mv) => mv,
+            None => {
+                self.send(&format!("Illegal move: {}", text));
+                return;
+            }
+        };
+        self.game.apply_move(mv);
+
+        if !self.send_result() && self.engine_color == Some(self.game.get_active_color()) {
+            self.think();
+        }
+    }
+
+    // searches and plays the engine's move
+    fn think(&mut self) {
+        if self.send_result() {
+            return;
+        }
+
+        let mut limits = Limits {
+            depth: self.depth,
+            time: self.move_time,
+            ..Limits::default()
+        };
+        if limits.time.is_none() {
+            match self.time_left {
+                Some(time) => {
+                    limits.manager = Some(TimeManager::new(
+                        &self.game,
+                        time,
+                        self.increment,
+                        self.moves_to_go(),
+                    ))
+                }
+                None if self.depth.is_none() => limits.time = Some(DEFAULT_MOVE_TIME),
+                None => {}
+            }
+        }
+
+        let start = Instant::now();
+        let game = self.game.clone();
+        let post = self.post;
+        let output = &mut self.output;
+        let result = self.searcher.search(&game, &limits, |result| {
+            if post {
+                send_line(output, &thinking_line(&game, result, start.elapsed()));
+            }
+        });
+
+        let mv = match result.best_move {
+            Some(mv) => mv,
+            None => return,
+        };
+        let text = self.game.move_to_uci(mv);
+        self.game.apply_move(mv);
+        self.send(&format!("move {}", text));
+        self.send_result();
+    }
+
+    // moves left until the next time control, counting from the start of the game
+    fn moves_to_go(&self) -> Option<u32> {
+        if self.moves_per_session == 0 {
+            return None;
+        }
+        let played = self.game.fullmove_number.saturating_sub(1);
+        Some(self.moves_per_session - played % self.moves_per_session)
+    }
+
+    // tells the GUI if the game has ended, returning true if it has
+    fn send_result(&mut self) -> bool {
+        let result = match self.game.get_outcome() {
+            None => return false,
+            Some(Outcome::Checkmate {
+                winner: Color::White,
+            }) => "1-0 {White mates}",
+            Some(Outcome::Checkmate {
+                winner: Color::Black,
+            }) => "0-1 {Black mates}",
+            Some(Outcome::Stalemate) => "1/2-1/2 {Stalemate}",
+            Some(outcome) => match outcome.winner() {
+                Some(Color::White) => "1-0 {White wins}",
+                Some(Color::Black) => "0-1 {Black wins}",
+                None => "1/2-1/2 {Draw}",
+            },
+        };
+        self.send(result);
+        true
+    }
+}
+
+// xboard calls standard chess "normal"
+fn variant_name(variant: Variant) -> &'static str {
+    match variant {
+        Variant::Standard => "normal",
+        Variant::Antichess => "giveaway",
+        _ => variant.name(),
+    }
+}
+
+// the names xboard uses, and the ones of Variant::from_name()
+fn parse_variant(name: &str) -> Option<Variant> {
+    match name {
+        "suicide" => Some(Variant::Antichess),
+        _ => Variant::from_name(name),
+    }
+}
+
+// level <moves per session> <base time> <increment>, the base time in minutes or minutes:seconds
+// and the increment in seconds
+fn parse_level(args: &str) -> Option<(u32, Duration, Duration)> {
+    let fields: Vec<&str> = args.split_whitespace().collect();
+    if fields.len() != 3 {
+        return None;
+    }
+
+    let moves = fields[0].parse().ok()?;
+    let base = match fields[1].split_once(':') {
+        Some((minutes, seconds)) => {
+            minutes.parse::<u64>().ok()? * 60 + seconds.parse::<u64>().ok()?
+        }
+        None => fields[1].parse::<u64>().ok()? * 60,
+    };
+    let increment = fields[2].parse::<f64>().ok()?;
+    if increment < 0.0 {
+        return None;
+    }
+
+    Some((
+        moves,
+        Duration::from_secs(base),
+        Duration::from_secs_f64(increment),
+    ))
+}
+
+// e.g. "e2e4" or "e7e8q"
+fn looks_like_move(text: &str) -> bool {
+    (text.len() == 4 || text.len() == 5)
+        && text.is_ascii()
+        && parse_square(&text[0..2], BOARD_SIZE, BOARD_SIZE).is_some()
+}
+
+// writes a line and flushes it, also used while searching, when the engine itself is borrowed
+fn send_line<W: Write>(output: &mut W, line: &str) {
+    // there is nobody to tell if the GUI went away
+    let _ = writeln!(output, "{}", line);
+    let _ = output.flush();
+}
+
+// <depth> <score> <time in centiseconds> <nodes> <pv>
+fn thinking_line(game: &Game, result: &SearchResult, elapsed: Duration) -> String {
+    let score = match mate_in(result.score) {
+        Some(moves) if moves > 0 => MATE_SCORE + moves,
+        Some(moves) => -MATE_SCORE + moves,
+        None => result.score,
+    };
+
+    let mut game = game.clone();
+    let pv: Vec<String> = result
+        .pv
+        .iter()
+        .map(|mv| {
+            let text = game.move_to_uci(*mv);
+            game.do_move(*mv);
+            text
+        })
+        .collect();
+
+    format!(
+        "{} {} {} {} {}",
+        result.depth,
+        score,
+        elapsed.as_millis() / 10,
+        result.nodes,
+        pv.join(" ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(input: &str) -> Vec<String> {
+        let output = run(input.as_bytes(), Vec::new());
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| line.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn feature_negotiation() {
+        let output = session("xboard\nprotover 2\nping 7\n");
+
+        assert_eq!(output[0], "feature done=0");
+        assert!(output[1].contains("usermove=1") && output[1].contains("setboard=1"));
+        assert_eq!(output[2], "feature done=1");
+        assert_eq!(output[3], "pong 7");
+    }
+
+    #[test]
+    fn plays_black_after_new() {
+        let output = session("new\nsd 2\nusermove e2e4\n");
+
+        assert_eq!(output.len(), 1);
+        assert!(output[0].starts_with("move "));
+    }
+
+    #[test]
+    fn force_mode_only_follows_moves() {
+        let output = session("new\nforce\nusermove e2e4\ne7e5\nusermove e1e2\nusermove e1e3\n");
+
+        // bare moves work as well, and nothing is played back in force mode
+        assert_eq!(output, vec!["Illegal move: e1e3"]);
+    }
+
+    #[test]
+    fn setboard_go_and_result() {
+        let output = session(
+            "new\nforce\nsetboard rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq g3 0 2\npost\nsd 2\ngo\n",
+        );
+
+        // thinking output first, with mate in one sent as 100001
+        assert!(output[0].starts_with("1 100001 "));
+        assert!(output[0].ends_with(" d8h4"));
+        assert_eq!(output[output.len() - 2], "move d8h4");
+        assert_eq!(output[output.len() - 1], "0-1 {Black mates}");
+    }
+
+    #[test]
+    fn variants() {
+        let output = session("protover 2\n");
+        assert!(output[1].contains("variants=\"normal,kingofthehill,3check,"));
+        assert!(output[1].contains(",giveaway,"));
+        assert!(!output[1].contains("antichess"));
+
+        // the king walks onto the hill
+        let output = session(
+            "new\nvariant kingofthehill\nforce\nsetboard 7k/8/8/8/8/2K5/8/8 w - - 0 1\nsd 2\ngo\n",
+        );
+        assert_eq!(output[output.len() - 2], "move c3d4");
+        assert_eq!(output[output.len() - 1], "1-0 {White wins}");
+
+        // the king is an ordinary piece, and black wins by losing it
+        for name in ["giveaway", "suicide"] {
+            let output = session(&format!(
+                "new\nvariant {}\nforce\nsetboard 8/8/8/8/8/8/1k6/K7 w - - 0 1\nsd 2\ngo\n",
+                name
+            ));
+            assert_eq!(output, vec!["move a1b2", "0-1 {Black wins}"]);
+        }
+
+        let output = session("new\nvariant shogi\n");
+        assert_eq!(output, vec!["Error (unsupported variant): shogi"]);
+    }
+
+    #[test]
+    fn undo_and_remove() {
+        let output = session(
+            "new\nforce\ne2e4\ne7e5\ng1f3\nundo\nusermove g1f3\nremove\nusermove g1f3\nusermove e7e5\nsetboard 8/8/8 w - - 0 1\nfoo\n",
+        );
+
+        assert_eq!(
+            output,
+            vec![
+                "Illegal move: g1f3",
+                "tellusererror Illegal position",
+                "Error (unknown command): foo",
+            ]
+        );
+    }
+
+    #[test]
+    fn level() {
+        assert_eq!(
+            parse_level("40 5 0"),
+            Some((40, Duration::from_secs(300), Duration::ZERO))
+        );
+        assert_eq!(
+            parse_level("0 2:30 1.5"),
+            Some((0, Duration::from_secs(150), Duration::from_millis(1500)))
+        );
+        assert_eq!(parse_level("0 2"), None);
+
+        let mut engine = Engine::new(Vec::new());
+        engine.command("level 40 5 0");
+        engine.command("time 6000");
+        assert_eq!(engine.moves_to_go(), Some(40));
+        assert_eq!(engine.time_left, Some(Duration::from_secs(60)));
+    }
+}
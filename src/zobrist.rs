@@ -0,0 +1,125 @@
+//! Zobrist hashing of positions, used for the transposition table and to find repetitions.
+//!
+//! Instead of a table of random numbers the keys are made by mixing the index of the
+//! feature (piece on a square, en passant file, ...) with splitmix64, which gives the same
+//! well spread out numbers without having to store or initialise anything.
+
+use crate::variant::Pocket;
+use crate::{Color, Game, PieceType};
+
+// the different kinds of things that are hashed, kept apart so that their keys never collide
+const PIECE_FEATURE: u64 = 0;
+const CASTLING_FEATURE: u64 = 1;
+const EN_PASSANT_FEATURE: u64 = 2;
+const SIDE_FEATURE: u64 = 3;
+const CHECKS_FEATURE: u64 = 4;
+const POCKET_FEATURE: u64 = 5;
+const PROMOTED_FEATURE: u64 = 6;
+
+// splitmix64, see https://prng.di.unimi.it/splitmix64.c
+pub(crate) fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+fn key(feature: u64, index: u64) -> u64 {
+    mix((index << 3) | feature)
+}
+
+fn piece_index(piece: PieceType) -> u64 {
+    match piece {
+        PieceType::King => 0,
+        PieceType::Queen => 1,
+        PieceType::Rook => 2,
+        PieceType::Bishop => 3,
+        PieceType::Knight => 4,
+        PieceType::Pawn => 5,
+        PieceType::Archbishop => 6,
+        PieceType::Chancellor => 7,
+        PieceType::Amazon => 8,
+        PieceType::Camel => 9,
+        PieceType::Grasshopper => 10,
+        PieceType::Duck => 11,
+        // the lowercase letters a to z, as 1 to 26
+        PieceType::Custom(letter) => 12 + (letter as u64 & 0x1f),
+    }
+}
+
+// hash the whole position from scratch
+pub(crate) fn hash<const WIDTH: usize, const HEIGHT: usize>(game: &Game<WIDTH, HEIGHT>) -> u64 {
+    let mut hash = 0;
+
+    for row in 0..HEIGHT {
+        for col in 0..WIDTH {
+            let piece = match game.board[row][col] {
+                Some(piece) => piece,
+                None => continue,
+            };
+            let square = (row * WIDTH + col) as u64;
+            let color = match piece.color {
+                Color::White => 0,
+                Color::Black => 1,
+            };
+            hash ^= key(
+                PIECE_FEATURE,
+                (square * 128) | (piece_index(piece.piece) << 1) | color,
+            );
+            if game.promoted[row][col] {
+                hash ^= key(PROMOTED_FEATURE, square);
+            }
+
+            // an untouched rook next to an untouched king is a castling right
+            if piece.piece == PieceType::Rook
+                && piece.untouched
+                && king_untouched(game, row, piece.color)
+            {
+                hash ^= key(CASTLING_FEATURE, square);
+            }
+        }
+    }
+
+    if let Some((_, col)) = game.en_passant {
+        hash ^= key(EN_PASSANT_FEATURE, col as u64);
+    }
+    if game.active_color == Color::Black {
+        hash ^= key(SIDE_FEATURE, 0);
+    }
+    // the duck still has to be moved in Duck chess
+    if game.duck_to_move {
+        hash ^= key(SIDE_FEATURE, 1);
+    }
+
+    // checks given so far in Three-Check
+    for (color, checks) in game.checks.iter().enumerate() {
+        if *checks > 0 {
+            hash ^= key(CHECKS_FEATURE, (*checks as u64) << 1 | color as u64);
+        }
+    }
+
+    // pieces in hand in Crazyhouse
+    for (color, pocket) in game.pockets.iter().enumerate() {
+        for piece in Pocket::PIECES {
+            let count = pocket.count(piece) as u64;
+            if count > 0 {
+                hash ^= key(
+                    POCKET_FEATURE,
+                    (count << 4) | (piece_index(piece) << 1) | color as u64,
+                );
+            }
+        }
+    }
+
+    hash
+}
+
+fn king_untouched<const WIDTH: usize, const HEIGHT: usize>(
+    game: &Game<WIDTH, HEIGHT>,
+    row: usize,
+    color: Color,
+) -> bool {
+    game.board[row].iter().any(|square| {
+        matches!(square, Some(piece) if piece.piece == PieceType::King && piece.color == color && piece.untouched)
+    })
+}
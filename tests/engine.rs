@@ -0,0 +1,39 @@
+use std::process::Command;
+
+use vprytz_chess::engine::{Engine, Score};
+use vprytz_chess::search::Limits;
+use vprytz_chess::Game;
+
+// our own uci binary stands in for an external engine
+fn engine() -> Engine {
+    Engine::start(Command::new(env!("CARGO_BIN_EXE_uci"))).unwrap()
+}
+
+#[test]
+fn analyses_a_game() {
+    let mut engine = engine();
+    assert_eq!(engine.name(), Some("vprytz-chess"));
+    assert!(engine.options().contains(&"MultiPV".to_string()));
+    engine.set_option("MultiPV", "2").unwrap();
+    engine.new_game().unwrap();
+
+    let mut game = Game::new();
+    for (from, to) in [("F2", "F3"), ("E7", "E5"), ("G2", "G4")] {
+        game.make_move(from.to_string(), to.to_string());
+    }
+
+    let limits = Limits {
+        depth: Some(2),
+        ..Limits::default()
+    };
+    let analysis = engine.go(&game, &limits).unwrap();
+
+    assert_eq!(analysis.best_move.unwrap().to_string(), "d8h4");
+    let last = analysis.info.last().unwrap();
+    assert_eq!(last.depth, Some(2));
+    assert_eq!(last.multipv, Some(2));
+    assert!(analysis
+        .info
+        .iter()
+        .any(|info| info.multipv == Some(1) && info.score == Some(Score::Mate(1))));
+}
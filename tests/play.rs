@@ -0,0 +1,56 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+// pipes a scripted session into the play binary and returns what it printed
+fn session(args: &[&str], input: &str) -> String {
+    let mut play = Command::new(env!("CARGO_BIN_EXE_play"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    play.stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = play.wait_with_output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn save_and_load() {
+    let file = std::env::temp_dir().join(format!("play-{}.pgn", std::process::id()));
+    let file = file.to_str().unwrap();
+
+    let output = session(&[], &format!("e4\ne5\nBc4\nresign\npgn save {}\n", file));
+    assert!(output.contains("1-0 (White wins by resignation)"));
+    let pgn = std::fs::read_to_string(file).unwrap();
+    assert!(pgn.contains("[Result \"1-0\"]"));
+    assert!(pgn.ends_with("1. e4 e5 2. Bc4 1-0\n"));
+
+    // the loaded game goes on from where it was saved
+    let output = session(&[], &format!("load {}\nNc6\nQh5\nNf6\nQxf7\nfen\n", file));
+    std::fs::remove_file(file).unwrap();
+    assert!(output.contains("1-0 (White wins by checkmate)"));
+    assert!(
+        output.ends_with("r1bqkb1r/pppp1Qpp/2n2n2/4p3/2B1P3/8/PPPP1PPP/RNB1K1NR b KQkq - 0 4\n")
+    );
+}
+
+#[test]
+fn engine_plays_black() {
+    let output = session(&["--engine", "black", "--depth", "2"], "e4\nd4\nquit\ne5\n");
+    assert_eq!(output.matches(" plays ").count(), 2);
+    assert!(!output.contains("Unknown"));
+}
+
+#[test]
+fn bad_arguments() {
+    let output = Command::new(env!("CARGO_BIN_EXE_play"))
+        .args(["--engine", "purple"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
@@ -0,0 +1,58 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+// pipes a scripted session into the engine binary and returns what it answered
+fn session(input: &str) -> String {
+    let mut engine = Command::new(env!("CARGO_BIN_EXE_uci"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    engine
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = engine.wait_with_output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn scripted_game() {
+    let output =
+        session("uci\nisready\nucinewgame\nposition startpos moves e2e4 e7e5 g1f3\ngo depth 3\n");
+    let lines: Vec<&str> = output.lines().collect();
+
+    assert!(lines.contains(&"uciok"));
+    assert!(lines.contains(&"readyok"));
+    assert!(lines.iter().any(|line| line.starts_with("info depth 3 ")));
+    assert!(lines.last().unwrap().starts_with("bestmove "));
+}
+
+#[test]
+fn quit_stops_the_search() {
+    let output = session("position startpos\ngo infinite\nquit\nisready\n");
+
+    // the best move is still sent, but nothing after quit is read
+    assert!(output.lines().last().unwrap().starts_with("bestmove "));
+    assert!(!output.contains("readyok"));
+}
+
+#[test]
+fn new_position_stops_an_infinite_search() {
+    let output =
+        session("position startpos\ngo infinite\nposition startpos moves e2e4\ngo depth 2\n");
+    let lines: Vec<&str> = output.lines().collect();
+
+    // one best move for the infinite search and one for the second search, which is Black's
+    let best: Vec<&&str> = lines
+        .iter()
+        .filter(|line| line.starts_with("bestmove "))
+        .collect();
+    assert_eq!(best.len(), 2);
+    assert!(["a7", "b7", "c7", "d7", "e7", "f7", "g7", "h7", "b8", "g8"]
+        .iter()
+        .any(|from| best[1].starts_with(&format!("bestmove {}", from))));
+}